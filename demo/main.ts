@@ -1,7 +1,14 @@
-// route /user
+/**
+ * route /user
+ * A registered user of the shop.
+ */
 interface User {
   id: number;
+  /** Display name shown in the header. */
   name: string;
+  // @faker email
   email: string;
+  /** Age in years @min 18 @max 99 */
+  age?: number;
   active: boolean;
 }
@@ -0,0 +1,86 @@
+// One entity per kind of prop, plus an order that nests them the way a
+// real API does.
+
+/** route /scalars */
+interface Scalars {
+  active: boolean;
+  count: number;
+  /** @float precision=3 */
+  weight: number;
+  big: bigint;
+  createdAt: Date;
+}
+
+/** route /strings */
+interface Strings {
+  id: string;
+  name: string;
+  // @faker email
+  email: string;
+  description: string;
+}
+
+enum Status {
+  Pending = "pending",
+  Paid = "paid",
+  Shipped = "shipped",
+  Delivered = "delivered",
+  Cancelled = "cancelled",
+}
+
+enum Priority {
+  Low = 1,
+  Normal,
+  High,
+}
+
+/** route /enums */
+interface Enums {
+  status: Status;
+  priority: Priority;
+}
+
+/** route /arrays */
+interface Arrays {
+  tags: string[];
+  /** @minItems 5 @maxItems 10 */
+  scores: number[];
+  matrix: number[][];
+}
+
+interface Address {
+  street: string;
+  city: string;
+  country: string;
+  latitude: number;
+  longitude: number;
+}
+
+interface Customer {
+  id: number;
+  name: string;
+  // @faker email
+  email: string;
+  address: Address;
+}
+
+interface LineItem {
+  sku: string;
+  quantity: number;
+  price: number;
+  priority: Priority;
+}
+
+/** route /orders/{id} */
+interface Order {
+  id: number;
+  customer: Customer;
+  items: LineItem[];
+  status: Status;
+  note?: string;
+  placedAt: Date;
+  shipping?: Address;
+}
+
+/** route /orders */
+type Orders = Order[];
@@ -0,0 +1,44 @@
+//! Generation throughput: one object per kind of prop, a nested order, and
+//! order lists up to `?count=1000`. Run with `cargo bench --bench generate`.
+//!
+//! The target is at least 100k orders a second in lists of any size; a
+//! change that drops `list/Order/*` below that needs a reason.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ssg::bench::{entities, Generator};
+
+const SHOP: &str = include_str!("fixtures/shop.ts");
+
+fn objects(c: &mut Criterion) {
+    let entities = entities("shop.ts", SHOP);
+    let mut generator = Generator::default();
+    let mut group = c.benchmark_group("object");
+    for entity in entities.iter().filter(|entity| !entity.is_list()) {
+        group.bench_function(entity.name(), |b| {
+            b.iter(|| black_box(generator.object(entity)))
+        });
+    }
+    group.finish();
+}
+
+fn lists(c: &mut Criterion) {
+    let entities = entities("shop.ts", SHOP);
+    let orders = entities
+        .iter()
+        .find(|entity| entity.is_list())
+        .expect("the fixture declares a list");
+    let mut generator = Generator::default();
+    let mut group = c.benchmark_group("list");
+    for count in [10, 100, 1000] {
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::new(orders.name(), count),
+            &count,
+            |b, &count| b.iter(|| black_box(generator.list(orders, count))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, objects, lists);
+criterion_main!(benches);
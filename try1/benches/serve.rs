@@ -0,0 +1,139 @@
+//! End-to-end requests against a running `ssg` over one keep-alive
+//! connection, so middleware, routing and serialization are all counted.
+
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+const SHOP: &str = include_str!("fixtures/shop.ts");
+
+/// The `ssg` binary serving the fixture from a scratch directory, stopped
+/// on drop.
+struct Server {
+    child: Child,
+    dir: PathBuf,
+    port: u16,
+}
+
+impl Server {
+    fn start() -> Server {
+        let dir = std::env::temp_dir().join(format!("ssg-bench-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("shop.ts"), SHOP).unwrap();
+        let port = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+            .port();
+        let child = Command::new(env!("CARGO_BIN_EXE_ssg"))
+            .args(["--port", &port.to_string(), "--seed", "0"])
+            .current_dir(&dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        let server = Server { child, dir, port };
+        let started = Instant::now();
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "ssg didn't start listening"
+            );
+            thread::sleep(Duration::from_millis(20));
+        }
+        server
+    }
+
+    fn connect(&self) -> Client {
+        let stream = TcpStream::connect(("127.0.0.1", self.port)).unwrap();
+        stream.set_nodelay(true).unwrap();
+        Client {
+            writer: stream.try_clone().unwrap(),
+            reader: BufReader::new(stream),
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+struct Client {
+    writer: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl Client {
+    /// The body of a GET, which must succeed.
+    fn get(&mut self, path: &str) -> Vec<u8> {
+        write!(self.writer, "GET {path} HTTP/1.1\r\nHost: bench\r\n\r\n").unwrap();
+        let mut line = String::new();
+        self.reader.read_line(&mut line).unwrap();
+        assert!(line.starts_with("HTTP/1.1 200"), "GET {path}: {line}");
+        let mut length = None;
+        loop {
+            line.clear();
+            self.reader.read_line(&mut line).unwrap();
+            let header = line.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = Some(value.trim().parse().unwrap());
+                }
+            }
+        }
+        match length {
+            Some(length) => self.read(length),
+            None => self.read_chunked(),
+        }
+    }
+
+    fn read(&mut self, length: usize) -> Vec<u8> {
+        let mut body = vec![0; length];
+        self.reader.read_exact(&mut body).unwrap();
+        body
+    }
+
+    /// A `Transfer-Encoding: chunked` body, which streamed lists use.
+    fn read_chunked(&mut self) -> Vec<u8> {
+        let mut body = Vec::new();
+        let mut line = String::new();
+        loop {
+            line.clear();
+            self.reader.read_line(&mut line).unwrap();
+            let size = usize::from_str_radix(line.trim_end(), 16).unwrap();
+            body.extend(self.read(size));
+            line.clear();
+            self.reader.read_line(&mut line).unwrap();
+            if size == 0 {
+                return body;
+            }
+        }
+    }
+}
+
+fn requests(c: &mut Criterion) {
+    let server = Server::start();
+    let mut client = server.connect();
+    let mut group = c.benchmark_group("request");
+    for path in ["/scalars", "/orders/1", "/orders", "/orders?count=100"] {
+        group.bench_function(path, |b| b.iter(|| black_box(client.get(path))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, requests);
+criterion_main!(benches);
@@ -0,0 +1,190 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    middleware::{from_fn, Next},
+    web, Error, HttpResponse,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    assertion, assets, batch,
+    cli::Config,
+    date, entity_docs, har, metrics,
+    openapi::{self, METHODS},
+    recording, route_groups, scenario, store, validation, Entity,
+};
+
+/// Registers the admin endpoints under the configured prefix, each behind
+/// `--admin-token` when one is set. Health and metrics are toggled on their
+/// own and never need the token, nor do assets, which `<img>` tags load.
+pub fn configure(cfg: &mut web::ServiceConfig, config: &Config) {
+    cfg.route(
+        &config.admin_path("assets/image/{size}"),
+        web::get().to(assets::image),
+    );
+    if config.health {
+        cfg.route(&config.admin_path("health"), web::get().to(health));
+    }
+    if config.metrics {
+        cfg.route(&config.admin_path("metrics"), web::get().to(metrics::serve));
+    }
+    if !config.admin {
+        return;
+    }
+
+    let admin = |name: &str| web::resource(config.admin_path(name)).wrap(from_fn(require_token));
+    cfg.service(admin("routes").route(web::get().to(routes)))
+        .service(admin("entities").route(web::get().to(entity_docs::page)))
+        .service(admin("config").route(web::get().to(show_config)))
+        .service(admin("batch").route(web::post().to(batch::run)))
+        .service(
+            admin("requests")
+                .route(web::get().to(recording::list))
+                .route(web::delete().to(recording::clear)),
+        )
+        .service(admin("har").route(web::get().to(har::export)))
+        .service(
+            admin("requests/assert")
+                .route(web::get().to(assertion::assert_query))
+                .route(web::post().to(assertion::assert_body)),
+        )
+        .service(admin("diff/{route:.*}").route(web::get().to(scenario::serve_diff)))
+        .service(
+            admin("violations")
+                .route(web::get().to(validation::list))
+                .route(web::delete().to(validation::clear)),
+        );
+    if config.stateful {
+        cfg.service(admin("reset").route(web::post().to(store::reset)));
+    }
+}
+
+/// Rejects requests without `Authorization: Bearer <--admin-token>`.
+async fn require_token(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let expected = req
+        .app_data::<web::Data<Config>>()
+        .and_then(|config| config.admin_token.clone());
+    let Some(expected) = expected else {
+        return Ok(next.call(req).await?.map_into_left_body());
+    };
+
+    let provided = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if provided.is_some_and(|token| constant_time_eq(token.as_bytes(), expected.as_bytes())) {
+        return Ok(next.call(req).await?.map_into_left_body());
+    }
+
+    let response = HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, "Bearer"))
+        .json(json!({ "error": "admin token required" }));
+    Ok(req.into_response(response).map_into_right_body())
+}
+
+/// Compares without returning early, so timing doesn't leak the token.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Up, and which `--instances` instance answered.
+async fn health(config: web::Data<Config>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({ "status": "ok", "instance": config.instance }))
+}
+
+/// Lists every logical route with the entities (versions) served under it.
+pub async fn routes(entities: web::Data<Vec<Entity>>, config: web::Data<Config>) -> HttpResponse {
+    let routes: Vec<Value> = route_groups(&entities)
+        .into_iter()
+        .map(|(route, group)| {
+            let entities: Vec<Value> = group
+                .iter()
+                .map(|entity| {
+                    let path = entity.served_route(config.version_prefix);
+                    let operations: Vec<Value> = METHODS
+                        .iter()
+                        .map(|method| {
+                            json!({
+                                "method": method.to_uppercase(),
+                                "operation_id": openapi::operation_id(entity, method, &path),
+                                "summary": openapi::summary(entity, method, &path),
+                            })
+                        })
+                        .collect();
+                    json!({
+                        "name": entity.name,
+                        "version": entity.version,
+                        "path": path,
+                        "description": openapi::operation_description(entity),
+                        "tags": entity.tags,
+                        "operations": operations,
+                    })
+                })
+                .collect();
+            json!({
+                "route": route,
+                "methods": METHODS.map(str::to_uppercase),
+                "entities": entities,
+            })
+        })
+        .collect();
+    HttpResponse::Ok().json(routes)
+}
+
+/// The effective settings, with the admin token redacted.
+async fn show_config(config: web::Data<Config>) -> HttpResponse {
+    HttpResponse::Ok().json(json!({
+        "max_body_bytes": config.max_body_bytes,
+        "fuzz": config.fuzz,
+        "seed": config.seed,
+        "quiet_404": config.quiet_404,
+        "bandwidth": config.bandwidth,
+        "version_prefix": config.version_prefix,
+        "optional_present_rate": config.optional_present_rate,
+        "request_history": config.request_history,
+        "record": config.record,
+        "ir": config.ir,
+        "replay": config.replay,
+        "replay_match_body": config.replay_match_body,
+        "replay_strict": config.replay_strict,
+        "max_file_size": config.max_file_size,
+        "lossy_utf8": config.lossy_utf8,
+        "bigint_as_string": config.bigint_as_string,
+        "admin_prefix": config.admin_prefix,
+        "admin_token": config.admin_token.as_ref().map(|_| "<redacted>"),
+        "auth_tokens": config
+            .auth_tokens
+            .iter()
+            .map(|token| json!({ "token": "<redacted>", "roles": token.roles }))
+            .collect::<Vec<_>>(),
+        "health": config.health,
+        "metrics": config.metrics,
+        "stateful": config.stateful,
+        "seed_count": config.seed_count,
+        "pretty": config.pretty,
+        "route_keywords": config.route_keywords,
+        "port": config.port,
+        "instances": config.instances,
+        "instance": config.instance,
+        "idempotency_ttl_secs": config.idempotency_ttl.as_secs_f64(),
+        "idempotency_keys": config.idempotency_keys,
+        "profile": config.profile,
+        "request_overrides": config.request_overrides,
+        "batch_limit": config.batch_limit,
+        "max_pool": config.max_pool,
+        "jitter_ms": config.jitter.as_secs_f64() * 1000.0,
+        "consistency_lag_secs": config.consistency_lag.as_secs_f64(),
+        "now": match config.clock {
+            date::Clock::Real => None,
+            clock => Some(date::format(clock.now())),
+        },
+        "locale": config.locale.name(),
+        "locale_from_header": config.locale_from_header,
+        "infer": config.infer,
+    }))
+}
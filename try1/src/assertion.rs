@@ -0,0 +1,377 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpResponse};
+use serde_json::{json, Value};
+
+use crate::recording::{RecordedRequest, Recorder};
+
+/// Most near misses reported back when an assertion fails.
+const MAX_NEAR_MISSES: usize = 10;
+
+/// A check against the recorded traffic: how many requests matching
+/// `method`/`path` (and the header and body matchers) must have been seen.
+#[derive(Debug, Default)]
+pub struct Assertion {
+    method: Option<String>,
+    /// Exact path, or a prefix when it ends in `*`.
+    path: Option<String>,
+    min: Option<usize>,
+    max: Option<usize>,
+    count: Option<usize>,
+    /// Header name and, optionally, the exact value it must have.
+    headers: Vec<(String, Option<String>)>,
+    /// JSON path into the body and the value found there.
+    body: Vec<(String, Value)>,
+}
+
+impl Assertion {
+    /// `?method=POST&path=/users&min=1&header=Authorization,X-Trace`
+    fn from_query(query: &HashMap<String, String>) -> Result<Self, String> {
+        let number = |key: &str| -> Result<Option<usize>, String> {
+            query
+                .get(key)
+                .map(|v| v.parse().map_err(|_| format!("`{key}` must be a number")))
+                .transpose()
+        };
+        Ok(Assertion {
+            method: query.get("method").cloned(),
+            path: query.get("path").cloned(),
+            min: number("min")?,
+            max: number("max")?,
+            count: number("count")?,
+            headers: query
+                .get("header")
+                .map(|h| {
+                    h.split(',')
+                        .map(str::trim)
+                        .filter(|h| !h.is_empty())
+                        .map(|h| (h.to_string(), None))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            body: Vec::new(),
+        })
+    }
+
+    /// The POST form: the same fields as JSON, plus `headers` (a list of
+    /// names or a name → value object) and `body` (JSON path → value).
+    fn from_json(value: &Value) -> Result<Self, String> {
+        let object = value.as_object().ok_or("assertion must be a JSON object")?;
+        let string = |key: &str| -> Result<Option<String>, String> {
+            match object.get(key) {
+                None | Some(Value::Null) => Ok(None),
+                Some(Value::String(s)) => Ok(Some(s.clone())),
+                Some(_) => Err(format!("`{key}` must be a string")),
+            }
+        };
+        let number = |key: &str| -> Result<Option<usize>, String> {
+            match object.get(key) {
+                None | Some(Value::Null) => Ok(None),
+                Some(v) => v
+                    .as_u64()
+                    .map(|n| Some(n as usize))
+                    .ok_or(format!("`{key}` must be a non-negative integer")),
+            }
+        };
+
+        let headers = match object.get("headers") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Array(names)) => names
+                .iter()
+                .map(|n| n.as_str().map(|n| (n.to_string(), None)))
+                .collect::<Option<_>>()
+                .ok_or("`headers` entries must be strings")?,
+            Some(Value::Object(pairs)) => pairs
+                .iter()
+                .map(|(name, value)| {
+                    let value = match value {
+                        Value::String(s) => Some(s.clone()),
+                        _ => None,
+                    };
+                    (name.clone(), value)
+                })
+                .collect(),
+            Some(_) => return Err("`headers` must be an array or object".into()),
+        };
+        let body = match object.get("body") {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::Object(matchers)) => matchers
+                .iter()
+                .map(|(path, value)| (path.clone(), value.clone()))
+                .collect(),
+            Some(_) => return Err("`body` must be an object of JSON path → value".into()),
+        };
+
+        Ok(Assertion {
+            method: string("method")?,
+            path: string("path")?,
+            min: number("min")?,
+            max: number("max")?,
+            count: number("count")?,
+            headers,
+            body,
+        })
+    }
+
+    fn targets(&self, request: &RecordedRequest) -> bool {
+        let method = self
+            .method
+            .as_ref()
+            .is_none_or(|m| m.eq_ignore_ascii_case(&request.method));
+        let path = self
+            .path
+            .as_ref()
+            .is_none_or(|p| match p.strip_suffix('*') {
+                Some(prefix) => request.path.starts_with(prefix),
+                None => *p == request.path,
+            });
+        method && path
+    }
+
+    /// Reasons `request` fails the header and body matchers.
+    fn mismatches(&self, request: &RecordedRequest) -> Vec<String> {
+        let mut reasons = Vec::new();
+        for (name, expected) in &self.headers {
+            let actual = request
+                .headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .and_then(|(_, v)| v.as_str());
+            match (actual, expected) {
+                (None, _) => reasons.push(format!("missing header `{name}`")),
+                (Some(actual), Some(expected)) if actual != expected => reasons.push(format!(
+                    "header `{name}` is `{actual}`, expected `{expected}`"
+                )),
+                _ => (),
+            }
+        }
+        for (path, expected) in &self.body {
+            match request.body.as_ref().and_then(|body| lookup(body, path)) {
+                None => reasons.push(format!("body has no `{path}`")),
+                Some(actual) if actual != expected => {
+                    reasons.push(format!("body `{path}` is {actual}, expected {expected}"))
+                }
+                _ => (),
+            }
+        }
+        reasons
+    }
+
+    fn expectation(&self) -> String {
+        match (self.count, self.min, self.max) {
+            (Some(count), _, _) => format!("exactly {count}"),
+            (None, min, Some(max)) => format!("between {} and {max}", min.unwrap_or(0)),
+            (None, min, None) => format!("at least {}", min.unwrap_or(1)),
+        }
+    }
+
+    fn satisfied_by(&self, matched: usize) -> bool {
+        match self.count {
+            Some(count) => matched == count,
+            None => {
+                let min = match (self.min, self.max) {
+                    (None, None) => 1,
+                    (min, _) => min.unwrap_or(0),
+                };
+                matched >= min && self.max.is_none_or(|max| matched <= max)
+            }
+        }
+    }
+
+    fn evaluate(&self, requests: &[RecordedRequest]) -> HttpResponse {
+        let mut matched = 0;
+        let mut near_misses = Vec::new();
+        for request in requests.iter().filter(|r| self.targets(r)) {
+            let reasons = self.mismatches(request);
+            if reasons.is_empty() {
+                matched += 1;
+            } else if near_misses.len() < MAX_NEAR_MISSES {
+                near_misses.push(json!({
+                    "id": request.id,
+                    "method": request.method,
+                    "path": request.path,
+                    "reasons": reasons,
+                }));
+            }
+        }
+
+        if self.satisfied_by(matched) {
+            HttpResponse::Ok().json(json!({ "ok": true, "matched": matched }))
+        } else {
+            HttpResponse::Conflict().json(json!({
+                "ok": false,
+                "matched": matched,
+                "expected": self.expectation(),
+                "near_misses": near_misses,
+            }))
+        }
+    }
+}
+
+/// Resolves `$.items[0].name` (or `items.0.name`) inside `value`.
+fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let normalized = path
+        .trim_start_matches('$')
+        .replace('[', ".")
+        .replace(']', "");
+    normalized
+        .split('.')
+        .filter(|s| !s.is_empty())
+        .try_fold(value, |value, segment| match value {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        })
+}
+
+pub async fn assert_query(
+    recorder: web::Data<Recorder>,
+    query: web::Query<HashMap<String, String>>,
+) -> HttpResponse {
+    match Assertion::from_query(&query) {
+        Ok(assertion) => assertion.evaluate(&recorder.snapshot()),
+        Err(error) => HttpResponse::BadRequest().json(json!({ "error": error })),
+    }
+}
+
+pub async fn assert_body(recorder: web::Data<Recorder>, body: web::Json<Value>) -> HttpResponse {
+    match Assertion::from_json(&body) {
+        Ok(assertion) => assertion.evaluate(&recorder.snapshot()),
+        Err(error) => HttpResponse::BadRequest().json(json!({ "error": error })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, middleware, test as http, App};
+
+    use super::*;
+    use crate::recording;
+
+    fn traffic() -> Vec<http::TestRequest> {
+        vec![
+            http::TestRequest::post()
+                .uri("/users")
+                .insert_header(("X-Trace", "1"))
+                .set_json(json!({ "name": "ann", "tags": ["a", "b"] })),
+            http::TestRequest::post()
+                .uri("/users")
+                .set_json(json!({ "name": "bob", "tags": [] })),
+            http::TestRequest::get().uri("/users/1"),
+        ]
+    }
+
+    /// Sends `traffic()` through a recording app, then `assertion`.
+    async fn check(assertion: http::TestRequest) -> (StatusCode, Value) {
+        let app = http::init_service(
+            App::new()
+                .wrap(middleware::from_fn(recording::record))
+                .app_data(web::Data::new(Recorder::new(100)))
+                .route("/users", web::post().to(HttpResponse::Created))
+                .route("/users/{id}", web::get().to(HttpResponse::Ok))
+                .route("/__requests/assert", web::get().to(assert_query))
+                .route("/__requests/assert", web::post().to(assert_body)),
+        )
+        .await;
+        for req in traffic() {
+            http::call_service(&app, req.to_request()).await;
+        }
+        let res = http::call_service(&app, assertion.to_request()).await;
+        let status = res.status();
+        (status, http::read_body_json(res).await)
+    }
+
+    async fn query(query: &str) -> (StatusCode, Value) {
+        check(http::TestRequest::get().uri(&format!("/__requests/assert?{query}"))).await
+    }
+
+    async fn body(assertion: Value) -> (StatusCode, Value) {
+        check(
+            http::TestRequest::post()
+                .uri("/__requests/assert")
+                .set_json(assertion),
+        )
+        .await
+    }
+
+    #[actix_web::test]
+    async fn requests_are_counted_by_method_and_path() {
+        let (status, res) = query("method=POST&path=/users&min=1").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(res, json!({ "ok": true, "matched": 2 }));
+
+        assert_eq!(query("path=/users/*&count=1").await.0, StatusCode::OK);
+        assert_eq!(query("method=post&max=2").await.0, StatusCode::OK);
+
+        let (status, res) = query("method=POST&path=/users&count=1").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(
+            res,
+            json!({ "ok": false, "matched": 2, "expected": "exactly 1", "near_misses": [] })
+        );
+        let (status, res) = query("method=DELETE").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(res["expected"], "at least 1");
+    }
+
+    #[actix_web::test]
+    async fn body_fields_are_compared_by_json_path() {
+        let matched = body(json!({
+            "method": "POST",
+            "body": { "$.name": "ann", "tags[1]": "b" },
+            "count": 1,
+        }))
+        .await;
+        assert_eq!(
+            matched,
+            (StatusCode::OK, json!({ "ok": true, "matched": 1 }))
+        );
+
+        let (status, res) = body(json!({ "path": "/users", "body": { "name": "cy" } })).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(
+            res["near_misses"],
+            json!([
+                { "id": 1, "method": "POST", "path": "/users",
+                  "reasons": ["body `name` is \"ann\", expected \"cy\""] },
+                { "id": 2, "method": "POST", "path": "/users",
+                  "reasons": ["body `name` is \"bob\", expected \"cy\""] },
+            ])
+        );
+    }
+
+    #[actix_web::test]
+    async fn headers_must_be_present() {
+        let (status, res) = query("path=/users&header=x-trace&count=1").await;
+        assert_eq!((status, res["matched"].clone()), (StatusCode::OK, json!(1)));
+
+        let (status, res) = body(json!({ "path": "/users", "headers": { "X-Trace": "2" } })).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(
+            res["near_misses"][0]["reasons"],
+            json!(["header `X-Trace` is `1`, expected `2`"])
+        );
+        assert_eq!(
+            res["near_misses"][1]["reasons"],
+            json!(["missing header `X-Trace`"])
+        );
+    }
+
+    #[actix_web::test]
+    async fn malformed_assertions_are_rejected() {
+        assert_eq!(
+            query("min=lots").await,
+            (
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "`min` must be a number" })
+            )
+        );
+        assert_eq!(
+            body(json!({ "body": ["name"] })).await,
+            (
+                StatusCode::BAD_REQUEST,
+                json!({ "error": "`body` must be an object of JSON path → value" })
+            )
+        );
+    }
+}
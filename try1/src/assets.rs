@@ -0,0 +1,140 @@
+use std::{net::SocketAddr, sync::OnceLock};
+
+use actix_web::{
+    http::header::{self, CacheControl, CacheDirective},
+    web, HttpResponse,
+};
+use fake::rand::Rng;
+use serde_json::json;
+
+use crate::cli::Config;
+
+/// Largest width or height `/__assets/image` draws.
+pub const MAX_IMAGE_SIDE: u32 = 2048;
+
+/// Where the server listens, for absolute asset URLs in generated data.
+static ORIGIN: OnceLock<String> = OnceLock::new();
+
+/// Records the bound address; `--port 0` only learns it after binding.
+pub fn set_origin(addr: SocketAddr) {
+    let _ = ORIGIN.set(format!("http://{addr}"));
+}
+
+/// `http://host:port` of the server, or of `--port` before it's bound.
+pub fn origin(config: &Config) -> String {
+    ORIGIN
+        .get()
+        .cloned()
+        .unwrap_or_else(|| format!("http://127.0.0.1:{}", config.port))
+}
+
+/// A URL to a placeholder image of random size, for `@faker image-url`.
+pub fn image_url<R: Rng + ?Sized>(config: &Config, rng: &mut R) -> String {
+    let origin = origin(config);
+    let side = |rng: &mut R| rng.random_range(2..=16) * 50;
+    let (width, height) = (side(rng), side(rng));
+    format!(
+        "{origin}{}/{width}x{height}.png",
+        config.admin_path("assets/image")
+    )
+}
+
+/// `GET /__assets/image/{width}x{height}.png`: a solid PNG whose colour
+/// follows from its size, cacheable for good and loadable from any origin.
+pub async fn image(size: web::Path<String>) -> HttpResponse {
+    let Some((width, height)) = parse_size(&size) else {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!("expected `{{width}}x{{height}}.png`, got `{size}`"),
+        }));
+    };
+    if width == 0 || height == 0 || width > MAX_IMAGE_SIDE || height > MAX_IMAGE_SIDE {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!("width and height must be between 1 and {MAX_IMAGE_SIDE}, got {width}x{height}"),
+        }));
+    }
+    HttpResponse::Ok()
+        .content_type("image/png")
+        .insert_header(CacheControl(vec![
+            CacheDirective::Public,
+            CacheDirective::MaxAge(365 * 24 * 60 * 60),
+            CacheDirective::Extension("immutable".to_string(), None),
+        ]))
+        .insert_header((header::ACCESS_CONTROL_ALLOW_ORIGIN, "*"))
+        .insert_header(("Cross-Origin-Resource-Policy", "cross-origin"))
+        .body(solid_png(width, height, colour(width, height)))
+}
+
+fn parse_size(s: &str) -> Option<(u32, u32)> {
+    let (width, height) = s.strip_suffix(".png")?.split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+/// A muted colour that differs between nearby sizes.
+fn colour(width: u32, height: u32) -> [u8; 3] {
+    let hash = width.wrapping_mul(2654435761) ^ height.wrapping_mul(40503);
+    let channel = |shift: u32| 96 + (hash >> shift) as u8 % 128;
+    [channel(0), channel(8), channel(16)]
+}
+
+/// A one-bit palette PNG of a single colour, so even the largest image is
+/// small without a compressor: the pixel data goes in stored deflate blocks.
+fn solid_png(width: u32, height: u32, rgb: [u8; 3]) -> Vec<u8> {
+    let row = 1 + width.div_ceil(8) as usize;
+    let pixels = vec![0; row * height as usize];
+
+    let mut header = Vec::with_capacity(13);
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    // Bit depth 1, palette colour, default compression, filter, no interlace.
+    header.extend([1, 3, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"PLTE", &rgb);
+    chunk(&mut png, b"IDAT", &zlib_stored(&pixels));
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// A zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x01];
+    let mut blocks = data.chunks(u16::MAX as usize).peekable();
+    if blocks.peek().is_none() {
+        out.extend([1, 0, 0, 0xff, 0xff]);
+    }
+    while let Some(block) = blocks.next() {
+        out.push(blocks.peek().is_none() as u8);
+        let len = block.len() as u16;
+        out.extend(len.to_le_bytes());
+        out.extend((!len).to_le_bytes());
+        out.extend(block);
+    }
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
@@ -0,0 +1,140 @@
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, AUTHORIZATION, WWW_AUTHENTICATE},
+    middleware::Next,
+    web, Error, HttpResponse,
+};
+use serde_json::json;
+
+use crate::{cli::Config, Entity};
+
+/// Header `@auth apiKey` reads when it names none.
+const DEFAULT_API_KEY_HEADER: &str = "X-API-Key";
+
+/// Where `@auth` expects the credential.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Auth {
+    /// `Authorization: Bearer <token>`.
+    Bearer,
+    /// The token as the whole value of this header.
+    ApiKey(HeaderName),
+}
+
+impl Auth {
+    /// `bearer`, or `apiKey` with an optional header name, as in
+    /// `apiKey: X-API-Key`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (scheme, header) = s
+            .split_once([':', ' '])
+            .map_or((s, ""), |(scheme, header)| (scheme, header.trim()));
+        match scheme.trim().to_ascii_lowercase().as_str() {
+            "bearer" if header.is_empty() => Ok(Auth::Bearer),
+            "apikey" => {
+                let header = if header.is_empty() {
+                    DEFAULT_API_KEY_HEADER
+                } else {
+                    header
+                };
+                HeaderName::from_bytes(header.as_bytes())
+                    .map(Auth::ApiKey)
+                    .map_err(|_| format!("`{header}` isn't a header name"))
+            }
+            _ => Err(format!(
+                "expected `bearer` or `apiKey[: Header]`, got `{s}`"
+            )),
+        }
+    }
+}
+
+/// A credential `--auth-token` accepts, and the roles it holds for `@role`.
+#[derive(Debug, Clone)]
+pub struct MockToken {
+    pub value: String,
+    pub roles: Vec<String>,
+}
+
+impl MockToken {
+    /// `TOKEN` or `TOKEN:ROLE,ROLE`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (value, roles) = s.split_once(':').unwrap_or((s, ""));
+        if value.is_empty() {
+            return Err("`--auth-token` expects a token before any `:roles`".to_string());
+        }
+        Ok(MockToken {
+            value: value.to_string(),
+            roles: roles
+                .split(',')
+                .map(str::trim)
+                .filter(|role| !role.is_empty())
+                .map(String::from)
+                .collect(),
+        })
+    }
+}
+
+/// Answers for an `@auth` entity before its handler runs: 401 when the
+/// credential is missing or isn't one of `--auth-token`'s, 403 when it
+/// holds none of the `@role`s. Without `--auth-token`, any credential is
+/// accepted, holding no roles.
+pub async fn require(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let refused = match (
+        req.app_data::<web::Data<Entity>>(),
+        req.app_data::<web::Data<Config>>(),
+    ) {
+        (Some(entity), Some(config)) => check(&req, entity, config),
+        _ => None,
+    };
+    match refused {
+        Some(response) => Ok(req.into_response(response).map_into_right_body()),
+        None => Ok(next.call(req).await?.map_into_left_body()),
+    }
+}
+
+fn check(req: &ServiceRequest, entity: &Entity, config: &Config) -> Option<HttpResponse> {
+    let auth = entity.auth.as_ref()?;
+    let credential = match auth {
+        Auth::Bearer => req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer ")),
+        Auth::ApiKey(header) => req.headers().get(header).and_then(|v| v.to_str().ok()),
+    }
+    .map(str::trim)
+    .filter(|credential| !credential.is_empty());
+    let Some(credential) = credential else {
+        return Some(unauthorized(auth, "credentials required"));
+    };
+
+    let roles: &[String] = if config.auth_tokens.is_empty() {
+        &[]
+    } else {
+        match config
+            .auth_tokens
+            .iter()
+            .find(|token| token.value == credential)
+        {
+            Some(token) => &token.roles,
+            None => return Some(unauthorized(auth, "invalid credentials")),
+        }
+    };
+    if entity.roles.is_empty() || entity.roles.iter().any(|role| roles.contains(role)) {
+        return None;
+    }
+    Some(HttpResponse::Forbidden().json(json!({
+        "error": "insufficient role",
+        "required": entity.roles,
+    })))
+}
+
+fn unauthorized(auth: &Auth, error: &str) -> HttpResponse {
+    let mut response = HttpResponse::Unauthorized();
+    if *auth == Auth::Bearer {
+        response.insert_header((WWW_AUTHENTICATE, "Bearer"));
+    }
+    response.json(json!({ "error": error }))
+}
@@ -0,0 +1,320 @@
+use actix_web::{
+    error::PayloadError,
+    http::{
+        header::{self, HeaderMap, HeaderName, HeaderValue},
+        Method,
+    },
+    web, HttpRequest, HttpResponse,
+};
+use serde_json::{json, Map, Value};
+
+use crate::{assets, cli::Config};
+
+/// Headers of the batch request that sub-requests don't inherit: they
+/// describe the batch's own body and connection.
+const NOT_INHERITED: [HeaderName; 6] = [
+    header::HOST,
+    header::CONTENT_LENGTH,
+    header::CONTENT_TYPE,
+    header::TRANSFER_ENCODING,
+    header::CONNECTION,
+    header::EXPECT,
+];
+
+/// One entry of a batch: `{"method", "path", "headers"?, "body"?}`.
+struct SubRequest {
+    method: Method,
+    path: String,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    body: Option<Value>,
+}
+
+/// `POST /__batch`: runs each sub-request through the server as if it had
+/// been sent on its own, delays, scenarios and auth included, and answers
+/// with their `{status, headers, body}` in the same order. They run one
+/// after another, so under `--stateful` later ones see earlier writes.
+/// Sub-requests inherit the batch's headers, `Authorization` among them,
+/// unless they set their own.
+pub async fn run(
+    req: HttpRequest,
+    body: web::Json<Value>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    let Some(entries) = body.as_array() else {
+        return HttpResponse::BadRequest()
+            .json(json!({ "error": "expected an array of requests" }));
+    };
+    if entries.len() > config.batch_limit {
+        return HttpResponse::BadRequest().json(json!({
+            "error": format!(
+                "{} requests is more than --batch-limit {}",
+                entries.len(),
+                config.batch_limit
+            ),
+        }));
+    }
+    let mut requests = Vec::with_capacity(entries.len());
+    for (index, entry) in entries.iter().enumerate() {
+        match sub_request(entry) {
+            Ok(request) => requests.push(request),
+            Err(error) => {
+                return HttpResponse::BadRequest()
+                    .json(json!({ "error": format!("request {index}: {error}") }))
+            }
+        }
+    }
+
+    let client = awc::Client::builder().disable_timeout().finish();
+    let origin = assets::origin(&config);
+    let own_path = bare_path(req.path());
+    let mut results = Vec::with_capacity(requests.len());
+    for request in requests {
+        if bare_path(&request.path) == own_path {
+            results.push(failed(400, "batches can't be nested"));
+            continue;
+        }
+        let limit = config.max_body_bytes;
+        results.push(send(&client, &origin, req.headers(), request, limit).await);
+    }
+    HttpResponse::Ok().json(results)
+}
+
+/// `path` without its query, fragment or trailing slashes, so every
+/// spelling of the batch route is caught.
+fn bare_path(path: &str) -> &str {
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    match path.trim_end_matches('/') {
+        "" => "/",
+        path => path,
+    }
+}
+
+fn sub_request(entry: &Value) -> Result<SubRequest, String> {
+    let Some(entry) = entry.as_object() else {
+        return Err("expected an object".to_string());
+    };
+    let method = entry
+        .get("method")
+        .and_then(Value::as_str)
+        .ok_or("`method` must be a string")?;
+    let method = Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+        .map_err(|_| format!("`{method}` isn't an HTTP method"))?;
+    let path = entry
+        .get("path")
+        .and_then(Value::as_str)
+        .filter(|path| path.starts_with('/'))
+        .ok_or("`path` must be a string starting with `/`")?;
+    let mut headers = Vec::new();
+    match entry.get("headers") {
+        None => (),
+        Some(Value::Object(fields)) => {
+            for (name, value) in fields {
+                let value = value.as_str().map(HeaderValue::from_str);
+                match (HeaderName::from_bytes(name.as_bytes()), value) {
+                    (Ok(name), Some(Ok(value))) => headers.push((name, value)),
+                    _ => return Err(format!("header `{name}` must be a valid string header")),
+                }
+            }
+        }
+        Some(_) => return Err("`headers` must be an object".to_string()),
+    }
+    Ok(SubRequest {
+        method,
+        path: path.to_string(),
+        headers,
+        body: entry.get("body").cloned(),
+    })
+}
+
+/// Sends one sub-request to the server itself. Failing to reach it, or to
+/// read the answer, is that entry's 502 rather than the batch's, and so is
+/// an answer over `limit` bytes.
+async fn send(
+    client: &awc::Client,
+    origin: &str,
+    inherited: &HeaderMap,
+    request: SubRequest,
+    limit: usize,
+) -> Value {
+    let mut sent = client.request(request.method, format!("{origin}{}", request.path));
+    for (name, value) in inherited {
+        let overridden = request.headers.iter().any(|(own, _)| own == name);
+        if !NOT_INHERITED.contains(name) && !overridden {
+            sent = sent.append_header((name.clone(), value.clone()));
+        }
+    }
+    for (name, value) in request.headers {
+        sent = sent.insert_header((name, value));
+    }
+    let response = match request.body {
+        Some(body) => sent.send_json(&body).await,
+        None => sent.send().await,
+    };
+    let mut response = match response {
+        Ok(response) => response,
+        Err(err) => return failed(502, &err.to_string()),
+    };
+    let bytes = match response.body().limit(limit).await {
+        Ok(bytes) => bytes,
+        Err(PayloadError::Overflow) => {
+            return failed(
+                502,
+                &format!("the response is over --max-body-bytes {limit}"),
+            )
+        }
+        Err(err) => return failed(502, &err.to_string()),
+    };
+
+    let mut headers = Map::new();
+    for (name, value) in response.headers() {
+        headers.insert(
+            name.to_string(),
+            json!(String::from_utf8_lossy(value.as_bytes())),
+        );
+    }
+    let body = serde_json::from_slice(&bytes)
+        .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into_owned()));
+    json!({
+        "status": response.status().as_u16(),
+        "headers": headers,
+        "body": body,
+    })
+}
+
+fn failed(status: u16, error: &str) -> Value {
+    json!({
+        "status": status,
+        "headers": {},
+        "body": { "error": error },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{App, HttpServer};
+
+    use super::*;
+
+    const LIMIT: usize = 1024;
+
+    async fn echo(req: HttpRequest) -> HttpResponse {
+        HttpResponse::Ok().json(json!({ "path": req.path(), "query": req.query_string() }))
+    }
+
+    async fn big() -> HttpResponse {
+        HttpResponse::Ok().body("x".repeat(LIMIT + 1))
+    }
+
+    /// Serves `/__batch` beside `/echo` and a `/big` answer one byte over
+    /// `--max-body-bytes`, on a free port, and returns its origin.
+    fn serve(batch_limit: usize) -> String {
+        let listener = std::net::TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let config = web::Data::new(Config {
+            port,
+            batch_limit,
+            max_body_bytes: LIMIT,
+            ..Config::default()
+        });
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(config.clone())
+                .route("/__batch", web::post().to(run))
+                .route("/echo", web::to(echo))
+                .route("/big", web::get().to(big))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        actix_web::rt::spawn(server);
+        format!("http://127.0.0.1:{port}")
+    }
+
+    async fn batch(origin: &str, entries: Value) -> (u16, Value) {
+        let mut response = awc::Client::default()
+            .post(format!("{origin}/__batch"))
+            .send_json(&entries)
+            .await
+            .unwrap();
+        let body = response.json().await.unwrap();
+        (response.status().as_u16(), body)
+    }
+
+    /// Each result's status and body, without headers.
+    fn answers(results: &Value) -> Vec<(u64, Value)> {
+        results
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|result| (result["status"].as_u64().unwrap(), result["body"].clone()))
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn results_come_back_in_request_order() {
+        let origin = serve(10);
+        let (status, results) = batch(
+            &origin,
+            json!([
+                { "method": "get", "path": "/echo?n=1" },
+                { "method": "POST", "path": "/echo", "body": {} },
+                { "method": "GET", "path": "/echo?n=3" },
+            ]),
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert_eq!(
+            answers(&results),
+            [
+                (200, json!({ "path": "/echo", "query": "n=1" })),
+                (200, json!({ "path": "/echo", "query": "" })),
+                (200, json!({ "path": "/echo", "query": "n=3" })),
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn batches_over_the_limit_are_refused() {
+        let origin = serve(2);
+        let entry = json!({ "method": "GET", "path": "/echo" });
+        let (status, body) = batch(&origin, json!([entry, entry, entry])).await;
+        assert_eq!(status, 400);
+        assert_eq!(body["error"], "3 requests is more than --batch-limit 2");
+        let (status, _) = batch(&origin, json!([entry, entry])).await;
+        assert_eq!(status, 200);
+    }
+
+    #[actix_web::test]
+    async fn a_failing_entry_leaves_the_others_answered() {
+        let origin = serve(10);
+        let (status, results) = batch(
+            &origin,
+            json!([
+                { "method": "GET", "path": "/echo?n=1" },
+                { "method": "GET", "path": "/big" },
+                { "method": "GET", "path": "/__batch/" },
+                { "method": "GET", "path": "/__batch?x=1" },
+                { "method": "GET", "path": "/missing" },
+                { "method": "GET", "path": "/echo?n=2" },
+            ]),
+        )
+        .await;
+        assert_eq!(status, 200);
+        let nested = json!({ "error": "batches can't be nested" });
+        assert_eq!(
+            answers(&results),
+            [
+                (200, json!({ "path": "/echo", "query": "n=1" })),
+                (
+                    502,
+                    json!({ "error": format!("the response is over --max-body-bytes {LIMIT}") })
+                ),
+                (400, nested.clone()),
+                (400, nested),
+                (404, json!("")),
+                (200, json!({ "path": "/echo", "query": "n=2" })),
+            ]
+        );
+    }
+}
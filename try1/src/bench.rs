@@ -0,0 +1,55 @@
+//! What `benches/` needs from inside the crate, which they can't reach
+//! through the public API. Not meant for anything else.
+
+use std::path::Path;
+
+use fake::rand::{rngs::StdRng, SeedableRng};
+use oxc::allocator::Allocator;
+use serde_json::Value;
+
+use crate::{
+    cli::Config,
+    generate::{self, Context},
+    parser::{self, ScanContext},
+    Entity,
+};
+
+/// The entities `source` declares, parsed as a TypeScript file of that name.
+pub fn entities(name: &str, source: &str) -> Vec<Entity> {
+    let config = Config::default();
+    let allocator = Allocator::default();
+    let mut scan = ScanContext::new(&config, Path::new("."));
+    parser::parse_typescript_file(Path::new(name), source, &allocator, &mut scan)
+}
+
+/// Generates data for entities with the default config and a fixed seed,
+/// so runs are comparable.
+pub struct Generator {
+    config: Config,
+    ctx: Context,
+    rng: StdRng,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        let config = Config::default();
+        Generator {
+            ctx: Context::of(&config),
+            config,
+            rng: StdRng::seed_from_u64(0),
+        }
+    }
+}
+
+impl Generator {
+    /// One object with the entity's props, as a non-list GET returns.
+    pub fn object(&mut self, entity: &Entity) -> Value {
+        generate::fake_object(entity, &self.config, self.ctx, &mut self.rng)
+    }
+
+    /// `count` items of a list entity, sharing nested objects by id as one
+    /// response does.
+    pub fn list(&mut self, entity: &Entity, count: usize) -> Value {
+        generate::fake_response(entity, &self.config, self.ctx, Some(count), &mut self.rng)
+    }
+}
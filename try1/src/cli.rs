@@ -0,0 +1,519 @@
+use std::{error::Error, path::PathBuf, time::Duration};
+
+use crate::{auth::MockToken, date::Clock, generate::Locale, route_table::RouteFormat, throttle};
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    Serve,
+    Snapshot {
+        out: PathBuf,
+        check: bool,
+    },
+    /// Print the route table instead of serving.
+    Routes {
+        format: RouteFormat,
+    },
+    /// Print generated objects of one entity instead of serving.
+    Fake {
+        /// Interface name or route.
+        name: String,
+        count: usize,
+        /// One object per line rather than a JSON array.
+        ndjson: bool,
+    },
+    /// Print what the scan found, and why when it found nothing.
+    List,
+    /// Write the scanned entities as IR, to a file or stdout.
+    Scan {
+        emit_ir: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub max_body_bytes: usize,
+    pub fuzz: bool,
+    pub seed: Option<u64>,
+    pub quiet_404: bool,
+    /// Global response rate in bytes per second.
+    pub bandwidth: Option<u64>,
+    /// Serve versioned entities under `/v{n}` instead of by `Accept` profile.
+    pub version_prefix: bool,
+    /// Chance an optional prop is included when it has no `@presentRate`.
+    pub optional_present_rate: f64,
+    /// How many recent requests `/__requests` keeps.
+    pub request_history: usize,
+    /// Source files larger than this are skipped during the scan.
+    pub max_file_size: usize,
+    /// Read non-UTF-8 sources with replacement characters instead of skipping.
+    pub lossy_utf8: bool,
+    /// Emit every `bigint` prop as a JSON string.
+    pub bigint_as_string: bool,
+    /// Path prefix of the admin endpoints, e.g. `/__` for `/__routes`.
+    pub admin_prefix: String,
+    /// Bearer token the admin endpoints require, when set.
+    pub admin_token: Option<String>,
+    pub admin: bool,
+    /// Health and metrics stay reachable without the token, for probes.
+    pub health: bool,
+    pub metrics: bool,
+    /// Keep created, replaced and deleted items in memory per entity.
+    pub stateful: bool,
+    /// Items each collection starts with (and returns to on reset).
+    pub seed_count: usize,
+    /// Indent JSON responses unless `?pretty=false` says otherwise.
+    pub pretty: bool,
+    /// First words of a comment that mark the interface below as a route.
+    pub route_keywords: Vec<String>,
+    /// `0` lets the OS pick a free port.
+    pub port: u16,
+    /// Servers started on consecutive ports from `port`, each with its own
+    /// store, history and metrics.
+    pub instances: u16,
+    /// Which of them this config is for, from 0.
+    pub instance: u16,
+    /// Print the entities and their settings instead of serving them.
+    pub dry_run: bool,
+    /// Print every entity's props at startup.
+    pub print_entities: bool,
+    /// Write the seeded collections to this file instead of serving them.
+    pub dump_db: Option<PathBuf>,
+    /// JSONL file each request and its response are appended to.
+    pub record: Option<PathBuf>,
+    /// Entities from this `ssg scan --emit-ir` file instead of a scan.
+    pub ir: Option<PathBuf>,
+    /// `--record` trace whose responses answer the requests it recorded.
+    pub replay: Option<PathBuf>,
+    /// Replay only exchanges recorded with the same request body.
+    pub replay_match_body: bool,
+    /// 404 requests the replayed trace doesn't have, instead of generating.
+    pub replay_strict: bool,
+    /// How long a POST's `Idempotency-Key` replays its first response.
+    pub idempotency_ttl: Duration,
+    /// Most keys remembered; the least recently used go first.
+    pub idempotency_keys: usize,
+    /// Credentials `@auth` routes accept; any when empty.
+    pub auth_tokens: Vec<MockToken>,
+    /// The `ssg.toml` profile whose settings were applied.
+    pub profile: Option<String>,
+    /// Honour `X-Mock-Override` on GETs.
+    pub request_overrides: bool,
+    /// Most sub-requests one `POST /__batch` may carry.
+    pub batch_limit: usize,
+    /// Largest `?count=` a filtered, sorted or paged list may generate,
+    /// since that pool is built whole before it's sliced.
+    pub max_pool: usize,
+    /// Longest random delay added to every entity response.
+    pub jitter: Duration,
+    /// How long `--stateful` writes stay unseen by reads, unless an entity's
+    /// `@consistency-lag` says otherwise.
+    pub consistency_lag: Duration,
+    /// Exit after the scan when it printed any warning.
+    pub fail_on_warning: bool,
+    /// Start even when the scan finds no entities.
+    pub allow_empty: bool,
+    /// Prefix relative routes with their file's path under this directory.
+    pub routes_from_dirs: Option<PathBuf>,
+    /// Also serve entities inferred from the example `.json` responses here.
+    pub infer: Option<PathBuf>,
+    /// What generated dates count as now, from `--now`.
+    pub clock: Clock,
+    /// Data set for generated names, places and text.
+    pub locale: Locale,
+    /// Pick the locale per request from `Accept-Language`, falling back to
+    /// `locale`.
+    pub locale_from_header: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            max_body_bytes: 256 * 1024,
+            fuzz: false,
+            seed: None,
+            quiet_404: false,
+            bandwidth: None,
+            version_prefix: false,
+            optional_present_rate: 0.5,
+            request_history: 1000,
+            max_file_size: 2 * 1024 * 1024,
+            lossy_utf8: false,
+            bigint_as_string: false,
+            admin_prefix: "/__".into(),
+            admin_token: None,
+            admin: true,
+            health: true,
+            metrics: true,
+            stateful: false,
+            seed_count: 10,
+            pretty: false,
+            route_keywords: vec!["route".into()],
+            port: 3000,
+            instances: 1,
+            instance: 0,
+            dry_run: false,
+            print_entities: false,
+            dump_db: None,
+            record: None,
+            ir: None,
+            replay: None,
+            replay_match_body: false,
+            replay_strict: false,
+            idempotency_ttl: Duration::from_secs(24 * 60 * 60),
+            idempotency_keys: 1000,
+            auth_tokens: Vec::new(),
+            profile: None,
+            request_overrides: true,
+            batch_limit: 50,
+            max_pool: 10_000,
+            jitter: Duration::ZERO,
+            consistency_lag: Duration::ZERO,
+            fail_on_warning: false,
+            allow_empty: false,
+            routes_from_dirs: None,
+            infer: None,
+            clock: Clock::Real,
+            locale: Locale::En,
+            locale_from_header: false,
+        }
+    }
+}
+
+impl Config {
+    /// Where the admin endpoint `name` is served: `/__routes` under the
+    /// default prefix, `/admin/routes` under `/admin`.
+    pub fn admin_path(&self, name: &str) -> String {
+        if self.admin_prefix.ends_with(['/', '_']) {
+            format!("{}{name}", self.admin_prefix)
+        } else {
+            format!("{}/{name}", self.admin_prefix)
+        }
+    }
+
+    /// Whether `path` is one of the mock's own endpoints rather than an entity.
+    pub fn is_internal(&self, path: &str) -> bool {
+        path.starts_with("/_") || path.starts_with(&self.admin_prefix)
+    }
+}
+
+pub fn parse_args(
+    args: impl IntoIterator<Item = String>,
+) -> Result<(Command, Config), Box<dyn Error>> {
+    let mut config = Config::default();
+    let mut args = args.into_iter().peekable();
+
+    let mut command = match args.peek().map(String::as_str) {
+        Some("serve") => {
+            args.next();
+            Command::Serve
+        }
+        Some("snapshot") => {
+            args.next();
+            Command::Snapshot {
+                out: PathBuf::from("__mock_snapshots__"),
+                check: false,
+            }
+        }
+        Some("routes") => {
+            args.next();
+            Command::Routes {
+                format: RouteFormat::Json,
+            }
+        }
+        Some("fake") => {
+            args.next();
+            Command::Fake {
+                name: String::new(),
+                count: 1,
+                ndjson: false,
+            }
+        }
+        Some("list") => {
+            args.next();
+            Command::List
+        }
+        Some("scan") => {
+            args.next();
+            Command::Scan { emit_ir: None }
+        }
+        _ => Command::Serve,
+    };
+
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), &mut command) {
+            ("--max-body-bytes", _) => {
+                config.max_body_bytes = parse_size(&value(&arg, args.next())?)?
+            }
+            ("--fuzz", _) => config.fuzz = true,
+            ("--quiet-404", _) => config.quiet_404 = true,
+            ("--version-prefix", _) => config.version_prefix = true,
+            ("--bandwidth", _) => {
+                config.bandwidth = Some(throttle::parse_bandwidth(&value(&arg, args.next())?)?)
+            }
+            ("--optional-present-rate", _) => {
+                config.optional_present_rate = parse_rate(&value(&arg, args.next())?)?
+            }
+            ("--request-history", _) => {
+                config.request_history = parse_num(&arg, &value(&arg, args.next())?)?
+            }
+            ("--max-file-size", _) => {
+                config.max_file_size = parse_size(&value(&arg, args.next())?)?
+            }
+            ("--lossy-utf8", _) => config.lossy_utf8 = true,
+            ("--bigint-as-string", _) => config.bigint_as_string = true,
+            ("--admin-prefix", _) => {
+                let prefix = value(&arg, args.next())?;
+                if !prefix.starts_with('/') {
+                    return Err(
+                        format!("`--admin-prefix` must start with `/`, got `{prefix}`").into(),
+                    );
+                }
+                config.admin_prefix = prefix;
+            }
+            ("--admin-token", _) => config.admin_token = Some(value(&arg, args.next())?),
+            ("--auth-token", _) => config
+                .auth_tokens
+                .push(MockToken::parse(&value(&arg, args.next())?)?),
+            ("--no-admin", _) => config.admin = false,
+            ("--no-health", _) => config.health = false,
+            ("--no-metrics", _) => config.metrics = false,
+            ("--no-request-overrides", _) => config.request_overrides = false,
+            ("--stateful", _) => config.stateful = true,
+            ("--pretty", _) => config.pretty = true,
+            ("--dry-run", _) => config.dry_run = true,
+            ("--print-entities", _) => config.print_entities = true,
+            ("--fail-on-warning", _) => config.fail_on_warning = true,
+            ("--allow-empty", _) => config.allow_empty = true,
+            ("--locale", _) => {
+                let tag = value(&arg, args.next())?;
+                config.locale =
+                    Locale::parse(&tag).ok_or_else(|| format!("unknown locale `{tag}`"))?
+            }
+            ("--locale-from-header", _) => config.locale_from_header = true,
+            ("--now", _) => {
+                config.clock =
+                    Clock::parse(&value(&arg, args.next())?).map_err(|e| format!("`--now`: {e}"))?
+            }
+            ("--routes-from-dirs", _) => {
+                config.routes_from_dirs = Some(value(&arg, args.next())?.into())
+            }
+            ("--infer", _) => config.infer = Some(value(&arg, args.next())?.into()),
+            ("--dump-db", _) => config.dump_db = Some(value(&arg, args.next())?.into()),
+            ("--record", _) => config.record = Some(value(&arg, args.next())?.into()),
+            ("--ir", _) => config.ir = Some(value(&arg, args.next())?.into()),
+            ("--emit-ir", Command::Scan { emit_ir }) => {
+                *emit_ir = Some(value(&arg, args.next())?.into())
+            }
+            ("--replay", _) => config.replay = Some(value(&arg, args.next())?.into()),
+            ("--replay-match-body", _) => config.replay_match_body = true,
+            ("--replay-strict", _) => config.replay_strict = true,
+            ("--profile", _) => config.profile = Some(value(&arg, args.next())?),
+            ("--port", _) => config.port = parse_num(&arg, &value(&arg, args.next())?)?,
+            ("--route-keywords", _) => {
+                config.route_keywords = value(&arg, args.next())?
+                    .split(',')
+                    .map(|k| k.trim().trim_start_matches('@').to_string())
+                    .filter(|k| !k.is_empty())
+                    .collect();
+                if config.route_keywords.is_empty() {
+                    return Err("`--route-keywords` expects at least one keyword".into());
+                }
+            }
+            ("--idempotency-ttl", _) => {
+                config.idempotency_ttl = parse_duration(&value(&arg, args.next())?)?
+            }
+            ("--idempotency-keys", _) => {
+                config.idempotency_keys = parse_num(&arg, &value(&arg, args.next())?)?
+            }
+            ("--instances", _) => config.instances = parse_num(&arg, &value(&arg, args.next())?)?,
+            ("--batch-limit", _) => {
+                config.batch_limit = parse_num(&arg, &value(&arg, args.next())?)?
+            }
+            ("--max-pool", _) => config.max_pool = parse_num(&arg, &value(&arg, args.next())?)?,
+            ("--jitter", _) => config.jitter = parse_millis(&value(&arg, args.next())?)?,
+            ("--consistency-lag", _) => {
+                config.consistency_lag = parse_duration(&value(&arg, args.next())?)?
+            }
+            ("--seed-count", _) => config.seed_count = parse_num(&arg, &value(&arg, args.next())?)?,
+            ("--seed", _) => config.seed = Some(parse_num(&arg, &value(&arg, args.next())?)?),
+            ("--out", Command::Snapshot { out, .. }) => *out = value(&arg, args.next())?.into(),
+            ("--check", Command::Snapshot { check, .. }) => *check = true,
+            ("--format", Command::Routes { format }) => {
+                let name = value(&arg, args.next())?;
+                *format = RouteFormat::parse(&name).ok_or_else(|| {
+                    format!("`--format` expects json, nginx or caddy, got `{name}`")
+                })?
+            }
+            ("--count", Command::Fake { count, .. }) => {
+                *count = parse_num(&arg, &value(&arg, args.next())?)?
+            }
+            ("--ndjson", Command::Fake { ndjson, .. }) => *ndjson = true,
+            (word, Command::Fake { name, .. }) if name.is_empty() && !word.starts_with('-') => {
+                *name = word.to_string()
+            }
+            _ => return Err(format!("unknown argument `{arg}`").into()),
+        }
+    }
+    if config.instances == 0 {
+        return Err("`--instances` expects at least 1".into());
+    }
+    if config.instances > 1 && config.port == 0 {
+        return Err("`--instances` needs a `--port` to count up from, not 0".into());
+    }
+    if config.port.checked_add(config.instances - 1).is_none() {
+        return Err(format!(
+            "`--instances {}` from port {} runs past port 65535",
+            config.instances, config.port
+        )
+        .into());
+    }
+    if let Command::Fake { name, .. } = &command {
+        if name.is_empty() {
+            return Err("`ssg fake` expects an interface name or route".into());
+        }
+    }
+    Ok((command, config))
+}
+
+fn value(flag: &str, next: Option<String>) -> Result<String, Box<dyn Error>> {
+    next.ok_or_else(|| format!("`{flag}` expects a value").into())
+}
+
+fn parse_num<T: std::str::FromStr>(flag: &str, s: &str) -> Result<T, Box<dyn Error>> {
+    s.parse()
+        .map_err(|_| format!("`{flag}` expects a number, got `{s}`").into())
+}
+
+/// Parses a probability in `0..=1`.
+pub fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(rate),
+        _ => Err(format!("expected a rate between 0 and 1, got `{s}`")),
+    }
+}
+
+/// Parses a duration such as `500ms`, `30s`, `10m` or `24h`; a bare number
+/// is seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, Box<dyn Error>> {
+    let trimmed = s.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration `{s}`"))?;
+    let seconds = match unit.trim() {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 60.0 * 60.0,
+        _ => return Err(format!("unknown duration unit in `{s}`").into()),
+    };
+    // Too long for a `Duration` is as invalid as not a number.
+    Ok(Duration::try_from_secs_f64(seconds).map_err(|_| format!("invalid duration `{s}`"))?)
+}
+
+/// Parses a delay given in milliseconds, or with an explicit unit.
+pub fn parse_millis(s: &str) -> Result<Duration, Box<dyn Error>> {
+    match s.trim().parse::<f64>() {
+        Ok(ms) if ms.is_finite() => Ok(Duration::try_from_secs_f64(ms / 1000.0)
+            .map_err(|_| format!("invalid duration `{s}`"))?),
+        Ok(_) => Err(format!("invalid duration `{s}`").into()),
+        Err(_) => parse_duration(s),
+    }
+}
+
+/// Parses a byte size such as `4096`, `256kb` or `2mb`.
+pub fn parse_size(s: &str) -> Result<usize, Box<dyn Error>> {
+    let lower = s.trim().to_ascii_lowercase();
+    let (digits, multiplier) = if let Some(n) = lower.strip_suffix("kb") {
+        (n, 1024)
+    } else if let Some(n) = lower.strip_suffix("mb") {
+        (n, 1024 * 1024)
+    } else if let Some(n) = lower.strip_suffix('b') {
+        (n, 1)
+    } else {
+        (lower.as_str(), 1)
+    };
+    let size = digits
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size `{s}`"))?;
+    Ok(size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes_take_a_unit() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("2kb").unwrap(), 2048);
+        assert_eq!(parse_size(" 3MB ").unwrap(), 3 * 1024 * 1024);
+        assert_eq!(parse_size("7b").unwrap(), 7);
+    }
+
+    #[test]
+    fn sizes_that_overflow_are_invalid() {
+        let max = format!("{}mb", usize::MAX);
+        assert_eq!(
+            parse_size(&max).unwrap_err().to_string(),
+            format!("invalid size `{max}`")
+        );
+        assert!(parse_size(&format!("{}kb", usize::MAX / 1024 + 1)).is_err());
+        assert_eq!(
+            parse_size(&format!("{}kb", usize::MAX / 1024)).unwrap(),
+            usize::MAX / 1024 * 1024
+        );
+    }
+
+    #[test]
+    fn durations_take_a_unit() {
+        assert_eq!(parse_duration("30").unwrap(), Duration::from_secs(30));
+        assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_duration("1.5m").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("24h").unwrap(), Duration::from_secs(86_400));
+    }
+
+    #[test]
+    fn durations_too_long_for_a_duration_are_invalid() {
+        for s in ["99999999999999999999999h", &"9".repeat(400)] {
+            assert_eq!(
+                parse_duration(s).unwrap_err().to_string(),
+                format!("invalid duration `{s}`")
+            );
+        }
+        for flag in ["--idempotency-ttl", "--consistency-lag"] {
+            let args = ["serve", flag, "99999999999999999999999h"].map(String::from);
+            assert_eq!(
+                parse_args(args).unwrap_err().to_string(),
+                "invalid duration `99999999999999999999999h`"
+            );
+        }
+    }
+
+    #[test]
+    fn delays_are_milliseconds_unless_they_name_a_unit() {
+        assert_eq!(parse_millis("250").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_millis("1.5s").unwrap(), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn delays_that_are_negative_endless_or_too_long_are_invalid() {
+        for s in ["-1", "inf", "-inf", "NaN", "1e30"] {
+            assert_eq!(
+                parse_millis(s).unwrap_err().to_string(),
+                format!("invalid duration `{s}`")
+            );
+        }
+        let args = ["serve", "--jitter", "inf"].map(String::from);
+        assert_eq!(
+            parse_args(args).unwrap_err().to_string(),
+            "invalid duration `inf`"
+        );
+    }
+}
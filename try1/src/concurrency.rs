@@ -0,0 +1,295 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use actix_web::{
+    http::{
+        header::{self, ETAG},
+        StatusCode,
+    },
+    HttpRequest, HttpResponse, HttpResponseBuilder,
+};
+use serde_json::{json, Value};
+
+use crate::Entity;
+
+/// The integer prop `@concurrency version` keeps on stored items.
+pub const VERSION_PROP: &str = "version";
+
+/// How `--stateful` guards PUT and PATCH against lost updates, from
+/// `@concurrency`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Concurrency {
+    #[default]
+    Off,
+    /// An ETag hashed from the item's content.
+    Etag,
+    /// A `version` prop counting updates, also sent as the ETag.
+    Version,
+}
+
+impl Concurrency {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "off" => Some(Concurrency::Off),
+            "etag" => Some(Concurrency::Etag),
+            "version" => Some(Concurrency::Version),
+            _ => None,
+        }
+    }
+}
+
+/// The item's current ETag, quoted.
+pub fn etag(entity: &Entity, item: &Value) -> Option<String> {
+    match entity.concurrency {
+        Concurrency::Off => None,
+        Concurrency::Etag => {
+            let mut hasher = DefaultHasher::new();
+            item.to_string().hash(&mut hasher);
+            Some(format!("\"{:016x}\"", hasher.finish()))
+        }
+        Concurrency::Version => Some(format!("\"{}\"", version(item))),
+    }
+}
+
+pub fn version(item: &Value) -> u64 {
+    item[VERSION_PROP].as_u64().unwrap_or_default()
+}
+
+/// Adds the item's `ETag` to a response about it.
+pub fn tag(response: &mut HttpResponseBuilder, entity: &Entity, item: &Value) {
+    if let Some(etag) = etag(entity, item) {
+        response.insert_header((ETAG, etag));
+    }
+}
+
+/// Lets a PUT or PATCH through when its `If-Match`, or under `version` the
+/// `version` its body sent, matches the stored item: 428 when it sends
+/// neither, 412 when it's stale.
+pub fn check(
+    req: &HttpRequest,
+    entity: &Entity,
+    item: &Value,
+    sent_version: Option<&Value>,
+) -> Result<(), HttpResponse> {
+    let Some(current) = etag(entity, item) else {
+        return Ok(());
+    };
+    let if_match = req
+        .headers()
+        .get(header::IF_MATCH)
+        .and_then(|value| value.to_str().ok());
+    let matches = match if_match {
+        Some(if_match) => if_match
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == "*" || tag == current),
+        None => match (entity.concurrency, sent_version) {
+            (Concurrency::Version, Some(sent)) => {
+                sent.as_u64().or_else(|| sent.as_str()?.parse().ok()) == Some(version(item))
+            }
+            _ => {
+                return Err(
+                    HttpResponse::build(StatusCode::PRECONDITION_REQUIRED).json(json!({
+                        "error": match entity.concurrency {
+                            Concurrency::Version => "send If-Match or the current `version`",
+                            _ => "send If-Match with the current ETag",
+                        },
+                    })),
+                )
+            }
+        },
+    };
+    if matches {
+        return Ok(());
+    }
+    let mut response = HttpResponse::PreconditionFailed();
+    tag(&mut response, entity, item);
+    Err(response.json(json!({
+        "error": "the item changed since it was read",
+        "etag": current,
+    })))
+}
+
+/// Sets the version of an item that had version `previous`, 0 when new.
+pub fn stamp(entity: &Entity, item: &mut Value, previous: u64) {
+    if entity.concurrency == Concurrency::Version {
+        item[VERSION_PROP] = json!(previous + 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test as http, web, App};
+
+    use super::*;
+    use crate::{cli::Config, register_route, store::Store, Prop, TProp};
+
+    fn notes(concurrency: Concurrency) -> Entity {
+        let prop = |id: &str, ty: TProp| Prop {
+            id: id.to_string(),
+            ty,
+            ..Prop::default()
+        };
+        Entity {
+            name: "Note".to_string(),
+            route: "/notes".to_string(),
+            concurrency,
+            props: vec![prop("id", TProp::Number), prop("text", TProp::String)],
+            ..Entity::default()
+        }
+    }
+
+    /// A `--stateful` collection of one note under `concurrency`.
+    struct Notes {
+        entity: Entity,
+        config: web::Data<Config>,
+        store: web::Data<Store>,
+    }
+
+    impl Notes {
+        fn seeded(concurrency: Concurrency) -> Self {
+            let entity = notes(concurrency);
+            let config = web::Data::new(Config {
+                stateful: true,
+                seed_count: 1,
+                ..Config::default()
+            });
+            let store = web::Data::new(Store::default());
+            store.reset(std::slice::from_ref(&entity), &config);
+            Notes {
+                entity,
+                config,
+                store,
+            }
+        }
+
+        /// The status, `ETag` and body `req` gets.
+        async fn send(&self, req: http::TestRequest) -> (StatusCode, Option<String>, Value) {
+            let app = App::new()
+                .app_data(self.config.clone())
+                .app_data(self.store.clone());
+            let app = register_route(app, "/notes", &[&self.entity], &self.config);
+            let app = http::init_service(app).await;
+            let res = http::call_service(&app, req.to_request()).await;
+            let status = res.status();
+            let etag = res
+                .headers()
+                .get(ETAG)
+                .map(|etag| etag.to_str().unwrap().to_string());
+            let body = http::read_body(res).await;
+            (status, etag, serde_json::from_slice(&body).unwrap())
+        }
+
+        async fn get(&self) -> (StatusCode, Option<String>, Value) {
+            self.send(http::TestRequest::get().uri("/notes/1")).await
+        }
+    }
+
+    fn patch(if_match: Option<&str>, body: Value) -> http::TestRequest {
+        let req = http::TestRequest::patch().uri("/notes/1").set_json(body);
+        match if_match {
+            Some(if_match) => req.insert_header((header::IF_MATCH, if_match)),
+            None => req,
+        }
+    }
+
+    #[actix_web::test]
+    async fn versions_start_at_one_and_count_updates() {
+        let notes = Notes::seeded(Concurrency::Version);
+        let (_, etag, note) = notes.get().await;
+        assert_eq!(
+            (etag.as_deref(), &note["version"]),
+            (Some("\"1\""), &json!(1))
+        );
+
+        let req = http::TestRequest::put()
+            .uri("/notes/1")
+            .insert_header((header::IF_MATCH, "\"1\""))
+            .set_json(json!({ "text": "a" }));
+        let (status, etag, note) = notes.send(req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            (etag.as_deref(), &note["version"]),
+            (Some("\"2\""), &json!(2))
+        );
+
+        // The body's `version` stands in for `If-Match`.
+        let (status, etag, note) = notes
+            .send(patch(None, json!({ "text": "b", "version": 2 })))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            (etag.as_deref(), &note["version"]),
+            (Some("\"3\""), &json!(3))
+        );
+        assert_eq!(note["text"], "b");
+    }
+
+    #[actix_web::test]
+    async fn stale_updates_get_412_with_the_current_etag() {
+        let notes = Notes::seeded(Concurrency::Version);
+        let (status, _, _) = notes
+            .send(patch(Some("\"1\""), json!({ "text": "a" })))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+
+        for stale in [
+            patch(Some("\"1\""), json!({ "text": "b" })),
+            patch(None, json!({ "text": "b", "version": 1 })),
+        ] {
+            let (status, etag, body) = notes.send(stale).await;
+            assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+            assert_eq!(etag.as_deref(), Some("\"2\""));
+            assert_eq!(
+                body,
+                json!({ "error": "the item changed since it was read", "etag": "\"2\"" })
+            );
+        }
+        assert_eq!(notes.get().await.2["text"], "a");
+    }
+
+    #[actix_web::test]
+    async fn etags_follow_the_content() {
+        let notes = Notes::seeded(Concurrency::Etag);
+        let (_, first, _) = notes.get().await;
+        let first = first.unwrap();
+
+        let (status, second, _) = notes
+            .send(patch(Some(&format!("W/{first}")), json!({ "text": "a" })))
+            .await;
+        assert_eq!(status, StatusCode::OK);
+        let second = second.unwrap();
+        assert_ne!(first, second);
+        assert_eq!(notes.get().await.1.as_deref(), Some(second.as_str()));
+
+        let (status, _, _) = notes
+            .send(patch(Some(&first), json!({ "text": "b" })))
+            .await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        let (status, _, _) = notes.send(patch(Some("*"), json!({ "text": "b" }))).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn updates_without_a_precondition_get_428() {
+        for (concurrency, error) in [
+            (Concurrency::Etag, "send If-Match with the current ETag"),
+            (
+                Concurrency::Version,
+                "send If-Match or the current `version`",
+            ),
+        ] {
+            let notes = Notes::seeded(concurrency);
+            let (status, _, body) = notes.send(patch(None, json!({ "text": "a" }))).await;
+            assert_eq!(status, StatusCode::PRECONDITION_REQUIRED);
+            assert_eq!(body, json!({ "error": error }));
+        }
+    }
+
+    #[actix_web::test]
+    async fn off_sends_no_etag_and_needs_no_precondition() {
+        let notes = Notes::seeded(Concurrency::Off);
+        assert_eq!(notes.get().await.1, None);
+        let (status, etag, _) = notes.send(patch(None, json!({ "text": "a" }))).await;
+        assert_eq!((status, etag), (StatusCode::OK, None));
+    }
+}
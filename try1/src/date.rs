@@ -0,0 +1,157 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DAY: i64 = 24 * 60 * 60;
+pub const YEAR: i64 = 365 * DAY;
+
+/// The first and last seconds `parse` accepts and `format` is given:
+/// years 0 through 9999, which four digits hold.
+pub const EARLIEST: i64 = -62_167_219_200;
+pub const LATEST: i64 = 253_402_300_799;
+
+/// Seconds since the Unix epoch.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
+
+/// Parses `2020-01-01`, `2020-01-01T12:30:00` or `2020-01-01T12:30:00Z` (UTC)
+/// into seconds since the epoch.
+pub fn parse(s: &str) -> Option<i64> {
+    let s = s.trim().trim_end_matches('Z');
+    let (date, time) = match s.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (s, None),
+    };
+    let mut parts = date.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    if !(0..=9999).contains(&year)
+        || !(1..=12).contains(&month)
+        || day < 1
+        || day > days_in_month(year, month)
+    {
+        return None;
+    }
+
+    let seconds = match time {
+        None => 0,
+        Some(time) => {
+            let mut parts = time.splitn(3, ':');
+            let hour: i64 = parts.next()?.parse().ok()?;
+            let minute: i64 = parts.next()?.parse().ok()?;
+            let second: i64 = parts.next().map_or(Some(0), |s| s.parse().ok())?;
+            if hour > 23 || minute > 59 || second > 59 {
+                return None;
+            }
+            hour * 3600 + minute * 60 + second
+        }
+    };
+    Some(days_from_civil(year, month, day) * DAY + seconds)
+}
+
+/// What "now" is for generated dates and store timestamps, from `--now`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Clock {
+    #[default]
+    Real,
+    /// Always this time.
+    Fixed(i64),
+    /// Real time shifted by this many seconds.
+    Offset(i64),
+}
+
+impl Clock {
+    /// Parses a date to freeze time at, such as `2024-06-01T12:00:00Z`, or
+    /// an offset from real time: `+3d`, `-2h`, `+90m`, `+1w`, `-30s`. Either
+    /// must land in years 0 through 9999.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let s = s.trim();
+        let Some(sign) = s.strip_prefix(['+', '-']).map(|_| &s[..1]) else {
+            return parse(s).map(Clock::Fixed).ok_or_else(|| {
+                format!(
+                    "expected a date like 2024-06-01T12:00:00Z or an offset like +3d, got `{s}`"
+                )
+            });
+        };
+        let offset = &s[1..];
+        let unit = match offset.chars().last() {
+            Some('s') => 1,
+            Some('m') => 60,
+            Some('h') => 60 * 60,
+            Some('d') => DAY,
+            Some('w') => 7 * DAY,
+            _ => return Err(format!("offset `{s}` needs a unit: s, m, h, d or w")),
+        };
+        let amount: i64 = offset[..offset.len() - 1]
+            .parse()
+            .map_err(|_| format!("invalid offset `{s}`"))?;
+        let seconds = amount
+            .checked_mul(unit)
+            .map(|seconds| if sign == "-" { -seconds } else { seconds })
+            .filter(|seconds| {
+                now()
+                    .checked_add(*seconds)
+                    .is_some_and(|at| (EARLIEST..=LATEST).contains(&at))
+            })
+            .ok_or_else(|| format!("offset `{s}` is out of range: years 0 through 9999"))?;
+        Ok(Clock::Offset(seconds))
+    }
+
+    /// Seconds since the epoch.
+    pub fn now(self) -> i64 {
+        match self {
+            Clock::Real => now(),
+            Clock::Fixed(timestamp) => timestamp,
+            Clock::Offset(offset) => now().saturating_add(offset).clamp(EARLIEST, LATEST),
+        }
+    }
+}
+
+/// Formats seconds since the epoch as ISO-8601 in UTC.
+pub fn format(timestamp: i64) -> String {
+    let (days, seconds) = (timestamp.div_euclid(DAY), timestamp.rem_euclid(DAY));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Howard Hinnant's civil calendar algorithms, counting days from 1970-01-01.
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -0,0 +1,111 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::warning;
+
+/// Comment that starts a file-level defaults block.
+pub const FILE_DIRECTIVE: &str = "ssg:defaults";
+/// Defaults shared by every source in a directory and below it.
+pub const DIRECTORY_FILE: &str = "ssg.defaults.toml";
+
+/// Where an entity setting came from. Precedence runs entity > file >
+/// directory > global.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    Global,
+    Directory(PathBuf),
+    File,
+    Entity,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::Global => write!(f, "global"),
+            Origin::Directory(path) => write!(f, "directory, {}", path.display()),
+            Origin::File => write!(f, "file"),
+            Origin::Entity => write!(f, "entity"),
+        }
+    }
+}
+
+/// An entity-level tag as finally applied, for `--dry-run`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting {
+    pub tag: String,
+    pub value: String,
+    pub origin: Origin,
+}
+
+/// Entity tags layered by origin: a layer setting a tag replaces every value
+/// earlier layers gave it, so an entity's `@tag` overrides the file's.
+#[derive(Debug, Clone, Default)]
+pub struct Defaults {
+    entries: Vec<(String, Vec<String>, Origin)>,
+}
+
+impl Defaults {
+    pub fn layer(&mut self, tags: &[(String, String)], origin: Origin) {
+        let mut layered: Vec<(String, Vec<String>, Origin)> = Vec::new();
+        for (tag, value) in tags {
+            match layered.iter_mut().find(|(t, _, _)| t == tag) {
+                Some((_, values, _)) => values.push(value.clone()),
+                None => layered.push((tag.clone(), vec![value.clone()], origin.clone())),
+            }
+        }
+        for entry in layered {
+            match self.entries.iter_mut().find(|(t, _, _)| *t == entry.0) {
+                Some(existing) => *existing = entry,
+                None => self.entries.push(entry),
+            }
+        }
+    }
+
+    /// Each tag's values in application order, with their origin.
+    pub fn settings(&self) -> impl Iterator<Item = Setting> + '_ {
+        self.entries.iter().flat_map(|(tag, values, origin)| {
+            values.iter().map(|value| Setting {
+                tag: tag.clone(),
+                value: value.clone(),
+                origin: origin.clone(),
+            })
+        })
+    }
+}
+
+/// Reads `dir/ssg.defaults.toml` into tag/value pairs, `None` when absent.
+/// Keys are tag names; arrays give a tag several values.
+pub fn read_directory_file(dir: &Path) -> Option<Vec<(String, String)>> {
+    let path = dir.join(DIRECTORY_FILE);
+    let text = fs::read_to_string(&path).ok()?;
+    let table: toml::Table = match text.parse() {
+        Ok(table) => table,
+        Err(err) => {
+            warning::warn(&path, err);
+            return None;
+        }
+    };
+
+    let mut tags = Vec::new();
+    for (key, value) in table {
+        let values = match value {
+            toml::Value::Array(items) => items,
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                toml::Value::String(s) => tags.push((key.clone(), s)),
+                toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => {
+                    tags.push((key.clone(), value.to_string()))
+                }
+                _ => warning::warn(
+                    &path,
+                    format!("`{key}` must be a string, number or boolean"),
+                ),
+            }
+        }
+    }
+    Some(tags)
+}
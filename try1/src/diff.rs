@@ -0,0 +1,164 @@
+use serde_json::{json, Map, Value};
+
+/// A structural diff from `a` to `b`, keyed by JSON Pointer paths. Objects
+/// are compared by key and arrays by index; anything else that differs is
+/// reported as changed at its path.
+pub fn diff(a: &Value, b: &Value) -> Value {
+    let mut changes = Changes::default();
+    compare("", a, b, &mut changes);
+    json!({
+        "added": changes.added,
+        "removed": changes.removed,
+        "changed": changes.changed,
+    })
+}
+
+#[derive(Default)]
+struct Changes {
+    added: Vec<Value>,
+    removed: Vec<Value>,
+    changed: Vec<Value>,
+}
+
+fn compare(path: &str, a: &Value, b: &Value, changes: &mut Changes) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => compare_objects(path, a, b, changes),
+        (Value::Array(a), Value::Array(b)) => {
+            for (index, (a, b)) in a.iter().zip(b).enumerate() {
+                compare(&format!("{path}/{index}"), a, b, changes);
+            }
+            for (index, value) in b.iter().enumerate().skip(a.len()) {
+                changes
+                    .added
+                    .push(json!({ "path": format!("{path}/{index}"), "value": value }));
+            }
+            for (index, value) in a.iter().enumerate().skip(b.len()) {
+                changes
+                    .removed
+                    .push(json!({ "path": format!("{path}/{index}"), "value": value }));
+            }
+        }
+        (a, b) if a != b => changes
+            .changed
+            .push(json!({ "path": path, "a": a, "b": b })),
+        _ => (),
+    }
+}
+
+fn compare_objects(
+    path: &str,
+    a: &Map<String, Value>,
+    b: &Map<String, Value>,
+    changes: &mut Changes,
+) {
+    for (key, a_value) in a {
+        let path = format!("{path}/{}", escape(key));
+        match b.get(key) {
+            Some(b_value) => compare(&path, a_value, b_value, changes),
+            None => changes
+                .removed
+                .push(json!({ "path": path, "value": a_value })),
+        }
+    }
+    for (key, b_value) in b {
+        if !a.contains_key(key) {
+            let path = format!("{path}/{}", escape(key));
+            changes
+                .added
+                .push(json!({ "path": path, "value": b_value }));
+        }
+    }
+}
+
+/// Escapes a key as a JSON Pointer reference token (RFC 6901).
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_values_have_no_changes() {
+        let value = json!({ "a": [1, { "b": null }], "c": "d" });
+        assert_eq!(
+            diff(&value, &value),
+            json!({ "added": [], "removed": [], "changed": [] })
+        );
+    }
+
+    #[test]
+    fn nested_object_changes_are_at_their_path() {
+        let a = json!({ "user": { "name": "ann", "address": { "zip": "1000" } } });
+        let b = json!({ "user": { "name": "ann", "address": { "zip": "2000" } } });
+        assert_eq!(
+            diff(&a, &b)["changed"],
+            json!([{ "path": "/user/address/zip", "a": "1000", "b": "2000" }])
+        );
+    }
+
+    #[test]
+    fn keys_are_added_and_removed() {
+        let a = json!({ "kept": 1, "gone": { "x": true } });
+        let b = json!({ "kept": 1, "new": [2] });
+        assert_eq!(
+            diff(&a, &b),
+            json!({
+                "added": [{ "path": "/new", "value": [2] }],
+                "removed": [{ "path": "/gone", "value": { "x": true } }],
+                "changed": [],
+            })
+        );
+    }
+
+    #[test]
+    fn array_elements_compare_by_index() {
+        let a = json!({ "items": [{ "id": 1 }, { "id": 2 }] });
+        let b = json!({ "items": [{ "id": 1 }, { "id": 3 }] });
+        assert_eq!(
+            diff(&a, &b)["changed"],
+            json!([{ "path": "/items/1/id", "a": 2, "b": 3 }])
+        );
+    }
+
+    #[test]
+    fn longer_and_shorter_arrays_add_and_remove_their_tails() {
+        let longer = diff(&json!([1, 2]), &json!([1, 2, 3, 4]));
+        assert_eq!(
+            longer["added"],
+            json!([{ "path": "/2", "value": 3 }, { "path": "/3", "value": 4 }])
+        );
+        assert_eq!(longer["removed"], json!([]));
+
+        let shorter = diff(&json!([[1, 2, 3]]), &json!([[1]]));
+        assert_eq!(
+            shorter["removed"],
+            json!([{ "path": "/0/1", "value": 2 }, { "path": "/0/2", "value": 3 }])
+        );
+        assert_eq!(shorter["added"], json!([]));
+    }
+
+    #[test]
+    fn values_of_another_type_are_changed_whole() {
+        let a = json!({ "tags": ["a"] });
+        let b = json!({ "tags": { "0": "a" } });
+        assert_eq!(
+            diff(&a, &b)["changed"],
+            json!([{ "path": "/tags", "a": ["a"], "b": { "0": "a" } }])
+        );
+    }
+
+    #[test]
+    fn keys_are_escaped_as_json_pointer_tokens() {
+        let a = json!({ "a/b": { "c~d": 1 }, "~/": 1 });
+        let b = json!({ "a/b": { "c~d": 2 }, "~/": 2 });
+        assert_eq!(
+            diff(&a, &b)["changed"],
+            json!([
+                { "path": "/a~1b/c~0d", "a": 1, "b": 2 },
+                { "path": "/~0~1", "a": 1, "b": 2 },
+            ])
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt::Write;
+
+use actix_web::{web, HttpResponse};
+
+use crate::{cli::Config, date, wildcard_prefix, Entity, NumberFormat, Prop, TProp};
+
+/// Kept inline so the page works offline and from any origin.
+const STYLE: &str = "
+body { font: 15px/1.5 system-ui, sans-serif; margin: 2rem auto; max-width: 60rem; padding: 0 1rem; color: #222; }
+section { border-top: 1px solid #ddd; padding: 1rem 0; }
+h2 { margin: 0 0 .25rem; }
+code { background: #f4f4f4; padding: 0 .25rem; border-radius: 3px; }
+.meta { color: #666; font-size: 13px; }
+table { border-collapse: collapse; width: 100%; margin-top: .75rem; font-size: 14px; }
+th, td { text-align: left; padding: .3rem .5rem; border-bottom: 1px solid #eee; vertical-align: top; }
+th { background: #fafafa; }
+";
+
+/// `GET /__entities`: every entity with its routes and a table of its props,
+/// for browsing what the mock serves without reading TypeScript.
+pub async fn page(entities: web::Data<Vec<Entity>>, config: web::Data<Config>) -> HttpResponse {
+    let root = std::env::current_dir().unwrap_or_default();
+    let mut html = format!(
+        "<!doctype html><html><head><meta charset=\"utf-8\"><title>Entities</title><style>{STYLE}</style></head><body><h1>Entities</h1><p class=\"meta\">{} entities</p>",
+        entities.len()
+    );
+    for entity in entities.iter() {
+        let path = entity.served_route(config.version_prefix);
+        let _ = write!(html, "<section><h2>{}</h2>", escape(&entity.name));
+        for route in routes(entity, &path, config.stateful) {
+            let _ = write!(html, "<div><code>{}</code>", escape(&route));
+            if !route.contains(['{', '*']) {
+                let _ = write!(
+                    html,
+                    " <a href=\"{}?pretty=true\">try it</a>",
+                    escape(&route)
+                );
+            }
+            html.push_str("</div>");
+        }
+        if let Some(description) = &entity.description {
+            let _ = write!(html, "<p>{}</p>", escape(description));
+        }
+        let source = entity.source.strip_prefix(&root).unwrap_or(&entity.source);
+        let _ = write!(
+            html,
+            "<p class=\"meta\">{}declared in <a href=\"file://{}\">{}</a></p>",
+            if entity.list { "a list, " } else { "" },
+            escape(&entity.source.display().to_string()),
+            escape(&source.display().to_string())
+        );
+        html.push_str(
+            "<table><tr><th>Prop</th><th>Type</th><th>Optional</th><th>Constraints</th><th>Hints</th></tr>",
+        );
+        rows(&mut html, &entity.props, "");
+        html.push_str("</table></section>");
+    }
+    html.push_str("</body></html>");
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(html)
+}
+
+/// The paths an entity answers on: the collection, and under `--stateful`
+/// its items too.
+fn routes(entity: &Entity, path: &str, stateful: bool) -> Vec<String> {
+    let mut routes = vec![path.to_string()];
+    if stateful && wildcard_prefix(path).is_none() {
+        routes.push(entity.item_route(path));
+    }
+    routes
+}
+
+/// One row per prop, nested objects' props following as `parent.child`.
+fn rows(html: &mut String, props: &[Prop], parent: &str) {
+    for prop in props {
+        let name = format!("{parent}{}", prop.id);
+        let _ = write!(
+            html,
+            "<tr><td><code>{}</code></td><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&name),
+            escape(&type_name(&prop.ty)),
+            if prop.optional { "yes" } else { "" },
+            escape(&constraints(prop).join(", ")),
+            escape(&hints(prop).join(" — "))
+        );
+        let mut ty = &prop.ty;
+        while let TProp::Array(item) = ty {
+            ty = item;
+        }
+        if let TProp::Object { props, .. } = ty {
+            rows(html, props, &format!("{name}."));
+        }
+    }
+}
+
+pub fn type_name(ty: &TProp) -> String {
+    match ty {
+        TProp::Boolean => "boolean".to_string(),
+        TProp::Number => "number".to_string(),
+        TProp::BigInt => "bigint".to_string(),
+        TProp::String => "string".to_string(),
+        TProp::Date => "Date".to_string(),
+        TProp::Enum(values) => values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(" | "),
+        TProp::Object { name, .. } => name.clone(),
+        TProp::Array(item) => format!("{}[]", type_name(item)),
+    }
+}
+
+pub fn constraints(prop: &Prop) -> Vec<String> {
+    let mut constraints = Vec::new();
+    if let Some(min) = prop.min {
+        constraints.push(format!("min {min}"));
+    }
+    if let Some(max) = prop.max {
+        constraints.push(format!("max {max}"));
+    }
+    if let Some(min) = prop.min_items {
+        constraints.push(format!("min {min} items"));
+    }
+    if let Some(max) = prop.max_items {
+        constraints.push(format!("max {max} items"));
+    }
+    if let Some(min) = prop.min_date {
+        constraints.push(format!("from {}", date::format(min)));
+    }
+    if let Some(max) = prop.max_date {
+        constraints.push(format!("until {}", date::format(max)));
+    }
+    if let Some(rate) = prop.empty_rate {
+        constraints.push(format!("empty {:.0}%", rate * 100.0));
+    }
+    if let Some(rate) = prop.present_rate {
+        constraints.push(format!("present {:.0}%", rate * 100.0));
+    }
+    constraints
+}
+
+pub fn hints(prop: &Prop) -> Vec<String> {
+    let mut hints = Vec::new();
+    if let Some(faker) = prop.faker {
+        hints.push(format!("faker {}", faker.name()));
+    }
+    match &prop.number_format {
+        Some(NumberFormat::Int) => hints.push("integer".to_string()),
+        Some(NumberFormat::Float { precision }) => hints.push(format!("{precision} decimals")),
+        Some(NumberFormat::Currency(code)) => hints.push(format!("currency {code}")),
+        Some(NumberFormat::Rating) => hints.push("rating in halves".to_string()),
+        None => (),
+    }
+    if prop.key {
+        hints.push("key".to_string());
+    }
+    if prop.as_string {
+        hints.push("sent as a string".to_string());
+    }
+    if let Some(description) = &prop.description {
+        hints.push(description.clone());
+    }
+    hints
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderValue, CONTENT_TYPE},
+    middleware::Next,
+    web::{self, Bytes},
+    Error, HttpRequest,
+};
+use futures_util::{stream, Stream};
+use serde_json::Value;
+
+use crate::{cli::Config, Entity};
+
+pub const JSON_UTF8: &str = "application/json; charset=utf-8";
+
+/// `?pretty=true` (or `1`) indents the response, `?pretty=false` compacts
+/// it; otherwise `--pretty` decides.
+pub fn pretty(req: &HttpRequest) -> bool {
+    let default = req
+        .app_data::<web::Data<Config>>()
+        .is_some_and(|config| config.pretty);
+    web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .ok()
+        .and_then(|query| match query.get("pretty")?.as_str() {
+            "" | "true" | "1" => Some(true),
+            "false" | "0" => Some(false),
+            _ => None,
+        })
+        .unwrap_or(default)
+}
+
+/// The `@body-from` prop's value as the response body: strings verbatim,
+/// other scalars as their JSON text.
+pub fn raw_body(entity: &Entity, data: &Value) -> Option<Vec<u8>> {
+    let value = data.get(entity.body_from.as_deref()?)?;
+    Some(match value {
+        Value::String(s) => s.clone().into_bytes(),
+        value => value.to_string().into_bytes(),
+    })
+}
+
+/// Whether a media type is JSON, e.g. `application/problem+json`.
+pub fn is_json(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim();
+    essence.eq_ignore_ascii_case("application/json") || essence.ends_with("+json")
+}
+
+pub fn to_json(data: &Value, pretty: bool) -> Vec<u8> {
+    let body = if pretty {
+        serde_json::to_vec_pretty(data)
+    } else {
+        serde_json::to_vec(data)
+    };
+    body.unwrap_or_default()
+}
+
+/// Serializes `items` as a JSON array one item per chunk, laid out like
+/// `to_json` would. Items are only pulled as the stream is polled, so a
+/// streaming body generates them as fast as the client reads.
+pub fn json_array(
+    items: impl Iterator<Item = Value>,
+    pretty: bool,
+) -> impl Stream<Item = Result<Bytes, Error>> {
+    let mut items = items.fuse();
+    let (mut opened, mut empty, mut closed) = (false, true, false);
+    stream::iter(std::iter::from_fn(move || {
+        if closed {
+            return None;
+        }
+        let mut chunk = Vec::new();
+        if !opened {
+            chunk.push(b'[');
+            opened = true;
+        }
+        match items.next() {
+            Some(item) => {
+                if !empty {
+                    chunk.push(b',');
+                }
+                empty = false;
+                let json = to_json(&item, pretty);
+                if pretty {
+                    chunk.extend(b"\n  ");
+                    for &byte in &json {
+                        chunk.push(byte);
+                        if byte == b'\n' {
+                            chunk.extend(b"  ");
+                        }
+                    }
+                } else {
+                    chunk.extend(json);
+                }
+            }
+            None => {
+                if pretty && !empty {
+                    chunk.push(b'\n');
+                }
+                chunk.push(b']');
+                closed = true;
+            }
+        }
+        Some(Ok(Bytes::from(chunk)))
+    }))
+}
+
+/// Adds `charset=utf-8` to JSON responses that were sent without one.
+pub async fn json_charset(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let mut res = next.call(req).await?;
+    let headers = res.headers_mut();
+    if headers
+        .get(CONTENT_TYPE)
+        .is_some_and(|ty| ty.as_bytes().eq_ignore_ascii_case(b"application/json"))
+    {
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(JSON_UTF8));
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use futures_util::StreamExt;
+    use serde_json::json;
+
+    use super::*;
+
+    async fn collect(items: Vec<Value>, pretty: bool) -> Vec<u8> {
+        let chunks: Vec<_> = json_array(items.into_iter(), pretty).collect().await;
+        chunks
+            .into_iter()
+            .flat_map(|chunk| chunk.unwrap())
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn streamed_arrays_match_to_json() {
+        for items in [
+            vec![],
+            vec![json!(1)],
+            vec![json!({ "a": [1, 2] }), json!("b")],
+        ] {
+            for pretty in [false, true] {
+                assert_eq!(
+                    collect(items.clone(), pretty).await,
+                    to_json(&Value::Array(items.clone()), pretty),
+                    "{items:?}, pretty: {pretty}"
+                );
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn items_are_pulled_only_as_chunks_are_read() {
+        let pulled = Rc::new(Cell::new(0u64));
+        let counted = pulled.clone();
+        let items = (0..u64::MAX).map(move |n| {
+            counted.set(counted.get() + 1);
+            json!({ "n": n })
+        });
+        let mut stream = Box::pin(json_array(items, false));
+        let mut read = Vec::new();
+        for _ in 0..3 {
+            read.extend(stream.next().await.unwrap().unwrap());
+        }
+        assert_eq!(pulled.get(), 3);
+        assert_eq!(read, br#"[{"n":0},{"n":1},{"n":2}"#);
+    }
+
+    #[actix_web::test]
+    async fn a_throttled_stream_pulls_no_further_than_it_has_sent() {
+        let pulled = Rc::new(Cell::new(0u64));
+        let counted = pulled.clone();
+        let items = (0..u64::MAX).map(move |n| {
+            counted.set(counted.get() + 1);
+            json!({ "n": n })
+        });
+        // 40 bytes/s goes out 4 bytes a tick.
+        let mut stream = Box::pin(crate::throttle::paced(json_array(items, false), 40));
+        let mut read = Vec::new();
+        for _ in 0..6 {
+            read.extend(stream.next().await.unwrap().unwrap());
+        }
+        assert_eq!(read, br#"[{"n":0},{"n":1},{"n":2}"#);
+        assert_eq!(pulled.get(), 3);
+    }
+
+    #[actix_web::test]
+    async fn a_throttled_stream_joins_small_items_into_whole_ticks() {
+        let items = (0..u64::MAX).map(|n| json!({ "n": n % 10 }));
+        // 800 bytes/s goes out 80 bytes, ten 8-byte items, a tick.
+        let mut stream = Box::pin(crate::throttle::paced(json_array(items, false), 800));
+        for _ in 0..3 {
+            assert_eq!(stream.next().await.unwrap().unwrap().len(), 80);
+        }
+    }
+}
@@ -0,0 +1,701 @@
+use std::collections::HashMap;
+
+use actix_web::{http::header, HttpRequest};
+use fake::{
+    faker, locales,
+    rand::{rngs::StdRng, seq::IndexedRandom, Rng, SeedableRng},
+    Fake, Faker,
+};
+use serde_json::{json, Number, Value};
+
+use crate::{assets, cli::Config, date, key_name, Entity, Prop, TProp};
+
+/// Value generators selectable with `@faker <kind>` on a prop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FakerKind {
+    Email,
+    Name,
+    FirstName,
+    LastName,
+    Username,
+    Phone,
+    City,
+    Country,
+    Company,
+    Sentence,
+    /// A URL to a placeholder image served by the mock itself.
+    ImageUrl,
+    /// A random (version 4) UUID.
+    Uuid,
+    Url,
+    Ipv4,
+    Ipv6,
+}
+
+impl FakerKind {
+    pub fn parse(s: &str) -> Option<Self> {
+        let kind = match s.to_ascii_lowercase().replace(['-', '_'], "").as_str() {
+            "email" => FakerKind::Email,
+            "name" | "fullname" => FakerKind::Name,
+            "firstname" => FakerKind::FirstName,
+            "lastname" => FakerKind::LastName,
+            "username" => FakerKind::Username,
+            "phone" => FakerKind::Phone,
+            "city" => FakerKind::City,
+            "country" => FakerKind::Country,
+            "company" => FakerKind::Company,
+            "sentence" => FakerKind::Sentence,
+            "imageurl" => FakerKind::ImageUrl,
+            "uuid" => FakerKind::Uuid,
+            "url" | "uri" => FakerKind::Url,
+            "ipv4" => FakerKind::Ipv4,
+            "ipv6" => FakerKind::Ipv6,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FakerKind::Email => "email",
+            FakerKind::Name => "name",
+            FakerKind::FirstName => "first-name",
+            FakerKind::LastName => "last-name",
+            FakerKind::Username => "username",
+            FakerKind::Phone => "phone",
+            FakerKind::City => "city",
+            FakerKind::Country => "country",
+            FakerKind::Company => "company",
+            FakerKind::Sentence => "sentence",
+            FakerKind::ImageUrl => "image-url",
+            FakerKind::Uuid => "uuid",
+            FakerKind::Url => "url",
+            FakerKind::Ipv4 => "ipv4",
+            FakerKind::Ipv6 => "ipv6",
+        }
+    }
+
+    /// The kind a JSON Schema string `format` asks for, if it's one we fake.
+    pub fn from_format(format: &str) -> Option<Self> {
+        let kind = match format {
+            "email" | "idn-email" => FakerKind::Email,
+            "uuid" => FakerKind::Uuid,
+            "uri" | "url" | "iri" => FakerKind::Url,
+            "ipv4" => FakerKind::Ipv4,
+            "ipv6" => FakerKind::Ipv6,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// The JSON Schema `format` of the strings this kind generates.
+    pub fn format(self) -> Option<&'static str> {
+        match self {
+            FakerKind::Email => Some("email"),
+            FakerKind::Uuid => Some("uuid"),
+            FakerKind::Url | FakerKind::ImageUrl => Some("uri"),
+            FakerKind::Ipv4 => Some("ipv4"),
+            FakerKind::Ipv6 => Some("ipv6"),
+            _ => None,
+        }
+    }
+}
+
+/// How a `number` prop's values look, from `@int`, `@float`, `@currency`
+/// or the prop's name. Without one, numbers are integers.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NumberFormat {
+    Int,
+    /// Rounded to this many decimals.
+    Float {
+        precision: u32,
+    },
+    /// Two decimals, never negative; the ISO 4217 code is informational.
+    Currency(String),
+    /// Zero to five in steps of a half.
+    Rating,
+}
+
+impl NumberFormat {
+    /// Decimals the values carry.
+    pub fn precision(&self) -> u32 {
+        match self {
+            NumberFormat::Int => 0,
+            NumberFormat::Float { precision } => *precision,
+            NumberFormat::Currency(_) => 2,
+            NumberFormat::Rating => 1,
+        }
+    }
+
+    /// The format a prop's name suggests, with the range it implies:
+    /// prices, ratings and coordinates.
+    pub fn from_name(name: &str) -> Option<(Self, f64, f64)> {
+        let name = name.to_ascii_lowercase();
+        let hint = match name.as_str() {
+            "lat" | "latitude" => (NumberFormat::Float { precision: 6 }, -90.0, 90.0),
+            "lng" | "lon" | "long" | "longitude" => {
+                (NumberFormat::Float { precision: 6 }, -180.0, 180.0)
+            }
+            "rating" => (NumberFormat::Rating, 0.0, 5.0),
+            name if name.ends_with("price") || name == "amount" || name == "cost" => {
+                (NumberFormat::Float { precision: 2 }, 0.0, 1000.0)
+            }
+            _ => return None,
+        };
+        Some(hint)
+    }
+}
+
+/// Data set for generated names, places and text, from `--locale`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    FrFr,
+    DeDe,
+    JaJp,
+    ZhCn,
+    ZhTw,
+    PtBr,
+    PtPt,
+    ArSa,
+}
+
+impl Locale {
+    /// Parses a language tag such as `fr`, `fr-FR` or `pt_PT`. A region
+    /// only matters where there's a data set for it (`pt-PT`, `zh-TW`);
+    /// otherwise the language picks the locale.
+    pub fn parse(tag: &str) -> Option<Self> {
+        let tag = tag.trim().replace('_', "-").to_ascii_lowercase();
+        let locale = match tag.as_str() {
+            "pt-pt" => Locale::PtPt,
+            "zh-tw" | "zh-hant" => Locale::ZhTw,
+            tag => match tag.split('-').next().unwrap_or_default() {
+                "en" => Locale::En,
+                "fr" => Locale::FrFr,
+                "de" => Locale::DeDe,
+                "ja" => Locale::JaJp,
+                "zh" => Locale::ZhCn,
+                "pt" => Locale::PtBr,
+                "ar" => Locale::ArSa,
+                _ => return None,
+            },
+        };
+        Some(locale)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::FrFr => "fr-FR",
+            Locale::DeDe => "de-DE",
+            Locale::JaJp => "ja-JP",
+            Locale::ZhCn => "zh-CN",
+            Locale::ZhTw => "zh-TW",
+            Locale::PtBr => "pt-BR",
+            Locale::PtPt => "pt-PT",
+            Locale::ArSa => "ar-SA",
+        }
+    }
+}
+
+/// The locale to generate a response in: under `--locale-from-header`, the
+/// first `Accept-Language` entry whose language has a data set, by primary
+/// tag only and ignoring quality values; `--locale` otherwise.
+fn requested_locale(req: &HttpRequest, config: &Config) -> Locale {
+    if !config.locale_from_header {
+        return config.locale;
+    }
+    req.headers()
+        .get(header::ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            value.split(',').find_map(|entry| {
+                let tag = entry.split(';').next().unwrap_or_default().trim();
+                Locale::parse(tag.split(['-', '_']).next().unwrap_or_default())
+            })
+        })
+        .unwrap_or(config.locale)
+}
+
+/// What a response is generated for: the locale, and the time dates are
+/// relative to.
+#[derive(Debug, Clone, Copy)]
+pub struct Context {
+    pub locale: Locale,
+    /// Seconds since the epoch.
+    pub now: i64,
+}
+
+impl Context {
+    /// The configured locale and clock, for data not generated for a request.
+    pub fn of(config: &Config) -> Self {
+        Context {
+            locale: config.locale,
+            now: config.clock.now(),
+        }
+    }
+}
+
+/// Header that sets "now" for one request, like `--now`.
+pub const NOW_HEADER: &str = "X-Mock-Now";
+
+/// The context to generate a response to `req` in: its locale, and
+/// `X-Mock-Now` over `--now` as the clock.
+pub fn requested(req: &HttpRequest, config: &Config) -> Result<Context, String> {
+    let clock = match req.headers().get(NOW_HEADER) {
+        Some(value) => value
+            .to_str()
+            .map_err(|_| format!("{NOW_HEADER} isn't valid text"))
+            .and_then(date::Clock::parse)
+            .map_err(|error| format!("{NOW_HEADER}: {error}"))?,
+        None => config.clock,
+    };
+    Ok(Context {
+        locale: requested_locale(req, config),
+        now: clock.now(),
+    })
+}
+
+/// RNG for one generation: fixed per path under `--seed`, fresh otherwise.
+pub fn rng_for(seed: Option<u64>, path: &str) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed ^ fnv1a(path)),
+        None => StdRng::from_rng(&mut fake::rand::rng()),
+    }
+}
+
+/// Stable string hash, unlike `DefaultHasher` which may change between releases.
+fn fnv1a(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Items in a list response when the request doesn't give `?count=`.
+pub const DEFAULT_COUNT: usize = 10;
+
+/// What a GET returns: one object, or `count` of them (by default
+/// `DEFAULT_COUNT`) for list entities.
+pub fn fake_response<R: Rng + ?Sized>(
+    entity: &Entity,
+    config: &Config,
+    ctx: Context,
+    count: Option<usize>,
+    rng: &mut R,
+) -> Value {
+    if !entity.list {
+        return fake_object(entity, config, ctx, rng);
+    }
+    let count = count.unwrap_or(DEFAULT_COUNT);
+    let mut memo = Memo::default();
+    Value::Array(
+        (0..count)
+            .map(|_| fake_list_item(entity, config, ctx, &mut memo, rng))
+            .collect(),
+    )
+}
+
+pub fn fake_object<R: Rng + ?Sized>(
+    entity: &Entity,
+    config: &Config,
+    ctx: Context,
+    rng: &mut R,
+) -> Value {
+    fake_props(&entity.props, config, ctx, &mut Memo::default(), rng)
+}
+
+/// One item of a list response; `memo` is shared by the whole list.
+pub fn fake_list_item<R: Rng + ?Sized>(
+    entity: &Entity,
+    config: &Config,
+    ctx: Context,
+    memo: &mut Memo,
+    rng: &mut R,
+) -> Value {
+    fake_props(&entity.props, config, ctx, memo, rng)
+}
+
+/// Most nested objects a `Memo` remembers; later ones are left as generated.
+const MEMO_CAPACITY: usize = 10_000;
+
+/// The nested objects generated so far in one response, by type and `id`,
+/// so that every reference to the same id comes out as the same object.
+#[derive(Default)]
+pub struct Memo(HashMap<(String, String), Value>);
+
+impl Memo {
+    /// The object generated earlier with `object`'s type and key (its `@key`
+    /// prop, or `id`), or `object` itself, remembered for later references.
+    /// Objects without a key aren't shared.
+    fn reuse(&mut self, name: &str, key: &str, object: Value) -> Value {
+        let Some(id) = object.get(key).filter(|id| !id.is_null()) else {
+            return object;
+        };
+        let key = (name.to_string(), id.to_string());
+        if let Some(earlier) = self.0.get(&key) {
+            return earlier.clone();
+        }
+        if self.0.len() < MEMO_CAPACITY {
+            self.0.insert(key, object.clone());
+        }
+        object
+    }
+}
+
+fn fake_props<R: Rng + ?Sized>(
+    props: &[Prop],
+    config: &Config,
+    ctx: Context,
+    memo: &mut Memo,
+    rng: &mut R,
+) -> Value {
+    let mut data = json!({});
+
+    for prop in props {
+        if prop.optional {
+            let rate = prop.present_rate.unwrap_or(config.optional_present_rate);
+            if !rng.random_bool(rate) {
+                continue;
+            }
+        }
+        data[&prop.id] = fake_value(prop, config, ctx, memo, rng);
+    }
+
+    data
+}
+
+fn fake_value<R: Rng + ?Sized>(
+    prop: &Prop,
+    config: &Config,
+    ctx: Context,
+    memo: &mut Memo,
+    rng: &mut R,
+) -> Value {
+    let value = match &prop.ty {
+        TProp::Boolean => Value::Bool(Faker.fake_with_rng(rng)),
+        TProp::Number => match &prop.number_format {
+            None | Some(NumberFormat::Int) => Value::Number(Number::from(fake_number(prop, rng))),
+            Some(format) => fake_decimal(prop, format, rng),
+        },
+        TProp::BigInt => Value::Number(Number::from(fake_bigint(prop, rng))),
+        TProp::String if prop.faker == Some(FakerKind::ImageUrl) => {
+            Value::String(assets::image_url(config, rng))
+        }
+        TProp::String => Value::String(fake_string(prop.faker, ctx.locale, rng)),
+        TProp::Enum(values) => values.choose(rng).cloned().unwrap_or(Value::Null),
+        TProp::Object { name, props } => {
+            let object = fake_props(props, config, ctx, memo, rng);
+            memo.reuse(name, key_name(props), object)
+        }
+        TProp::Array(_) => fake_array(prop, config, ctx, memo, rng),
+        TProp::Date => Value::String(date::format(fake_date(prop, ctx.now, rng))),
+    };
+    match value {
+        Value::Number(n) if as_string(prop, config) => Value::String(n.to_string()),
+        value => value,
+    }
+}
+
+/// Without `@minItems`/`@maxItems` arrays hold one to five items; with
+/// `@emptyRate` that share of them comes out empty instead.
+fn fake_array<R: Rng + ?Sized>(
+    prop: &Prop,
+    config: &Config,
+    ctx: Context,
+    memo: &mut Memo,
+    rng: &mut R,
+) -> Value {
+    let Some(item) = prop.item() else {
+        return Value::Null;
+    };
+    if prop.empty_rate.is_some_and(|rate| rng.random_bool(rate)) {
+        return json!([]);
+    }
+    let min = prop.min_items.unwrap_or(1);
+    let max = prop.max_items.unwrap_or(min.max(5)).max(min);
+    let len = rng.random_range(min..=max);
+    Value::Array(
+        (0..len)
+            .map(|_| fake_value(&item, config, ctx, memo, rng))
+            .collect(),
+    )
+}
+
+/// Dates fall within the past year unless `@minDate`/`@maxDate` say
+/// otherwise; a single bound keeps the range a year wide when it can't
+/// reach today. Either way they stay within the years `date::format` writes.
+fn fake_date<R: Rng + ?Sized>(prop: &Prop, now: i64, rng: &mut R) -> i64 {
+    let (min, max) = match (prop.min_date, prop.max_date) {
+        (Some(min), Some(max)) => (min, max),
+        (Some(min), None) if min < now => (min, now),
+        (Some(min), None) => (min, min + date::YEAR),
+        (None, Some(max)) => (max.min(now) - date::YEAR, max),
+        (None, None) => (now - date::YEAR, now),
+    };
+    let (min, max) = (
+        min.clamp(date::EARLIEST, date::LATEST),
+        max.clamp(date::EARLIEST, date::LATEST),
+    );
+    if min >= max {
+        min
+    } else {
+        rng.random_range(min..=max)
+    }
+}
+
+/// Whether a numeric prop is emitted as a JSON string, either by its own
+/// `@asString` or, for `bigint`, by `--bigint-as-string`.
+pub fn as_string(prop: &Prop, config: &Config) -> bool {
+    match prop.ty {
+        TProp::Number => prop.as_string,
+        TProp::BigInt => prop.as_string || config.bigint_as_string,
+        _ => false,
+    }
+}
+
+/// Without bounds, bigints land above 2^53 so that clients parsing them as
+/// doubles lose precision the way they would against a real backend.
+fn fake_bigint<R: Rng + ?Sized>(prop: &Prop, rng: &mut R) -> i64 {
+    if prop.min.is_none() && prop.max.is_none() {
+        return rng.random_range((1 << 53) + 1..=i64::MAX);
+    }
+    fake_number(prop, rng)
+}
+
+fn fake_number<R: Rng + ?Sized>(prop: &Prop, rng: &mut R) -> i64 {
+    if prop.min.is_none() && prop.max.is_none() {
+        return faker::number::en::NumberWithFormat("###")
+            .fake_with_rng::<String, _>(rng)
+            .parse()
+            .unwrap();
+    }
+    let min = prop.min.map_or(0, |n| n.ceil() as i64);
+    let max = prop
+        .max
+        .map_or(min.saturating_add(999), |n| n.floor() as i64);
+    let (min, max) = (
+        min.clamp(date::EARLIEST, date::LATEST),
+        max.clamp(date::EARLIEST, date::LATEST),
+    );
+    if min >= max {
+        min
+    } else {
+        rng.random_range(min..=max)
+    }
+}
+
+/// A number with decimals, within `@min`/`@max` (by default 0 to 1000),
+/// rounded to the format's precision. Never NaN or infinite.
+fn fake_decimal<R: Rng + ?Sized>(prop: &Prop, format: &NumberFormat, rng: &mut R) -> Value {
+    let floor = match format {
+        NumberFormat::Currency(_) | NumberFormat::Rating => 0.0,
+        _ => f64::MIN,
+    };
+    let min = prop.min.unwrap_or(0.0).max(floor);
+    let max = prop.max.unwrap_or(match format {
+        NumberFormat::Rating => 5.0,
+        _ => min + 1000.0,
+    });
+    let value = match format {
+        NumberFormat::Rating => {
+            let (low, high) = ((min * 2.0).ceil() as i64, (max * 2.0).floor() as i64);
+            let halves = if low >= high {
+                low
+            } else {
+                rng.random_range(low..=high)
+            };
+            halves as f64 / 2.0
+        }
+        _ if min >= max => min,
+        _ => {
+            // Bounds far apart overflow `max - min`, which `random_range`
+            // can't sample; a weighted mean of the two never does.
+            let value = if (max - min).is_finite() {
+                rng.random_range(min..=max)
+            } else {
+                let t: f64 = rng.random();
+                min * (1.0 - t) + max * t
+            };
+            let scale = 10f64.powi(format.precision() as i32);
+            ((value * scale).round() / scale).clamp(min, max)
+        }
+    };
+    Number::from_f64(value).map_or(json!(0), Value::Number)
+}
+
+fn fake_string<R: Rng + ?Sized>(kind: Option<FakerKind>, locale: Locale, rng: &mut R) -> String {
+    match locale {
+        Locale::En => fake_string_in(kind, locales::EN, rng),
+        Locale::FrFr => fake_string_in(kind, locales::FR_FR, rng),
+        Locale::DeDe => fake_string_in(kind, locales::DE_DE, rng),
+        Locale::JaJp => fake_string_in(kind, locales::JA_JP, rng),
+        Locale::ZhCn => fake_string_in(kind, locales::ZH_CN, rng),
+        Locale::ZhTw => fake_string_in(kind, locales::ZH_TW, rng),
+        Locale::PtBr => fake_string_in(kind, locales::PT_BR, rng),
+        Locale::PtPt => fake_string_in(kind, locales::PT_PT, rng),
+        Locale::ArSa => fake_string_in(kind, locales::AR_SA, rng),
+    }
+}
+
+fn fake_string_in<L, R>(kind: Option<FakerKind>, l: L, rng: &mut R) -> String
+where
+    L: faker::impls::address::CityNameGenFn,
+    R: Rng + ?Sized,
+{
+    use faker::{address, company, internet, lorem, name, phone_number};
+    match kind {
+        Some(FakerKind::Email) => internet::raw::SafeEmail(l).fake_with_rng(rng),
+        Some(FakerKind::Name) => name::raw::Name(l).fake_with_rng(rng),
+        Some(FakerKind::FirstName) => name::raw::FirstName(l).fake_with_rng(rng),
+        Some(FakerKind::LastName) => name::raw::LastName(l).fake_with_rng(rng),
+        Some(FakerKind::Username) => internet::raw::Username(l).fake_with_rng(rng),
+        Some(FakerKind::Phone) => phone_number::raw::PhoneNumber(l).fake_with_rng(rng),
+        Some(FakerKind::City) => address::raw::CityName(l).fake_with_rng(rng),
+        Some(FakerKind::Country) => address::raw::CountryName(l).fake_with_rng(rng),
+        Some(FakerKind::Company) => company::raw::CompanyName(l).fake_with_rng(rng),
+        Some(FakerKind::Sentence) => lorem::raw::Sentence(l, 3..8).fake_with_rng(rng),
+        Some(FakerKind::ImageUrl) => unreachable!("image URLs are generated in fake_value"),
+        Some(FakerKind::Uuid) => {
+            let bits: u128 = rng.random();
+            // Version 4, RFC 4122 variant.
+            let bits = bits & !(0xf << 76) | (0x4 << 76);
+            let bits = bits & !(0x3 << 62) | (0x2 << 62);
+            let hex = format!("{bits:032x}");
+            format!(
+                "{}-{}-{}-{}-{}",
+                &hex[..8],
+                &hex[8..12],
+                &hex[12..16],
+                &hex[16..20],
+                &hex[20..]
+            )
+        }
+        Some(FakerKind::Url) => {
+            let host: String = lorem::raw::Word(l).fake_with_rng(rng);
+            let suffix: String = internet::raw::DomainSuffix(l).fake_with_rng(rng);
+            let page: String = lorem::raw::Word(l).fake_with_rng(rng);
+            format!("https://{host}.{suffix}/{page}")
+        }
+        Some(FakerKind::Ipv4) => internet::raw::IPv4(l).fake_with_rng(rng),
+        Some(FakerKind::Ipv6) => internet::raw::IPv6(l).fake_with_rng(rng),
+        None => lorem::raw::Word(l).fake_with_rng(rng),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{test as http, web, App};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{cli, overrides::OVERRIDE_HEADER, register_route};
+
+    fn prop(id: &str, ty: TProp) -> Prop {
+        Prop {
+            id: id.to_string(),
+            ty,
+            ..Prop::default()
+        }
+    }
+
+    /// Props declared out of alphabetical order, nested in objects and in
+    /// list items.
+    fn order(route: &str, list: bool) -> Entity {
+        let address = TProp::Object {
+            name: "Address".to_string(),
+            props: vec![prop("zip", TProp::String), prop("city", TProp::String)],
+        };
+        let line = TProp::Object {
+            name: "Line".to_string(),
+            props: vec![prop("sku", TProp::String), prop("qty", TProp::Number)],
+        };
+        Entity {
+            name: "Order".to_string(),
+            route: route.to_string(),
+            list,
+            props: vec![
+                prop("total", TProp::Number),
+                prop("address", address),
+                prop("lines", TProp::Array(Box::new(line))),
+                prop("id", TProp::Number),
+                prop("at", TProp::Date),
+            ],
+            ..Entity::default()
+        }
+    }
+
+    /// Every object's keys in the order they're written, depth first.
+    fn keys(value: &Value, out: &mut Vec<String>) {
+        match value {
+            Value::Object(fields) => {
+                for (key, value) in fields {
+                    out.push(key.clone());
+                    keys(value, out);
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|item| keys(item, out)),
+            _ => (),
+        }
+    }
+
+    #[actix_web::test]
+    async fn seeded_responses_are_byte_identical_in_declaration_order() {
+        let args = ["--seed", "3", "--now", "2024-06-01T00:00:00Z"];
+        let (_, config) = cli::parse_args(args.map(String::from)).unwrap();
+        let config = web::Data::new(config);
+        let (one, list) = (order("/order", false), order("/orders", true));
+        let app = App::new().app_data(config.clone());
+        let app = register_route(app, "/order", &[&one], &config);
+        let app = http::init_service(register_route(app, "/orders", &[&list], &config)).await;
+
+        let patch = json!({ "address": { "country": "NL", "zip": "1011" }, "note": "x" });
+        for uri in ["/order", "/orders?count=3"] {
+            let mut bodies = Vec::new();
+            for _ in 0..2 {
+                let req = http::TestRequest::get()
+                    .uri(uri)
+                    .insert_header((OVERRIDE_HEADER, patch.to_string()))
+                    .to_request();
+                bodies.push(http::call_and_read_body(&app, req).await);
+            }
+            assert_eq!(bodies[0], bodies[1], "{uri}");
+
+            let body: Value = serde_json::from_slice(&bodies[0]).unwrap();
+            let items = match body {
+                Value::Array(items) => items,
+                item => vec![item],
+            };
+            for item in items {
+                assert_eq!(item["address"]["zip"], "1011");
+                let mut found = Vec::new();
+                keys(&item, &mut found);
+                let lines = item["lines"].as_array().unwrap().len();
+                let mut expected = vec!["total", "address", "zip", "city", "country", "lines"];
+                expected.extend(["sku", "qty"].repeat(lines));
+                expected.extend(["id", "at", "note"]);
+                assert_eq!(found, expected, "{uri}");
+            }
+        }
+    }
+
+    #[test]
+    fn decimals_stay_within_bounds_too_far_apart_to_subtract() {
+        let format = NumberFormat::Float { precision: 2 };
+        for (min, max) in [(-1e308, 1e308), (f64::MIN, f64::MAX), (0.0, f64::MAX)] {
+            let prop = Prop {
+                min: Some(min),
+                max: Some(max),
+                ..prop("x", TProp::Number)
+            };
+            let mut rng = rng_for(Some(1), "/x");
+            for _ in 0..100 {
+                let value = fake_decimal(&prop, &format, &mut rng).as_f64().unwrap();
+                assert!(
+                    (min..=max).contains(&value),
+                    "{value} outside {min}..={max}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+
+use actix_web::{http::StatusCode, web, HttpResponse};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    assets,
+    cli::Config,
+    date,
+    recording::{RecordedRequest, Recorder},
+};
+
+/// Bytes of each body an export includes when `?max_body=` doesn't say.
+const DEFAULT_MAX_BODY: usize = 64 * 1024;
+
+/// `GET /__har`: the request history as a HAR 1.2 log, which browsers'
+/// dev tools and proxies can open. `?since=` keeps requests from that time
+/// on, as milliseconds since the epoch or an ISO-8601 date. Bodies longer
+/// than `?max_body=` bytes are cut there, noted in the entry's comment.
+pub async fn export(
+    recorder: web::Data<Recorder>,
+    config: web::Data<Config>,
+    query: web::Query<HashMap<String, String>>,
+) -> HttpResponse {
+    let since = match query.get("since") {
+        None => 0,
+        Some(since) => match since
+            .parse::<u128>()
+            .ok()
+            .or_else(|| date::parse(since).map(|secs| secs.max(0) as u128 * 1000))
+        {
+            Some(since) => since,
+            None => {
+                return HttpResponse::BadRequest().json(json!({
+                    "error": format!(
+                        "`since` expects milliseconds since the epoch or an ISO-8601 date, got `{since}`"
+                    ),
+                }))
+            }
+        },
+    };
+    let max_body = match query.get("max_body").map(|max| max.parse::<usize>()) {
+        None => DEFAULT_MAX_BODY,
+        Some(Ok(max)) => max,
+        Some(Err(_)) => {
+            return HttpResponse::BadRequest()
+                .json(json!({ "error": "`max_body` expects a number of bytes" }))
+        }
+    };
+
+    let origin = assets::origin(&config);
+    let entries: Vec<Value> = recorder
+        .snapshot()
+        .iter()
+        .filter(|request| request.timestamp >= since)
+        .map(|request| entry(request, &origin, max_body))
+        .collect();
+    HttpResponse::Ok()
+        .insert_header(("Content-Disposition", "attachment; filename=\"ssg.har\""))
+        .json(json!({
+            "log": {
+                "version": "1.2",
+                "creator": { "name": "ssg", "version": env!("CARGO_PKG_VERSION") },
+                "entries": entries,
+            }
+        }))
+}
+
+fn entry(request: &RecordedRequest, origin: &str, max_body: usize) -> Value {
+    let mut truncated = Vec::new();
+    let mut url = format!("{origin}{}", request.path);
+    if !request.query.is_empty() {
+        url = format!("{url}?{}", request.query);
+    }
+    let query_string: Vec<Value> = web::Query::<Vec<(String, String)>>::from_query(&request.query)
+        .map(|query| query.into_inner())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect();
+
+    let mut har_request = json!({
+        "method": request.method,
+        "url": url,
+        "httpVersion": "HTTP/1.1",
+        "cookies": [],
+        "headers": name_values(&request.headers),
+        "queryString": query_string,
+        "headersSize": -1,
+        "bodySize": -1,
+    });
+    if let Some(body) = &request.body {
+        let text = match body {
+            Value::String(text) => text.clone(),
+            body => body.to_string(),
+        };
+        har_request["bodySize"] = json!(text.len());
+        har_request["postData"] = json!({
+            "mimeType": header(&request.headers, "content-type"),
+            "text": cut(&text, max_body, "request", &mut truncated),
+        });
+    }
+
+    let response = &request.response;
+    let body = String::from_utf8_lossy(&response.body);
+    if response.body.len() < response.size {
+        truncated.push(format!(
+            "response body cut to {} of {} bytes by --max-body-bytes",
+            response.body.len(),
+            response.size
+        ));
+    }
+    let status = StatusCode::from_u16(request.status).ok();
+    let mut entry = json!({
+        "startedDateTime": started(request.timestamp),
+        "time": request.duration_ms,
+        "request": har_request,
+        "response": {
+            "status": request.status,
+            "statusText": status.and_then(|s| s.canonical_reason()).unwrap_or_default(),
+            "httpVersion": "HTTP/1.1",
+            "cookies": [],
+            "headers": name_values(&response.headers),
+            "content": {
+                "size": response.size,
+                "mimeType": header(&response.headers, "content-type"),
+                "text": cut(&body, max_body, "response", &mut truncated),
+            },
+            "redirectURL": header(&response.headers, "location"),
+            "headersSize": -1,
+            "bodySize": response.size,
+        },
+        "cache": {},
+        // Jitter and scenario delays come before the headers, bandwidth
+        // throttling while the body is sent.
+        "timings": {
+            "send": 0,
+            "wait": response.wait_ms,
+            "receive": (request.duration_ms - response.wait_ms).max(0.0),
+        },
+    });
+    if !truncated.is_empty() {
+        entry["comment"] = json!(truncated.join("; "));
+    }
+    entry
+}
+
+/// `text` up to `max` bytes, cut on a character boundary.
+fn cut(text: &str, max: usize, what: &str, truncated: &mut Vec<String>) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    truncated.push(format!("{what} body cut to {end} of {} bytes", text.len()));
+    text[..end].to_string()
+}
+
+fn name_values(headers: &Map<String, Value>) -> Vec<Value> {
+    headers
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "value": value }))
+        .collect()
+}
+
+fn header<'h>(headers: &'h Map<String, Value>, name: &str) -> &'h str {
+    headers
+        .get(name)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+}
+
+/// ISO-8601 with milliseconds, as HAR wants.
+fn started(timestamp: u128) -> String {
+    let seconds = date::format((timestamp / 1000) as i64);
+    format!("{}.{:03}Z", seconds.trim_end_matches('Z'), timestamp % 1000)
+}
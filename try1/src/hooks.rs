@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use actix_web::{http::StatusCode, web, HttpRequest};
+use serde_json::Value;
+
+use crate::Entity;
+
+/// What a request hook is told about the request it's shaping.
+pub struct RouteMatch {
+    /// The declared route, such as `/users/{id}`.
+    pub route: String,
+    pub method: String,
+    pub params: HashMap<String, String>,
+    pub query: HashMap<String, String>,
+}
+
+/// What happens after a hook ran.
+pub enum Hooked {
+    /// Carry on with the next hook, then send the value.
+    Continue,
+    /// Send the value now, with this status, skipping later hooks.
+    Respond(StatusCode),
+}
+
+pub type Hook = Box<dyn Fn(&RouteMatch, &mut Value) -> Hooked + Send + Sync>;
+
+/// The registered hooks, shared by every worker.
+pub struct Hooks(pub Vec<Hook>);
+
+pub type Transform = Box<dyn Fn(&Entity, &mut Value) + Send + Sync>;
+
+/// The registered response transforms, shared by every worker.
+pub struct Transforms(pub Vec<Transform>);
+
+impl Transforms {
+    /// Runs every transform on `data`, in the order they were registered.
+    pub fn apply(&self, entity: &Entity, data: &mut Value) {
+        for transform in &self.0 {
+            transform(entity, data);
+        }
+    }
+}
+
+/// Runs the transforms registered for the server on a value `entity`
+/// generated or stored, then reshapes it with the entity's `@transform`.
+pub fn transform(req: &HttpRequest, entity: &Entity, data: &mut Value) {
+    if let Some(transforms) = req.app_data::<web::Data<Transforms>>() {
+        transforms.apply(entity, data);
+    }
+    if let Some(view) = &entity.view {
+        view.apply(data);
+    }
+}
+
+/// Whether the server has request hooks for `req` to go through.
+pub fn registered(req: &HttpRequest) -> bool {
+    req.app_data::<web::Data<Hooks>>()
+        .is_some_and(|hooks| !hooks.0.is_empty())
+}
+
+/// Runs the hooks registered for the server on `data`, returning the status
+/// the first short-circuiting one asked for.
+pub fn run(req: &HttpRequest, route: &str, data: &mut Value) -> Option<StatusCode> {
+    let hooks = req.app_data::<web::Data<Hooks>>()?;
+    if hooks.0.is_empty() {
+        return None;
+    }
+    let matched = RouteMatch {
+        route: route.to_string(),
+        method: req.method().to_string(),
+        params: req
+            .match_info()
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+        query: web::Query::<HashMap<String, String>>::from_query(req.query_string())
+            .map(|q| q.into_inner())
+            .unwrap_or_default(),
+    };
+    hooks.0.iter().find_map(|hook| match hook(&matched, data) {
+        Hooked::Continue => None,
+        Hooked::Respond(status) => Some(status),
+    })
+}
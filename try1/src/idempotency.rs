@@ -0,0 +1,386 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::{self, BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{HeaderMap, HeaderName},
+        Method, StatusCode,
+    },
+    middleware::Next,
+    web::{self, Bytes},
+    Error, HttpMessage, HttpResponse,
+};
+use serde_json::json;
+
+use crate::{cli::Config, recording};
+
+const KEY_HEADER: &str = "Idempotency-Key";
+const REPLAY_HEADER: &str = "Idempotent-Replay";
+
+/// Responses to POSTs sent with an `Idempotency-Key`, kept for `--idempotency-ttl`
+/// and bounded to the `--idempotency-keys` most recently used. Keys are
+/// scoped to the path they were sent to, so two routes can reuse one.
+pub struct Idempotency {
+    capacity: usize,
+    ttl: Duration,
+    inner: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Bumped on every use, so the smallest `last_used` is the LRU entry.
+    clock: u64,
+    entries: HashMap<(String, String), Entry>,
+}
+
+struct Entry {
+    /// The request the key was first used with: method, path, query, body.
+    fingerprint: Vec<u8>,
+    /// `None` while that request is still being answered.
+    response: Option<Stored>,
+    stored: Instant,
+    last_used: u64,
+}
+
+struct Stored {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+enum Lookup {
+    /// First use: the key is held for this request until it's answered.
+    Reserved,
+    Replay(HttpResponse),
+    Mismatch,
+    /// A request with the key is still being answered.
+    InFlight,
+}
+
+impl Idempotency {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Idempotency {
+            capacity,
+            ttl,
+            inner: Mutex::new(State::default()),
+        }
+    }
+
+    /// Looks `key` up and, on a miss, reserves it in the same lock, so of
+    /// two concurrent requests with one key only the first is answered.
+    fn lookup(&self, key: &(String, String), fingerprint: &[u8]) -> Lookup {
+        let mut state = self.inner.lock().unwrap();
+        state.clock += 1;
+        let clock = state.clock;
+        let ttl = self.ttl;
+        state
+            .entries
+            .retain(|_, entry| entry.stored.elapsed() < ttl);
+        let Some(entry) = state.entries.get_mut(key) else {
+            self.insert(
+                &mut state,
+                key.clone(),
+                Entry {
+                    fingerprint: fingerprint.to_vec(),
+                    response: None,
+                    stored: Instant::now(),
+                    last_used: clock,
+                },
+            );
+            return Lookup::Reserved;
+        };
+        if entry.fingerprint != fingerprint {
+            return Lookup::Mismatch;
+        }
+        let Some(stored) = &entry.response else {
+            return Lookup::InFlight;
+        };
+        entry.last_used = clock;
+
+        let mut response = HttpResponse::build(stored.status);
+        for (name, value) in &stored.headers {
+            response.append_header((name.clone(), value.clone()));
+        }
+        response.insert_header((REPLAY_HEADER, "true"));
+        Lookup::Replay(response.body(stored.body.clone()))
+    }
+
+    /// Stores the response to the request that reserved `key`.
+    fn store(&self, key: (String, String), fingerprint: Vec<u8>, response: Stored) {
+        let mut state = self.inner.lock().unwrap();
+        state.clock += 1;
+        let entry = Entry {
+            fingerprint,
+            response: Some(response),
+            stored: Instant::now(),
+            last_used: state.clock,
+        };
+        self.insert(&mut state, key, entry);
+    }
+
+    /// Lets `key` go after its request failed, so it can be retried.
+    fn release(&self, key: &(String, String)) {
+        let mut state = self.inner.lock().unwrap();
+        if state
+            .entries
+            .get(key)
+            .is_some_and(|entry| entry.response.is_none())
+        {
+            state.entries.remove(key);
+        }
+    }
+
+    fn insert(&self, state: &mut State, key: (String, String), entry: Entry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if state.entries.len() >= self.capacity && !state.entries.contains_key(&key) {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                state.entries.remove(&oldest);
+            }
+        }
+        state.entries.insert(key, entry);
+    }
+}
+
+/// Holds a reserved key until its response is stored, and lets it go if
+/// the request fails or is dropped first.
+struct Reservation {
+    idempotency: web::Data<Idempotency>,
+    key: Option<(String, String)>,
+}
+
+impl Reservation {
+    fn store(mut self, fingerprint: Vec<u8>, response: Stored) {
+        if let Some(key) = self.key.take() {
+            self.idempotency.store(key, fingerprint, response);
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(key) = &self.key {
+            self.idempotency.release(key);
+        }
+    }
+}
+
+/// Replays the stored response for a repeated `Idempotency-Key` on an entity
+/// POST, with `Idempotent-Replay: true`. In stateful mode that's the item
+/// the first request created, without creating another. Reusing a key on
+/// the same path for a different request is a 409, and so is a retry sent
+/// while the first request is still being answered. Server errors aren't
+/// stored, so those can be retried.
+pub async fn replay(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let idempotency = req.app_data::<web::Data<Idempotency>>().cloned();
+    let config = req.app_data::<web::Data<Config>>().cloned();
+    let key = req
+        .headers()
+        .get(KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    let (Some(idempotency), Some(config), Some(key)) = (idempotency, config, key) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    if req.method() != Method::POST || config.is_internal(req.path()) {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let (body, payload) = recording::read_body(req.take_payload(), config.max_body_bytes).await;
+    req.set_payload(payload);
+    // Bodies over the limit are rejected by the handler; they never match.
+    let mut fingerprint =
+        format!("{} {}?{}\n", req.method(), req.path(), req.query_string()).into_bytes();
+    fingerprint.extend(body.unwrap_or_default());
+
+    let scoped = (req.path().to_string(), key);
+    let (path, key) = &scoped;
+    let conflict = match idempotency.lookup(&scoped, &fingerprint) {
+        Lookup::Reserved => None,
+        Lookup::Replay(response) => return Ok(req.into_response(response)),
+        Lookup::Mismatch => Some(format!(
+            "`{key}` was already used on {path} with a different request"
+        )),
+        Lookup::InFlight => Some(format!(
+            "`{key}` is still being answered on {path}; retry once it is"
+        )),
+    };
+    if let Some(error) = conflict {
+        let response = HttpResponse::Conflict().json(json!({ "error": error }));
+        return Ok(req.into_response(response));
+    }
+
+    let reservation = Reservation {
+        idempotency,
+        key: Some(scoped),
+    };
+    let res = next.call(req).await?;
+    let status = res.status();
+    if status.is_server_error() {
+        return Ok(res.map_into_boxed_body());
+    }
+    let (req, res) = res.into_parts();
+    let (head, body) = res.into_parts();
+    let body = body::to_bytes(body).await.map_err(Into::into)?;
+    let headers: HeaderMap = head
+        .headers()
+        .iter()
+        .filter(|(name, _)| !is_hop_by_hop(name))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect();
+    reservation.store(
+        fingerprint,
+        Stored {
+            status,
+            headers,
+            body: body.clone(),
+        },
+    );
+    Ok(ServiceResponse::new(
+        req,
+        head.set_body(body).map_into_boxed_body(),
+    ))
+}
+
+fn is_hop_by_hop(name: &HeaderName) -> bool {
+    matches!(
+        name.as_str(),
+        "connection" | "transfer-encoding" | "content-length"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use actix_web::{middleware, rt::time::sleep, test as http, App};
+    use futures_util::future::join;
+    use serde_json::Value;
+
+    use super::*;
+
+    /// A POST route behind `replay` that answers with how many requests it
+    /// has answered, taking `DELAY` over each.
+    struct Posts {
+        config: web::Data<Config>,
+        idempotency: web::Data<Idempotency>,
+        answered: web::Data<AtomicUsize>,
+    }
+
+    const DELAY: Duration = Duration::from_millis(50);
+
+    async fn answer(answered: web::Data<AtomicUsize>) -> HttpResponse {
+        sleep(DELAY).await;
+        let n = answered.fetch_add(1, Ordering::SeqCst) + 1;
+        HttpResponse::Created().json(json!({ "n": n }))
+    }
+
+    impl Posts {
+        fn new(capacity: usize, ttl: Duration) -> Self {
+            Posts {
+                config: web::Data::new(Config::default()),
+                idempotency: web::Data::new(Idempotency::new(capacity, ttl)),
+                answered: web::Data::new(AtomicUsize::new(0)),
+            }
+        }
+
+        /// POSTs `body` to `/posts` with `key`: the status, whether it was
+        /// a replay, and the body.
+        async fn post(&self, key: &str, body: Value) -> (StatusCode, bool, Value) {
+            let app = App::new()
+                .wrap(middleware::from_fn(replay))
+                .app_data(self.config.clone())
+                .app_data(self.idempotency.clone())
+                .app_data(self.answered.clone())
+                .route("/posts", web::post().to(answer));
+            let app = http::init_service(app).await;
+            let req = http::TestRequest::post()
+                .uri("/posts")
+                .insert_header((KEY_HEADER, key))
+                .set_json(body);
+            let res = http::call_service(&app, req.to_request()).await;
+            let status = res.status();
+            let replayed = res.headers().contains_key(REPLAY_HEADER);
+            let body = http::read_body(res).await;
+            (status, replayed, serde_json::from_slice(&body).unwrap())
+        }
+    }
+
+    #[actix_web::test]
+    async fn a_repeated_key_replays_the_first_response() {
+        let posts = Posts::new(10, Duration::from_secs(60));
+        let first = posts.post("a", json!({ "title": "hi" })).await;
+        assert_eq!(first, (StatusCode::CREATED, false, json!({ "n": 1 })));
+        let again = posts.post("a", json!({ "title": "hi" })).await;
+        assert_eq!(again, (StatusCode::CREATED, true, json!({ "n": 1 })));
+        let other = posts.post("b", json!({ "title": "hi" })).await;
+        assert_eq!(other, (StatusCode::CREATED, false, json!({ "n": 2 })));
+    }
+
+    #[actix_web::test]
+    async fn a_key_reused_with_another_body_is_a_conflict() {
+        let posts = Posts::new(10, Duration::from_secs(60));
+        posts.post("a", json!({ "title": "hi" })).await;
+        let (status, replayed, body) = posts.post("a", json!({ "title": "bye" })).await;
+        assert_eq!((status, replayed), (StatusCode::CONFLICT, false));
+        assert_eq!(
+            body["error"],
+            "`a` was already used on /posts with a different request"
+        );
+        assert_eq!(posts.answered.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_web::test]
+    async fn a_retry_while_the_first_is_answered_is_a_conflict() {
+        let posts = Posts::new(10, Duration::from_secs(60));
+        let body = json!({ "title": "hi" });
+        let (first, retry) =
+            join(posts.post("a", body.clone()), posts.post("a", body.clone())).await;
+        assert_eq!(first, (StatusCode::CREATED, false, json!({ "n": 1 })));
+        assert_eq!(retry.0, StatusCode::CONFLICT);
+        assert_eq!(
+            retry.2["error"],
+            "`a` is still being answered on /posts; retry once it is"
+        );
+        assert_eq!(posts.answered.load(Ordering::SeqCst), 1);
+        let (status, replayed, _) = posts.post("a", body).await;
+        assert_eq!((status, replayed), (StatusCode::CREATED, true));
+    }
+
+    #[actix_web::test]
+    async fn keys_expire_after_the_ttl() {
+        let posts = Posts::new(10, DELAY * 2);
+        posts.post("a", json!({})).await;
+        sleep(DELAY * 3).await;
+        let again = posts.post("a", json!({})).await;
+        assert_eq!(again, (StatusCode::CREATED, false, json!({ "n": 2 })));
+    }
+
+    #[actix_web::test]
+    async fn the_least_recently_used_key_is_evicted() {
+        let posts = Posts::new(2, Duration::from_secs(60));
+        posts.post("a", json!({})).await;
+        posts.post("b", json!({})).await;
+        // Using `a` again leaves `b` the least recently used.
+        assert!(posts.post("a", json!({})).await.1);
+        posts.post("c", json!({})).await;
+        assert!(posts.post("a", json!({})).await.1);
+        assert!(posts.post("c", json!({})).await.1);
+        let b = posts.post("b", json!({})).await;
+        assert_eq!(b, (StatusCode::CREATED, false, json!({ "n": 4 })));
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+
+use crate::{date, warning, Entity, Prop, TProp};
+
+/// The example responses under `--infer`, grouped by the route they serve:
+/// the path below `dir` up to the first `.` of the file name, so
+/// `users.json` and `users.2.json` are both examples of `/users`.
+pub fn examples(dir: &Path) -> io::Result<BTreeMap<String, Vec<PathBuf>>> {
+    let mut examples: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    let mut dirs_to_visit = vec![dir.to_path_buf()];
+    while let Some(current_dir) = dirs_to_visit.pop() {
+        for entry in fs::read_dir(&current_dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs_to_visit.push(path);
+                continue;
+            }
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if !name.ends_with(".json") || name.ends_with(".schema.json") {
+                continue;
+            }
+            let stem = name.split('.').next().unwrap_or_default();
+            let parent = path
+                .parent()
+                .and_then(|parent| parent.strip_prefix(dir).ok())
+                .unwrap_or(Path::new(""));
+            let mut route = String::new();
+            for component in parent.components() {
+                route.push('/');
+                route.push_str(&component.as_os_str().to_string_lossy());
+            }
+            if stem != "index" || route.is_empty() {
+                route.push('/');
+                route.push_str(stem);
+            }
+            examples.entry(route).or_default().push(path);
+        }
+    }
+    for paths in examples.values_mut() {
+        paths.sort();
+    }
+    Ok(examples)
+}
+
+/// An entity whose props fit every example of `route`. An array of objects
+/// makes a list entity from all their items; fields missing from some
+/// examples, or `null` in any, are optional.
+pub fn entity(route: String, paths: &[PathBuf], samples: &[Value]) -> Option<Entity> {
+    let path = paths.first()?;
+    let list = samples.iter().all(Value::is_array);
+    let objects: Vec<&Map<String, Value>> = if list {
+        samples
+            .iter()
+            .filter_map(Value::as_array)
+            .flatten()
+            .filter_map(Value::as_object)
+            .collect()
+    } else {
+        samples.iter().filter_map(Value::as_object).collect()
+    };
+    if objects.is_empty() || (!list && objects.len() < samples.len()) {
+        warning::warn(
+            path,
+            format!("examples of `{route}` are neither objects nor arrays of objects, skipping"),
+        );
+        return None;
+    }
+
+    let name = route
+        .rsplit('/')
+        .find(|segment| !segment.is_empty())
+        .map(pascal_case)
+        .unwrap_or_else(|| "Root".to_string());
+    Some(Entity {
+        route,
+        name,
+        source: path.clone(),
+        description: Some(format!(
+            "Inferred from {} example{}.",
+            samples.len(),
+            if samples.len() == 1 { "" } else { "s" }
+        )),
+        list,
+        props: props(&objects),
+        ..Default::default()
+    })
+}
+
+/// A prop for every key seen, in the order first seen.
+fn props(objects: &[&Map<String, Value>]) -> Vec<Prop> {
+    let mut keys: Vec<&String> = Vec::new();
+    for object in objects {
+        for key in object.keys() {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+    }
+    keys.into_iter()
+        .map(|key| {
+            let values: Vec<&Value> = objects.iter().filter_map(|o| o.get(key)).collect();
+            let optional = values.len() < objects.len() || values.iter().any(|v| v.is_null());
+            Prop {
+                id: key.clone(),
+                ty: infer_type(key, &values),
+                optional,
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+/// The type every non-null value fits; a string when they disagree or are
+/// all `null`.
+fn infer_type(key: &str, values: &[&Value]) -> TProp {
+    let values: Vec<&Value> = values.iter().copied().filter(|v| !v.is_null()).collect();
+    let Some(first) = values.first() else {
+        return TProp::String;
+    };
+    let same_kind = values
+        .iter()
+        .all(|v| std::mem::discriminant(*v) == std::mem::discriminant(*first));
+    if !same_kind {
+        return TProp::String;
+    }
+    match first {
+        Value::Bool(_) => TProp::Boolean,
+        Value::Number(_) => TProp::Number,
+        Value::String(_) if values.iter().all(|v| v.as_str().is_some_and(is_date)) => TProp::Date,
+        Value::Array(_) => {
+            let items: Vec<&Value> = values
+                .iter()
+                .filter_map(|v| v.as_array())
+                .flatten()
+                .collect();
+            TProp::Array(Box::new(infer_type(key, &items)))
+        }
+        Value::Object(_) => {
+            let objects: Vec<&Map<String, Value>> =
+                values.iter().filter_map(|v| v.as_object()).collect();
+            TProp::Object {
+                name: pascal_case(key),
+                props: props(&objects),
+            }
+        }
+        _ => TProp::String,
+    }
+}
+
+/// Dates are only inferred from full ISO-8601 strings, so that a code like
+/// `2024-01` stays a string.
+fn is_date(s: &str) -> bool {
+    s.len() >= 10 && s.as_bytes()[4] == b'-' && date::parse(s).is_some()
+}
+
+fn pascal_case(s: &str) -> String {
+    s.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+                .collect::<String>()
+        })
+        .collect()
+}
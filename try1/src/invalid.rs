@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpRequest};
+use fake::rand::{seq::IndexedRandom, Rng};
+use serde_json::{Number, Value};
+
+use crate::{generate::FakerKind, Entity, Prop, TProp};
+
+/// Ways a generated object can be made to break its own schema.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Violation {
+    WrongType,
+    Missing,
+    OutOfRange,
+    MalformedEmail,
+}
+
+impl Violation {
+    pub const ALL: [Violation; 4] = [
+        Violation::WrongType,
+        Violation::Missing,
+        Violation::OutOfRange,
+        Violation::MalformedEmail,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Violation::WrongType => "wrong-type",
+            Violation::Missing => "missing",
+            Violation::OutOfRange => "out-of-range",
+            Violation::MalformedEmail => "malformed-email",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "type" | "wrong-type" => Some(Violation::WrongType),
+            "missing" => Some(Violation::Missing),
+            "range" | "out-of-range" => Some(Violation::OutOfRange),
+            "email" | "malformed-email" => Some(Violation::MalformedEmail),
+            _ => None,
+        }
+    }
+
+    pub fn applies_to(self, prop: &Prop) -> bool {
+        match self {
+            Violation::WrongType => true,
+            Violation::Missing => !prop.optional,
+            Violation::OutOfRange => {
+                matches!(prop.ty, TProp::Number | TProp::BigInt)
+                    && (prop.min.is_some() || prop.max.is_some())
+            }
+            Violation::MalformedEmail => prop.faker == Some(FakerKind::Email),
+        }
+    }
+
+    /// The most specific violation for a prop, used when none is named.
+    fn default_for(prop: &Prop) -> Self {
+        [Violation::MalformedEmail, Violation::OutOfRange]
+            .into_iter()
+            .find(|v| v.applies_to(prop))
+            .unwrap_or(Violation::WrongType)
+    }
+}
+
+/// Works out which props to break for this request.
+///
+/// `X-Mock-Invalid: name,age:missing` names the props (and optionally the
+/// violation) explicitly; `?__invalid=true` targets every prop. With `fuzz`
+/// the targeted props and the violations are picked at random instead.
+pub fn requested<'a, R: Rng + ?Sized>(
+    req: &HttpRequest,
+    entity: &'a Entity,
+    fuzz: bool,
+    rng: &mut R,
+) -> Result<Vec<(&'a Prop, Violation)>, String> {
+    let mut targets: Vec<(&Prop, Option<Violation>)> = Vec::new();
+
+    if let Some(header) = req.headers().get("X-Mock-Invalid") {
+        let header = header
+            .to_str()
+            .map_err(|_| "X-Mock-Invalid is not valid UTF-8".to_string())?;
+        for item in header.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+            let (name, kind) = match item.split_once(':') {
+                Some((name, kind)) => (name.trim(), Some(kind.trim())),
+                None => (item, None),
+            };
+            let prop = entity
+                .props
+                .iter()
+                .find(|p| p.id == name)
+                .ok_or_else(|| format!("unknown prop `{name}` in X-Mock-Invalid"))?;
+            let violation = match kind {
+                Some(kind) => {
+                    let v = Violation::parse(kind)
+                        .ok_or_else(|| format!("unknown violation `{kind}` in X-Mock-Invalid"))?;
+                    if !v.applies_to(prop) {
+                        return Err(format!("`{}` cannot be applied to `{name}`", v.name()));
+                    }
+                    Some(v)
+                }
+                None => None,
+            };
+            targets.push((prop, violation));
+        }
+    } else {
+        let query = web::Query::<HashMap<String, String>>::from_query(req.query_string())
+            .map(|q| q.into_inner())
+            .unwrap_or_default();
+        if !matches!(
+            query.get("__invalid").map(String::as_str),
+            Some("true" | "1")
+        ) {
+            return Ok(Vec::new());
+        }
+        targets = entity.props.iter().map(|p| (p, None)).collect();
+        if fuzz && !targets.is_empty() {
+            targets.retain(|_| rng.random_bool(0.5));
+            if targets.is_empty() {
+                let prop = entity.props.choose(rng).unwrap();
+                targets.push((prop, None));
+            }
+        }
+    }
+
+    Ok(targets
+        .into_iter()
+        .map(|(prop, violation)| {
+            let violation = violation.unwrap_or_else(|| {
+                if fuzz {
+                    let applicable: Vec<_> = Violation::ALL
+                        .into_iter()
+                        .filter(|v| v.applies_to(prop))
+                        .collect();
+                    *applicable.choose(rng).unwrap()
+                } else {
+                    Violation::default_for(prop)
+                }
+            });
+            (prop, violation)
+        })
+        .collect())
+}
+
+pub fn apply(data: &mut Value, prop: &Prop, violation: Violation) {
+    let Some(data) = data.as_object_mut() else {
+        return;
+    };
+    match violation {
+        Violation::Missing => {
+            data.shift_remove(&prop.id);
+        }
+        Violation::WrongType => {
+            // Keyed off the emitted value so `@asString` numbers become numbers.
+            let wrong = match (data.get(&prop.id), &prop.ty) {
+                (Some(Value::String(s)), TProp::Number | TProp::BigInt) => s
+                    .parse::<Number>()
+                    .map_or(Value::Number(Number::from(0)), Value::Number),
+                (_, TProp::Boolean) => Value::String("true".into()),
+                (current, TProp::Number | TProp::BigInt) => {
+                    Value::String(current.map_or("0".into(), |v| v.to_string()))
+                }
+                (_, TProp::String | TProp::Date) => Value::Number(Number::from(42)),
+                (_, TProp::Enum(values)) if values.iter().all(Value::is_string) => {
+                    Value::Number(Number::from(42))
+                }
+                (_, TProp::Enum(_) | TProp::Object { .. } | TProp::Array(_)) => {
+                    Value::String("invalid".into())
+                }
+            };
+            data.insert(prop.id.clone(), wrong);
+        }
+        Violation::OutOfRange => {
+            let outside = match (prop.min, prop.max) {
+                (_, Some(max)) => max.floor() as i64 + 1,
+                (Some(min), None) => min.ceil() as i64 - 1,
+                (None, None) => return,
+            };
+            let outside = match data.get(&prop.id) {
+                Some(Value::String(_)) => Value::String(outside.to_string()),
+                _ => Value::Number(Number::from(outside)),
+            };
+            data.insert(prop.id.clone(), outside);
+        }
+        Violation::MalformedEmail => {
+            if let Some(Value::String(email)) = data.get_mut(&prop.id) {
+                *email = email.replace('@', "");
+            }
+        }
+    }
+}
+
+/// Renders violations for the `X-Mock-Violations` header.
+pub fn describe(violations: &[(&Prop, Violation)]) -> String {
+    violations
+        .iter()
+        .map(|(prop, v)| format!("{}:{}", prop.id, v.name()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
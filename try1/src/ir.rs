@@ -0,0 +1,622 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use actix_web::http::StatusCode;
+use serde_json::{json, Map, Value};
+
+use crate::{
+    auth::Auth,
+    concurrency::Concurrency,
+    defaults::{Origin, Setting},
+    generate::{FakerKind, NumberFormat},
+    scenario::Scenario,
+    view::View,
+    Entity, Prop, TProp,
+};
+
+/// Version of the IR format. Bumped whenever a file written by one ssg
+/// would be misread by another, which then refuses it.
+pub const IR_VERSION: u64 = 1;
+
+/// The scanned entities as the JSON `ssg scan --emit-ir` writes: all that
+/// serving them needs, so `--ir` can start without reading TypeScript.
+/// Source paths are written relative to `root`.
+pub fn emit(entities: &[Entity], root: &Path) -> Value {
+    let relative = |path: &Path| {
+        path.strip_prefix(root)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    };
+    let entities: Vec<Value> = entities
+        .iter()
+        .map(|entity| {
+            json!({
+                "name": entity.name,
+                "route": entity.route,
+                "route_prefix": entity.route_prefix,
+                "version": entity.version,
+                "source": relative(&entity.source),
+                "description": entity.description,
+                "summary": entity.summary,
+                "tags": entity.tags,
+                "operation_id": entity.operation_id,
+                "bandwidth": entity.bandwidth,
+                "settings": entity
+                    .settings
+                    .iter()
+                    .map(|setting| {
+                        let origin = match &setting.origin {
+                            Origin::Global => json!("global"),
+                            Origin::Directory(path) => json!({ "directory": relative(path) }),
+                            Origin::File => json!("file"),
+                            Origin::Entity => json!("entity"),
+                        };
+                        json!({ "tag": setting.tag, "value": setting.value, "origin": origin })
+                    })
+                    .collect::<Vec<_>>(),
+                "content_type": entity.content_type,
+                "format": entity.format,
+                "body_from": entity.body_from,
+                "scenarios": entity
+                    .scenarios
+                    .iter()
+                    .map(|scenario| {
+                        json!({
+                            "name": scenario.name,
+                            "status": scenario.status.map(|status| status.as_u16()),
+                            "body": scenario.body,
+                            "delay_ms": scenario.delay.map(millis),
+                        })
+                    })
+                    .collect::<Vec<_>>(),
+                "concurrency": match entity.concurrency {
+                    Concurrency::Off => "off",
+                    Concurrency::Etag => "etag",
+                    Concurrency::Version => "version",
+                },
+                "auth": entity.auth.as_ref().map(|auth| match auth {
+                    Auth::Bearer => "bearer".to_string(),
+                    Auth::ApiKey(header) => format!("apiKey: {header}"),
+                }),
+                "roles": entity.roles,
+                "soft_delete": entity.soft_delete,
+                "consistency_lag_ms": entity.consistency_lag.map(millis),
+                "transform": entity.view.as_ref().map(View::source),
+                "data": entity.data,
+                "list": entity.list,
+                "props": props_json(&entity.props),
+            })
+        })
+        .collect();
+    json!({
+        "ir_version": IR_VERSION,
+        "generator": format!("ssg {}", env!("CARGO_PKG_VERSION")),
+        "entities": entities,
+    })
+}
+
+/// Writes the IR to `out`, or to stdout without one.
+pub fn write(entities: &[Entity], root: &Path, out: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let text = serde_json::to_string_pretty(&emit(entities, root))? + "\n";
+    match out {
+        Some(out) => {
+            fs::write(out, text)?;
+            println!("wrote {} entities to {}", entities.len(), out.display());
+        }
+        None => print!("{text}"),
+    }
+    Ok(())
+}
+
+/// The entities an `ssg scan --emit-ir` file holds. A file of another IR
+/// version is refused rather than half understood.
+pub fn load(path: &Path) -> Result<Vec<Entity>, String> {
+    let at = |error: String| format!("{}: {error}", path.display());
+    let text = fs::read_to_string(path).map_err(|err| at(err.to_string()))?;
+    let ir: Value =
+        serde_json::from_str(&text).map_err(|err| at(format!("invalid JSON: {err}")))?;
+    match ir.get("ir_version").and_then(Value::as_u64) {
+        Some(IR_VERSION) => (),
+        Some(version) => {
+            return Err(at(format!(
+                "written as IR version {version} by {}, but this ssg reads version {IR_VERSION}; \
+                 scan again with `ssg scan --emit-ir`",
+                ir.get("generator")
+                    .and_then(Value::as_str)
+                    .unwrap_or("another ssg")
+            )))
+        }
+        None => {
+            return Err(at(
+                "no `ir_version`, so not a file `ssg scan --emit-ir` wrote".to_string(),
+            ))
+        }
+    }
+    let Some(entities) = ir.get("entities").and_then(Value::as_array) else {
+        return Err(at("`entities` must be a list".to_string()));
+    };
+    entities
+        .iter()
+        .enumerate()
+        .map(|(index, entity)| {
+            read_entity(entity).map_err(|e| at(format!("entities[{index}]: {e}")))
+        })
+        .collect()
+}
+
+fn read_entity(value: &Value) -> Result<Entity, String> {
+    let fields = Fields::of(value)?;
+    let props = fields
+        .list("props")?
+        .iter()
+        .enumerate()
+        .map(|(index, prop)| read_prop(prop).map_err(|e| format!("props[{index}]: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    let settings = fields
+        .list("settings")?
+        .iter()
+        .map(|setting| {
+            let setting = Fields::of(setting)?;
+            let origin = match setting.get("origin") {
+                Some(Value::String(origin)) if origin == "global" => Origin::Global,
+                Some(Value::String(origin)) if origin == "file" => Origin::File,
+                Some(Value::String(origin)) if origin == "entity" => Origin::Entity,
+                Some(origin) => Origin::Directory(
+                    Fields::of(origin)
+                        .and_then(|origin| origin.string("directory"))
+                        .map_err(|_| "`origin` must be global, file, entity or {\"directory\"}")?
+                        .into(),
+                ),
+                None => return Err("`origin` is missing".to_string()),
+            };
+            Ok(Setting {
+                tag: setting.string("tag")?,
+                value: setting.string("value")?,
+                origin,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(|e| format!("settings: {e}"))?;
+    let scenarios = fields
+        .list("scenarios")?
+        .iter()
+        .map(|scenario| {
+            let scenario = Fields::of(scenario)?;
+            Ok(Scenario {
+                name: scenario.string("name")?,
+                status: scenario
+                    .integer("status")?
+                    .map(|status: u16| {
+                        StatusCode::from_u16(status)
+                            .map_err(|_| format!("`{status}` isn't an HTTP status"))
+                    })
+                    .transpose()?,
+                body: scenario.get("body").cloned(),
+                delay: scenario.duration("delay_ms")?,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(|e| format!("scenarios: {e}"))?;
+    let concurrency = match fields.opt_string("concurrency")? {
+        Some(name) => Concurrency::parse(&name)
+            .ok_or_else(|| format!("`concurrency` expects off, etag or version, got `{name}`"))?,
+        None => Concurrency::Off,
+    };
+    let auth = fields
+        .opt_string("auth")?
+        .map(|auth| Auth::parse(&auth).map_err(|e| format!("auth: {e}")))
+        .transpose()?;
+    let view = fields
+        .opt_string("transform")?
+        .map(|source| View::new(&source, &props).map_err(|e| format!("transform: {e}")))
+        .transpose()?;
+    Ok(Entity {
+        route: fields.string("route")?,
+        route_prefix: fields.opt_string("route_prefix")?,
+        name: fields.string("name")?,
+        source: PathBuf::from(fields.opt_string("source")?.unwrap_or_default()),
+        description: fields.opt_string("description")?,
+        summary: fields.opt_string("summary")?,
+        tags: fields.strings("tags")?,
+        operation_id: fields.opt_string("operation_id")?,
+        version: fields.integer("version")?,
+        bandwidth: fields.integer("bandwidth")?,
+        settings,
+        content_type: fields.opt_string("content_type")?,
+        format: fields.opt_string("format")?,
+        body_from: fields.opt_string("body_from")?,
+        scenarios,
+        concurrency,
+        auth,
+        roles: fields.strings("roles")?,
+        soft_delete: fields.flag("soft_delete")?,
+        consistency_lag: fields.duration("consistency_lag_ms")?,
+        view,
+        data: fields.get("data").cloned(),
+        list: fields.flag("list")?,
+        props,
+    })
+}
+
+fn props_json(props: &[Prop]) -> Vec<Value> {
+    props
+        .iter()
+        .map(|prop| {
+            json!({
+                "name": prop.id,
+                "type": type_json(&prop.ty),
+                "optional": prop.optional,
+                "description": prop.description,
+                "faker": prop.faker.map(FakerKind::name),
+                "min": prop.min,
+                "max": prop.max,
+                "present_rate": prop.present_rate,
+                "number_format": prop.number_format.as_ref().map(|format| match format {
+                    NumberFormat::Int => json!("int"),
+                    NumberFormat::Float { precision } => json!({ "float": precision }),
+                    NumberFormat::Currency(code) => json!({ "currency": code }),
+                    NumberFormat::Rating => json!("rating"),
+                }),
+                "as_string": prop.as_string,
+                "min_items": prop.min_items,
+                "max_items": prop.max_items,
+                "empty_rate": prop.empty_rate,
+                "min_date": prop.min_date,
+                "max_date": prop.max_date,
+                "key": prop.key,
+            })
+        })
+        .collect()
+}
+
+fn type_json(ty: &TProp) -> Value {
+    match ty {
+        TProp::Boolean => json!("boolean"),
+        TProp::Number => json!("number"),
+        TProp::BigInt => json!("bigint"),
+        TProp::String => json!("string"),
+        TProp::Date => json!("date"),
+        TProp::Enum(values) => json!({ "enum": values }),
+        TProp::Object { name, props } => json!({ "object": name, "props": props_json(props) }),
+        TProp::Array(item) => json!({ "array": type_json(item) }),
+    }
+}
+
+fn read_prop(value: &Value) -> Result<Prop, String> {
+    let fields = Fields::of(value)?;
+    let id = fields.string("name")?;
+    let at = |e: String| format!("`{id}`: {e}");
+    let ty = read_type(fields.get("type").ok_or("`type` is missing")?).map_err(at)?;
+    let faker = fields
+        .opt_string("faker")
+        .map_err(at)?
+        .map(|name| FakerKind::parse(&name).ok_or_else(|| at(format!("unknown faker `{name}`"))))
+        .transpose()?;
+    let number_format = match fields.get("number_format") {
+        None => None,
+        Some(Value::String(format)) if format == "int" => Some(NumberFormat::Int),
+        Some(Value::String(format)) if format == "rating" => Some(NumberFormat::Rating),
+        Some(format) => {
+            let format = Fields::of(format).map_err(at)?;
+            match (format.integer("float"), format.opt_string("currency")) {
+                (Ok(Some(precision)), _) => Some(NumberFormat::Float { precision }),
+                (_, Ok(Some(code))) => Some(NumberFormat::Currency(code)),
+                _ => {
+                    return Err(at(
+                        "`number_format` must be int, rating, {\"float\"} or {\"currency\"}"
+                            .to_string(),
+                    ))
+                }
+            }
+        }
+    };
+    let read = || -> Result<Prop, String> {
+        Ok(Prop {
+            id: id.clone(),
+            ty,
+            optional: fields.flag("optional")?,
+            description: fields.opt_string("description")?,
+            faker,
+            min: fields.number("min")?,
+            max: fields.number("max")?,
+            present_rate: fields.rate("present_rate")?,
+            number_format,
+            as_string: fields.flag("as_string")?,
+            min_items: fields.integer("min_items")?,
+            max_items: fields.integer("max_items")?,
+            empty_rate: fields.rate("empty_rate")?,
+            min_date: fields.integer("min_date")?,
+            max_date: fields.integer("max_date")?,
+            key: fields.flag("key")?,
+        })
+    };
+    read().map_err(at)
+}
+
+fn read_type(value: &Value) -> Result<TProp, String> {
+    if let Some(name) = value.as_str() {
+        return match name {
+            "boolean" => Ok(TProp::Boolean),
+            "number" => Ok(TProp::Number),
+            "bigint" => Ok(TProp::BigInt),
+            "string" => Ok(TProp::String),
+            "date" => Ok(TProp::Date),
+            _ => Err(format!("unknown type `{name}`")),
+        };
+    }
+    let fields = Fields::of(value)?;
+    if let Some(values) = fields.get("enum") {
+        let values = values.as_array().ok_or("`enum` must be a list")?;
+        return Ok(TProp::Enum(values.clone()));
+    }
+    if let Some(item) = fields.get("array") {
+        return Ok(TProp::Array(Box::new(read_type(item)?)));
+    }
+    if let Some(name) = fields.opt_string("object")? {
+        let props = fields
+            .list("props")?
+            .iter()
+            .map(read_prop)
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(TProp::Object { name, props });
+    }
+    Err("a type must be a name, {\"enum\"}, {\"array\"} or {\"object\"}".to_string())
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// An IR object's fields, read with errors naming the field. `null` and a
+/// missing field are the same.
+struct Fields<'v>(&'v Map<String, Value>);
+
+impl<'v> Fields<'v> {
+    fn of(value: &'v Value) -> Result<Self, String> {
+        value
+            .as_object()
+            .map(Fields)
+            .ok_or_else(|| "expected an object".to_string())
+    }
+
+    fn get(&self, name: &str) -> Option<&'v Value> {
+        self.0.get(name).filter(|value| !value.is_null())
+    }
+
+    fn read<T>(
+        &self,
+        name: &str,
+        expected: &str,
+        read: impl Fn(&'v Value) -> Option<T>,
+    ) -> Result<Option<T>, String> {
+        self.get(name)
+            .map(|value| read(value).ok_or_else(|| format!("`{name}` must be {expected}")))
+            .transpose()
+    }
+
+    fn string(&self, name: &str) -> Result<String, String> {
+        self.opt_string(name)?
+            .ok_or_else(|| format!("`{name}` is missing"))
+    }
+
+    fn opt_string(&self, name: &str) -> Result<Option<String>, String> {
+        self.read(name, "a string", |value| value.as_str().map(String::from))
+    }
+
+    fn flag(&self, name: &str) -> Result<bool, String> {
+        Ok(self
+            .read(name, "true or false", Value::as_bool)?
+            .unwrap_or(false))
+    }
+
+    fn number(&self, name: &str) -> Result<Option<f64>, String> {
+        self.read(name, "a number", Value::as_f64)
+    }
+
+    /// A probability, from 0 to 1.
+    fn rate(&self, name: &str) -> Result<Option<f64>, String> {
+        self.read(name, "a number from 0 to 1", |value| {
+            value.as_f64().filter(|rate| (0.0..=1.0).contains(rate))
+        })
+    }
+
+    /// A duration written in milliseconds, as `millis` writes it.
+    fn duration(&self, name: &str) -> Result<Option<Duration>, String> {
+        self.read(name, "milliseconds, from 0 to a sane length", |value| {
+            Duration::try_from_secs_f64(value.as_f64()? / 1000.0).ok()
+        })
+    }
+
+    fn integer<T: TryFrom<i64>>(&self, name: &str) -> Result<Option<T>, String> {
+        self.read(name, "a whole number in range", |value| {
+            value.as_i64().and_then(|n| T::try_from(n).ok())
+        })
+    }
+
+    fn strings(&self, name: &str) -> Result<Vec<String>, String> {
+        let strings = self.read(name, "a list of strings", |value| {
+            value
+                .as_array()?
+                .iter()
+                .map(|s| s.as_str().map(String::from))
+                .collect()
+        })?;
+        Ok(strings.unwrap_or_default())
+    }
+
+    fn list(&self, name: &str) -> Result<&'v [Value], String> {
+        let list = self.read(name, "a list", |value| value.as_array().map(Vec::as_slice))?;
+        Ok(list.unwrap_or(&[]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::header, test as http, web, App};
+    use oxc::allocator::Allocator;
+
+    use super::*;
+    use crate::{cli, parser, register_route, route_groups, store::Store};
+
+    /// A project using every tag an entity or prop can carry, directory
+    /// and file defaults, a list, a data-backed route and a transform.
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/ir")
+    }
+
+    fn config(extra: &[&str]) -> cli::Config {
+        let args = [
+            "--seed",
+            "7",
+            "--now",
+            "2024-06-01T00:00:00Z",
+            "--version-prefix",
+            "--auth-token",
+            "t:editor",
+        ];
+        let args = args.iter().chain(extra).map(|arg| arg.to_string());
+        cli::parse_args(args).unwrap().1
+    }
+
+    fn scanned() -> Vec<Entity> {
+        let allocator = Allocator::default();
+        parser::scan_dir(&fixture(), &allocator, &config(&[]))
+            .unwrap()
+            .0
+    }
+
+    /// `ir` read back as `--ir` reads it, from a file.
+    fn reload(ir: &Value, name: &str) -> Result<Vec<Entity>, String> {
+        let path = std::env::temp_dir().join(format!("ssg-ir-{}-{name}.json", std::process::id()));
+        fs::write(&path, ir.to_string()).unwrap();
+        let loaded = load(&path);
+        fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    /// Status, content type and body of a few GETs to every route.
+    async fn responses(entities: &[Entity], config: cli::Config) -> Vec<(String, String)> {
+        let config = web::Data::new(config);
+        let store = web::Data::new(Store::default());
+        if config.stateful {
+            store.reset(entities, &config);
+        }
+        let mut app = App::new().app_data(config.clone()).app_data(store);
+        for (route, group) in route_groups(entities) {
+            app = register_route(app, route, &group, &config);
+        }
+        let app = http::init_service(app).await;
+
+        let mut responses = Vec::new();
+        for entity in entities {
+            let route = entity.served_route(config.version_prefix);
+            for uri in [route.clone(), format!("{route}?count=3&sort=-id")] {
+                let req = http::TestRequest::get()
+                    .uri(&uri)
+                    .insert_header(("X-Key", "t"))
+                    .insert_header((header::AUTHORIZATION, "Bearer t"))
+                    .to_request();
+                let res = http::call_service(&app, req).await;
+                let described = format!(
+                    "{uri} {} {:?}",
+                    res.status(),
+                    res.headers().get(header::CONTENT_TYPE)
+                );
+                let body = http::read_body(res).await;
+                responses.push((described, String::from_utf8_lossy(&body).into_owned()));
+            }
+        }
+        responses
+    }
+
+    #[test]
+    fn emitting_loaded_ir_gives_the_same_ir() {
+        let ir = emit(&scanned(), &fixture());
+        let loaded = reload(&ir, "round-trip").unwrap();
+        assert_eq!(emit(&loaded, &fixture()), ir);
+    }
+
+    #[test]
+    fn the_fixture_uses_every_kind_of_setting() {
+        let ir = emit(&scanned(), &fixture());
+        let text = ir.to_string();
+        for field in [
+            "\"directory\":\"ssg.defaults.toml\"",
+            "\"origin\":\"file\"",
+            "\"transform\":\"",
+            "\"list\":true",
+            "\"data\":{",
+            "\"scenarios\":[{",
+            "\"key\":true",
+            "\"consistency_lag_ms\":250.0",
+            "\"version\":2",
+        ] {
+            assert!(text.contains(field), "no {field} in {text}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn loaded_ir_serves_what_the_scan_does() {
+        let entities = scanned();
+        let loaded = reload(&emit(&entities, &fixture()), "serve").unwrap();
+        for extra in [&[][..], &["--stateful"]] {
+            let scanned = responses(&entities, config(extra)).await;
+            assert!(scanned
+                .iter()
+                .any(|(described, _)| described.contains(" 200 OK ")));
+            assert_eq!(responses(&loaded, config(extra)).await, scanned);
+        }
+    }
+
+    #[test]
+    fn another_ir_version_is_refused() {
+        let mut ir = emit(&scanned(), &fixture());
+        ir["ir_version"] = json!(IR_VERSION + 1);
+        let error = reload(&ir, "version").unwrap_err();
+        assert!(
+            error.contains(&format!("written as IR version {}", IR_VERSION + 1)),
+            "{error}"
+        );
+        assert!(error.contains("ssg scan --emit-ir"), "{error}");
+    }
+
+    #[test]
+    fn ir_without_a_version_is_refused() {
+        let mut ir = emit(&scanned(), &fixture());
+        ir.as_object_mut().unwrap().remove("ir_version");
+        let error = reload(&ir, "unversioned").unwrap_err();
+        assert!(error.contains("no `ir_version`"), "{error}");
+    }
+
+    #[test]
+    fn durations_out_of_range_are_field_errors() {
+        let mut ir = emit(&scanned(), &fixture());
+        let posts = ir["entities"]
+            .as_array_mut()
+            .unwrap()
+            .iter_mut()
+            .find(|entity| entity["route"] == "/posts")
+            .unwrap();
+        posts["consistency_lag_ms"] = json!(1e300);
+        let error = reload(&ir, "lag").unwrap_err();
+        assert!(error.contains("`consistency_lag_ms` must be"), "{error}");
+    }
+
+    #[test]
+    fn rates_out_of_range_are_field_errors() {
+        for (field, rate) in [("present_rate", json!(1.5)), ("empty_rate", json!(-0.1))] {
+            let mut ir = emit(&scanned(), &fixture());
+            ir["entities"][0]["props"][0][field] = rate.clone();
+            let error = reload(&ir, field).unwrap_err();
+            assert!(
+                error.ends_with(&format!("`{field}` must be a number from 0 to 1")),
+                "{error}"
+            );
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::path::Path;
+
+use serde_json::{Map, Value};
+
+use crate::{generate::FakerKind, warning, Entity, Prop, TProp};
+
+/// Maps a `*.schema.json` document onto entities. The root schema becomes an
+/// entity routed by its `x-route` (or the file name), and so does every
+/// `$defs`/`definitions` entry that declares an `x-route`.
+pub fn parse_json_schema(path: &Path, source_text: &str) -> Vec<Entity> {
+    let root: Value = match serde_json::from_str(source_text) {
+        Ok(root) => root,
+        Err(err) => {
+            warning::warn(path, format!("invalid JSON: {err}"));
+            return Vec::new();
+        }
+    };
+
+    let file_stem = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_suffix(".schema.json"))
+        .unwrap_or("schema");
+
+    let mut entities = Vec::new();
+    if let Some(schema) = root.as_object() {
+        let route = schema
+            .get("x-route")
+            .and_then(Value::as_str)
+            .map(String::from)
+            .unwrap_or_else(|| format!("/{file_stem}"));
+        let name = schema
+            .get("title")
+            .and_then(Value::as_str)
+            .unwrap_or(file_stem);
+        entities.extend(entity_from_schema(path, name, route, schema));
+
+        let definitions = ["$defs", "definitions"]
+            .iter()
+            .filter_map(|key| schema.get(*key).and_then(Value::as_object))
+            .flatten();
+        for (name, definition) in definitions {
+            let Some(definition) = definition.as_object() else {
+                continue;
+            };
+            if let Some(route) = definition.get("x-route").and_then(Value::as_str) {
+                entities.extend(entity_from_schema(
+                    path,
+                    name,
+                    route.to_string(),
+                    definition,
+                ));
+            }
+        }
+    }
+    entities
+}
+
+fn entity_from_schema(
+    path: &Path,
+    name: &str,
+    route: String,
+    schema: &Map<String, Value>,
+) -> Option<Entity> {
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        warning::warn(
+            path,
+            format!("schema `{name}` has no `properties`, skipping"),
+        );
+        return None;
+    };
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|r| r.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+
+    let mut entity = Entity {
+        route,
+        name: name.to_string(),
+        source: path.to_path_buf(),
+        description: description(schema),
+        ..Default::default()
+    };
+
+    for (id, prop_schema) in properties {
+        let Some(prop_schema) = prop_schema.as_object() else {
+            continue;
+        };
+        let Some(ty) = prop_type(prop_schema) else {
+            warning::warn(
+                path,
+                format!("unsupported type for `{name}.{id}`, skipping"),
+            );
+            continue;
+        };
+        let faker = match ty {
+            TProp::String => faker(path, name, id, prop_schema),
+            _ => None,
+        };
+        entity.props.push(Prop {
+            id: id.clone(),
+            ty,
+            optional: !required.contains(&id.as_str()),
+            description: description(prop_schema),
+            faker,
+            min: prop_schema.get("minimum").and_then(Value::as_f64),
+            max: prop_schema.get("maximum").and_then(Value::as_f64),
+            min_items: count(prop_schema, "minItems"),
+            max_items: count(prop_schema, "maxItems"),
+            ..Default::default()
+        });
+    }
+    Some(entity)
+}
+
+fn prop_type(schema: &Map<String, Value>) -> Option<TProp> {
+    if let Some(values) = schema.get("enum").and_then(Value::as_array) {
+        return Some(TProp::Enum(values.clone()));
+    }
+    // `["string", "null"]` is treated as its non-null member.
+    let ty = match schema.get("type")? {
+        Value::String(ty) => ty.as_str(),
+        Value::Array(types) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|ty| *ty != "null")?,
+        _ => return None,
+    };
+    match ty {
+        "boolean" => Some(TProp::Boolean),
+        "integer" | "number" => Some(TProp::Number),
+        "string" if schema.get("format").and_then(Value::as_str) == Some("date-time") => {
+            Some(TProp::Date)
+        }
+        "string" => Some(TProp::String),
+        "array" => schema
+            .get("items")
+            .and_then(Value::as_object)
+            .and_then(prop_type)
+            .map(|item| TProp::Array(Box::new(item))),
+        _ => None,
+    }
+}
+
+/// The faker a string prop's `format` picks, as `@faker` would. Formats
+/// we can't fake warn and leave a plain string.
+fn faker(path: &Path, name: &str, id: &str, schema: &Map<String, Value>) -> Option<FakerKind> {
+    let format = schema.get("format").and_then(Value::as_str)?;
+    let kind = FakerKind::from_format(format);
+    if kind.is_none() {
+        warning::warn(
+            path,
+            format!("unsupported format `{format}` on `{name}.{id}`, generating plain strings"),
+        );
+    }
+    kind
+}
+
+fn count(schema: &Map<String, Value>, key: &str) -> Option<usize> {
+    schema.get(key).and_then(Value::as_u64).map(|n| n as usize)
+}
+
+fn description(schema: &Map<String, Value>) -> Option<String> {
+    schema
+        .get("description")
+        .and_then(Value::as_str)
+        .map(String::from)
+}
@@ -0,0 +1,890 @@
+use std::{error::Error, path::PathBuf, time::Duration};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceFactory, ServiceRequest, ServiceResponse},
+    error::{InternalError, JsonPayloadError},
+    http::{header, StatusCode},
+    middleware, web, App, HttpRequest, HttpResponse, HttpResponseBuilder, HttpServer, Resource,
+};
+use oxc::allocator::Allocator;
+use serde_json::{json, Value};
+
+mod admin;
+mod assertion;
+mod assets;
+mod auth;
+mod batch;
+#[doc(hidden)]
+pub mod bench;
+mod cli;
+mod concurrency;
+mod date;
+mod defaults;
+mod diff;
+mod entity_docs;
+mod format;
+mod generate;
+mod har;
+mod hooks;
+mod idempotency;
+mod infer;
+mod invalid;
+mod ir;
+mod jsonschema;
+mod logging;
+mod metrics;
+mod openapi;
+mod options;
+mod overrides;
+mod parser;
+mod port;
+mod profiles;
+mod query;
+mod recording;
+mod replay;
+mod resolve;
+mod route_table;
+mod sample;
+mod scenario;
+mod serializer;
+mod sidecar;
+mod snapshot;
+mod store;
+mod summary;
+mod throttle;
+mod validation;
+mod versioning;
+mod view;
+mod warning;
+
+use cli::{Command, Config};
+use generate::{FakerKind, NumberFormat};
+use query::CollectionQuery;
+
+/// A declared type and the route it's served at.
+#[derive(Debug, Clone, Default)]
+pub struct Entity {
+    route: String,
+    /// The directory part of `route`, when `--routes-from-dirs` added one.
+    route_prefix: Option<String>,
+    name: String,
+    /// The file the entity was declared in.
+    source: PathBuf,
+    description: Option<String>,
+    /// First line of the route comment, used as the operation summary.
+    summary: Option<String>,
+    /// OpenAPI operation tags, from `@tag`.
+    tags: Vec<String>,
+    /// From `@operationId`; the GET operation's id, the others prefix it.
+    operation_id: Option<String>,
+    /// From `version=N` on the route comment.
+    version: Option<u32>,
+    /// Response rate in bytes per second, from `@bandwidth`.
+    bandwidth: Option<u64>,
+    /// Entity tags as applied, with where each came from.
+    settings: Vec<defaults::Setting>,
+    /// Media type of GET responses, from `@content-type`; JSON when unset.
+    content_type: Option<String>,
+    /// Media type responses are always serialized as, from `@format`.
+    format: Option<String>,
+    /// Prop whose value is the whole response body, from `@body-from`.
+    body_from: Option<String>,
+    /// Named overrides of the GET response, picked with `?scenario=`.
+    scenarios: Vec<scenario::Scenario>,
+    /// How PUT and PATCH detect stale writes, from `@concurrency`.
+    concurrency: concurrency::Concurrency,
+    /// Credential the routes require, from `@auth`.
+    auth: Option<auth::Auth>,
+    /// From `@role`: credentials must hold one of these.
+    roles: Vec<String>,
+    /// From `@soft-delete`: under `--stateful`, DELETE stamps `deletedAt`
+    /// instead of removing the item.
+    soft_delete: bool,
+    /// From `@consistency-lag`: how long `--stateful` reads miss a write.
+    consistency_lag: Option<Duration>,
+    /// From `@transform`: reshapes each object right before it's sent.
+    view: Option<view::View>,
+    /// The literal a routed `const` declares, served as is.
+    data: Option<Value>,
+    /// Declared as `type Users = User[]`: GET returns a list of the props'
+    /// objects rather than one.
+    list: bool,
+    props: Vec<Prop>,
+}
+
+impl Entity {
+    /// The interface, class or schema name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The declared route, such as `/users/{id}`.
+    pub fn route(&self) -> &str {
+        &self.route
+    }
+
+    /// Whether GET returns a list of objects rather than one.
+    pub fn is_list(&self) -> bool {
+        self.list
+    }
+
+    /// The prop detail routes and the `--stateful` store look items up by:
+    /// the `@key` one, or `id`.
+    fn key(&self) -> &str {
+        key_name(&self.props)
+    }
+
+    /// The detail route under `path`, such as `/users/{id}`, its param named
+    /// after the key.
+    fn item_route(&self, path: &str) -> String {
+        format!("{}/{{{}}}", path.trim_end_matches('/'), self.key())
+    }
+
+    /// The path this entity is served at; `--version-prefix` moves versioned
+    /// entities under `/v{n}`.
+    fn served_route(&self, version_prefix: bool) -> String {
+        match self.version {
+            Some(version) if version_prefix => format!("/v{version}{}", self.route),
+            _ => self.route.clone(),
+        }
+    }
+}
+
+/// A route ending in `*` (`/api/*`) catches every path under its prefix.
+fn wildcard_prefix(route: &str) -> Option<&str> {
+    route.strip_suffix('*')
+}
+
+/// Turns a route into an actix path pattern, mapping a trailing `*` to a
+/// catch-all tail segment.
+fn actix_path(route: &str) -> String {
+    match wildcard_prefix(route) {
+        Some(prefix) => format!("{prefix}{{tail:.*}}"),
+        None => route.to_string(),
+    }
+}
+
+/// Groups entities sharing a route, in the order routes were first seen.
+/// Wildcard routes come last, longest prefix first, so that registering in
+/// this order lets specific routes take precedence.
+fn route_groups(entities: &[Entity]) -> Vec<(&str, Vec<&Entity>)> {
+    let mut groups: Vec<(&str, Vec<&Entity>)> = Vec::new();
+    for entity in entities {
+        match groups.iter_mut().find(|(route, _)| *route == entity.route) {
+            Some((_, group)) => group.push(entity),
+            None => groups.push((&entity.route, vec![entity])),
+        }
+    }
+    groups.sort_by_key(|(route, _)| {
+        wildcard_prefix(route).map(|prefix| std::cmp::Reverse(prefix.len()))
+    });
+    groups
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Prop {
+    id: String,
+    ty: TProp,
+    optional: bool,
+    description: Option<String>,
+    faker: Option<FakerKind>,
+    min: Option<f64>,
+    max: Option<f64>,
+    /// Chance an optional prop is included, from `@presentRate`.
+    present_rate: Option<f64>,
+    /// Integer, decimal or currency values for `number` props.
+    number_format: Option<NumberFormat>,
+    /// Emit numbers as JSON strings, from `@asString`.
+    as_string: bool,
+    /// Array length bounds, from `@minItems`/`@maxItems`.
+    min_items: Option<usize>,
+    max_items: Option<usize>,
+    /// Chance an array is generated empty, from `@emptyRate`.
+    empty_rate: Option<f64>,
+    /// Date bounds in seconds since the epoch, from `@minDate`/`@maxDate`.
+    min_date: Option<i64>,
+    max_date: Option<i64>,
+    /// From `@key`: items are looked up by this prop instead of `id`.
+    key: bool,
+}
+
+/// The prop items are looked up by when none is marked `@key`.
+const DEFAULT_KEY: &str = "id";
+
+/// The name of the prop that identifies objects with these props.
+fn key_name(props: &[Prop]) -> &str {
+    props
+        .iter()
+        .find(|prop| prop.key)
+        .map_or(DEFAULT_KEY, |prop| &prop.id)
+}
+
+impl Prop {
+    /// What an array prop's items are generated from: the same tags, with
+    /// the item type.
+    fn item(&self) -> Option<Prop> {
+        match &self.ty {
+            TProp::Array(item) => Some(Prop {
+                ty: (**item).clone(),
+                ..self.clone()
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+enum TProp {
+    Boolean,
+    Number,
+    /// TypeScript `bigint`; values exceed what a double holds exactly.
+    BigInt,
+    #[default]
+    String,
+    /// One of a fixed set of JSON values.
+    Enum(Vec<Value>),
+    /// A nested object, from a reference to another interface.
+    Object {
+        name: String,
+        props: Vec<Prop>,
+    },
+    /// `T[]` or `Array<T>`.
+    Array(Box<TProp>),
+    /// `Date`, sent as an ISO-8601 string.
+    Date,
+}
+
+async fn generate_fake_data(
+    req: HttpRequest,
+    entity: web::Data<Entity>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    let bandwidth = match throttle::bandwidth_for(&req, &entity, &config) {
+        Ok(bandwidth) => bandwidth,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    let scenario = match scenario::requested(&req, &entity) {
+        Ok(scenario) => scenario.cloned(),
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    let patch = match overrides::requested(&req) {
+        Ok(patch) => patch,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    if let Some(scenario) = scenario {
+        if let Some(delay) = scenario.delay {
+            actix_web::rt::time::sleep(delay).await;
+        }
+        if let Some(body) = &scenario.body {
+            let status = scenario.status.unwrap_or(StatusCode::OK);
+            let mut body = body.clone();
+            if let Some(patch) = &patch {
+                overrides::apply(&mut body, patch);
+            }
+            return reply(&req, HttpResponse::build(status), &body, bandwidth);
+        }
+        if let Some(status) = scenario.status {
+            let response = HttpResponse::build(status);
+            return generated_data(req, entity, config, bandwidth, response, patch);
+        }
+    }
+    generated_data(req, entity, config, bandwidth, HttpResponse::Ok(), patch)
+}
+
+/// The generated GET response, with any requested violations applied and
+/// the `X-Mock-Override` merged over it.
+fn generated_data(
+    req: HttpRequest,
+    entity: web::Data<Entity>,
+    config: web::Data<Config>,
+    bandwidth: Option<u64>,
+    mut response: HttpResponseBuilder,
+    patch: Option<Value>,
+) -> HttpResponse {
+    if let Some(data) = &entity.data {
+        let mut data = data.clone();
+        hooks::transform(&req, &entity, &mut data);
+        if let Some(patch) = &patch {
+            overrides::apply(&mut data, patch);
+        }
+        if let Some(status) = hooks::run(&req, &entity.route, &mut data) {
+            response.status(status);
+        }
+        return reply_entity(&req, response, &entity, &data, bandwidth);
+    }
+    if entity.list {
+        return list_fake_data(req, entity, config, bandwidth, response, patch);
+    }
+    let ctx = match generate::requested(&req, &config) {
+        Ok(ctx) => ctx,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    let mut rng = generate::rng_for(config.seed, req.path());
+    let mut data = generate::fake_object(&entity, &config, ctx, &mut rng);
+
+    let violations = match invalid::requested(&req, &entity, config.fuzz, &mut rng) {
+        Ok(violations) => violations,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    if !violations.is_empty() {
+        for (prop, violation) in &violations {
+            invalid::apply(&mut data, prop, *violation);
+        }
+        response.insert_header(("X-Mock-Violations", invalid::describe(&violations)));
+    }
+    hooks::transform(&req, &entity, &mut data);
+    if let Some(patch) = &patch {
+        overrides::apply(&mut data, patch);
+    }
+    if let Some(status) = hooks::run(&req, &entity.route, &mut data) {
+        response.status(status);
+    }
+
+    reply_entity(&req, response, &entity, &data, bandwidth)
+}
+
+/// `?count=` objects of a list entity, generated while the response is
+/// written rather than up front, so large counts don't pile up in memory;
+/// request hooks need the whole list, so with any registered it's built
+/// first.
+/// With filters, `?sort=` or `?page=`, `?count=` instead sizes the pool
+/// they pick from, generated whole first, so it's capped at `--max-pool`.
+fn list_fake_data(
+    req: HttpRequest,
+    entity: web::Data<Entity>,
+    config: web::Data<Config>,
+    bandwidth: Option<u64>,
+    mut response: HttpResponseBuilder,
+    patch: Option<Value>,
+) -> HttpResponse {
+    let query = match CollectionQuery::requested(&req, &entity, &config) {
+        Ok(query) => query,
+        Err(bad) => return bad.response(),
+    };
+    let count = query.count.unwrap_or(generate::DEFAULT_COUNT);
+    if !query.is_empty() && count > config.max_pool {
+        let mut bad = query::BadParams::default();
+        bad.push(
+            "count",
+            &count.to_string(),
+            format!(
+                "at most {} (`--max-pool`) with filters, `sort` or paging",
+                config.max_pool
+            ),
+        );
+        return bad.response();
+    }
+    let ctx = match generate::requested(&req, &config) {
+        Ok(ctx) => ctx,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    let mut rng = generate::rng_for(config.seed, req.path());
+    let violations: Vec<(Prop, invalid::Violation)> =
+        match invalid::requested(&req, &entity, config.fuzz, &mut rng) {
+            Ok(violations) => violations
+                .into_iter()
+                .map(|(prop, violation)| (prop.clone(), violation))
+                .collect(),
+            Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+        };
+
+    if !violations.is_empty() {
+        let described: Vec<_> = violations.iter().map(|(p, v)| (p, *v)).collect();
+        response.insert_header(("X-Mock-Violations", invalid::describe(&described)));
+    }
+    if !query.is_empty() {
+        // One pool per path whatever the query, seeded even without
+        // `--seed`, so every page and filter slices the same items.
+        let seed = config.seed.unwrap_or(store::DEFAULT_SEED);
+        let mut pool_rng = generate::rng_for(Some(seed), req.path());
+        let pool = match generate::fake_response(&entity, &config, ctx, Some(count), &mut pool_rng)
+        {
+            Value::Array(items) => items,
+            _ => Vec::new(),
+        };
+        let (mut items, total) = query.apply(pool);
+        for item in &mut items {
+            for (prop, violation) in &violations {
+                invalid::apply(item, prop, *violation);
+            }
+            hooks::transform(&req, &entity, item);
+            if let Some(patch) = &patch {
+                overrides::apply(item, patch);
+            }
+        }
+        query.describe(&mut response, &req, total);
+        let mut items = Value::Array(items);
+        if let Some(status) = hooks::run(&req, &entity.route, &mut items) {
+            response.status(status);
+        }
+        return reply(&req, response, &items, bandwidth);
+    }
+    let route = entity.route.clone();
+    let mut memo = generate::Memo::default();
+    let transforms = req.app_data::<web::Data<hooks::Transforms>>().cloned();
+    let items = (0..count).map(move |_| {
+        let mut item = generate::fake_list_item(&entity, &config, ctx, &mut memo, &mut rng);
+        for (prop, violation) in &violations {
+            invalid::apply(&mut item, prop, *violation);
+        }
+        if let Some(transforms) = &transforms {
+            transforms.apply(&entity, &mut item);
+        }
+        if let Some(view) = &entity.view {
+            view.apply(&mut item);
+        }
+        if let Some(patch) = &patch {
+            overrides::apply(&mut item, patch);
+        }
+        item
+    });
+    // Hooks see the whole list, as on every other route, so it can't be
+    // streamed past them.
+    if hooks::registered(&req) || !format::is_json(serializer::negotiate(&req).content_type()) {
+        let mut items = Value::Array(items.collect());
+        if let Some(status) = hooks::run(&req, &route, &mut items) {
+            response.status(status);
+        }
+        return reply(&req, response, &items, bandwidth);
+    }
+    let body = format::json_array(items, format::pretty(&req));
+    response.content_type(format::JSON_UTF8);
+    match bandwidth {
+        None => response.streaming(body),
+        Some(bytes_per_sec) => response.streaming(throttle::paced(body, bytes_per_sec)),
+    }
+}
+
+/// Echoes the submitted fields back over a freshly generated object, so
+/// POST/PUT look like a successful create/replace.
+async fn accept_fake_data(
+    req: HttpRequest,
+    entity: web::Data<Entity>,
+    config: web::Data<Config>,
+    body: web::Json<Value>,
+) -> HttpResponse {
+    let bandwidth = match throttle::bandwidth_for(&req, &entity, &config) {
+        Ok(bandwidth) => bandwidth,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    let failures = validation::check(&entity, &body, &config, validation::Body::Partial);
+    if !failures.is_empty() {
+        return validation::reject(&req, &entity, &body, failures);
+    }
+    let ctx = match generate::requested(&req, &config) {
+        Ok(ctx) => ctx,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    let mut rng = generate::rng_for(config.seed, req.path());
+    let mut data = generate::fake_object(&entity, &config, ctx, &mut rng);
+    if let (Some(data), Value::Object(fields)) = (data.as_object_mut(), body.into_inner()) {
+        data.extend(fields);
+    }
+
+    let mut response = if req.method() == actix_web::http::Method::POST {
+        HttpResponse::Created()
+    } else {
+        HttpResponse::Ok()
+    };
+    hooks::transform(&req, &entity, &mut data);
+    if let Some(status) = hooks::run(&req, &entity.route, &mut data) {
+        response.status(status);
+    }
+    reply(&req, response, &data, bandwidth)
+}
+
+/// Sends `data` as JSON (indented when `?pretty` asks), streamed at
+/// `bandwidth` bytes/s when one applies.
+fn reply(
+    req: &HttpRequest,
+    mut response: HttpResponseBuilder,
+    data: &Value,
+    bandwidth: Option<u64>,
+) -> HttpResponse {
+    let serializer = serializer::negotiate(req);
+    let body = if format::pretty(req) {
+        serializer.serialize_pretty(data)
+    } else {
+        serializer.serialize(data)
+    };
+    match body {
+        Ok(body) => {
+            response.content_type(serializer.content_type());
+            send(response, body, bandwidth)
+        }
+        Err(error) => HttpResponse::InternalServerError().json(json!({
+            "error": format!("serializing as {} failed: {error}", serializer.content_type()),
+        })),
+    }
+}
+
+/// Like `reply`, but in the entity's `@content-type`, with the `@body-from`
+/// prop as the raw body when it names one.
+fn reply_entity(
+    req: &HttpRequest,
+    mut response: HttpResponseBuilder,
+    entity: &Entity,
+    data: &Value,
+    bandwidth: Option<u64>,
+) -> HttpResponse {
+    let Some(content_type) = &entity.content_type else {
+        return reply(req, response, data, bandwidth);
+    };
+    let body = format::raw_body(entity, data)
+        .unwrap_or_else(|| format::to_json(data, format::pretty(req)));
+    response.content_type(content_type.as_str());
+    send(response, body, bandwidth)
+}
+
+fn send(mut response: HttpResponseBuilder, body: Vec<u8>, bandwidth: Option<u64>) -> HttpResponse {
+    match bandwidth {
+        None => response.body(body),
+        Some(bytes_per_sec) => response.streaming(throttle::throttled(body, bytes_per_sec)),
+    }
+}
+
+/// The resources serving `entity` at `path`. Under `--stateful` that's the
+/// collection plus a `{id}` item resource, and `{id}/purge` for
+/// `@soft-delete`; wildcard routes stay generated and `const` routes fixed.
+/// Each checks `@auth` before its handlers run.
+fn entity_resources(
+    path: &str,
+    entity: &Entity,
+    config: &Config,
+) -> Vec<
+    Resource<
+        impl ServiceFactory<
+            ServiceRequest,
+            Config = (),
+            Response = ServiceResponse<impl MessageBody>,
+            Error = actix_web::Error,
+            InitError = (),
+        >,
+    >,
+> {
+    let entity = web::Data::new(entity.clone());
+    resources(path, entity, config)
+        .into_iter()
+        .map(|resource| resource.wrap(middleware::from_fn(auth::require)))
+        .collect()
+}
+
+fn resources(path: &str, entity: web::Data<Entity>, config: &Config) -> Vec<Resource> {
+    if !config.stateful || wildcard_prefix(path).is_some() || entity.data.is_some() {
+        return vec![web::resource(actix_path(path))
+            .app_data(entity)
+            .route(web::get().to(generate_fake_data))
+            .route(web::post().to(accept_fake_data))
+            .route(web::put().to(accept_fake_data))];
+    }
+    let mut resources = vec![
+        web::resource(path)
+            .app_data(entity.clone())
+            .route(web::get().to(store::list))
+            .route(web::post().to(store::create)),
+        web::resource(entity.item_route(path))
+            .app_data(entity.clone())
+            .route(web::get().to(store::fetch))
+            .route(web::put().to(store::replace))
+            .route(web::patch().to(store::update))
+            .route(web::delete().to(store::remove)),
+    ];
+    if entity.soft_delete {
+        resources.push(
+            web::resource(format!("{}/purge", entity.item_route(path)))
+                .app_data(entity)
+                .route(web::delete().to(store::purge)),
+        );
+    }
+    resources
+}
+
+/// Registers a route's entities. Versions share the path and are picked by
+/// the `Accept` version profile, unless `--version-prefix` gives each its own.
+fn register_route<T>(mut app: App<T>, route: &str, group: &[&Entity], config: &Config) -> App<T>
+where
+    T: ServiceFactory<ServiceRequest, Config = (), Error = actix_web::Error, InitError = ()>,
+{
+    if config.version_prefix || group.iter().all(|e| e.version.is_none()) {
+        for entity in group {
+            for resource in
+                entity_resources(&entity.served_route(config.version_prefix), entity, config)
+            {
+                app = app.service(resource);
+            }
+        }
+        return app;
+    }
+
+    let vary = || middleware::DefaultHeaders::new().add((header::VARY, "Accept"));
+    for entity in group {
+        if let Some(version) = entity.version {
+            for resource in entity_resources(route, entity, config) {
+                app = app.service(resource.guard(versioning::accepts(version)).wrap(vary()));
+            }
+        }
+    }
+    for resource in entity_resources(route, versioning::default_entity(group), config) {
+        app = app.service(resource.guard(versioning::unversioned()).wrap(vary()));
+    }
+
+    let versions: Vec<u32> = group.iter().filter_map(|e| e.version).collect();
+    app.service(web::resource(actix_path(route)).to(move || {
+        let versions = versions.clone();
+        async move {
+            HttpResponse::NotAcceptable().json(json!({
+                "error": "unknown version requested",
+                "versions": versions,
+            }))
+        }
+    }))
+}
+
+fn json_config(max_body_bytes: usize) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(max_body_bytes)
+        .error_handler(move |err, _req| {
+            let response = match &err {
+                JsonPayloadError::Overflow { .. }
+                | JsonPayloadError::OverflowKnownLength { .. } => HttpResponse::PayloadTooLarge()
+                    .json(json!({
+                        "error": "payload too large",
+                        "limit": max_body_bytes,
+                    })),
+                _ => HttpResponse::BadRequest().json(json!({ "error": err.to_string() })),
+            };
+            InternalError::from_response(err, response).into()
+        })
+}
+
+/// Exit codes beyond the generic `1` (bad arguments, failed snapshot
+/// check), so scripts can react to each kind of failure.
+const EXIT_SCAN_FAILED: i32 = 2;
+const EXIT_BIND_FAILED: i32 = 3;
+const EXIT_WARNINGS: i32 = 4;
+const EXIT_NOT_FOUND: i32 = 5;
+const EXIT_NO_ENTITIES: i32 = 6;
+
+const HOST: &str = "127.0.0.1";
+
+pub use hooks::{Hooked, RouteMatch};
+pub use options::ServerOptions;
+pub use serializer::ResponseSerializer;
+
+/// Runs the `ssg` command line with `args` (without the program name),
+/// serving with `options`' hooks.
+pub async fn run(
+    args: impl IntoIterator<Item = String>,
+    options: ServerOptions,
+) -> Result<(), Box<dyn Error>> {
+    let root = std::env::current_dir()?;
+    let args = profiles::with_file_settings(&root, args.into_iter().collect())?;
+    let (command, config) = cli::parse_args(args)?;
+    let allocator = Allocator::default();
+    let scanned = match &config.ir {
+        Some(path) => ir::load(path).map(|entities| (entities, parser::ScanStats::default())),
+        None => parser::scan_dir(&root, &allocator, &config).map_err(|err| err.to_string()),
+    };
+    let (entities, stats) = match scanned {
+        Ok(scanned) => scanned,
+        Err(err) => {
+            let what = match config.ir {
+                Some(_) => "loading --ir",
+                None => "scanning for entities",
+            };
+            eprintln!("error: {what} failed: {err}");
+            std::process::exit(EXIT_SCAN_FAILED);
+        }
+    };
+
+    let warnings = warning::collected();
+    if config.fail_on_warning && !warnings.is_empty() {
+        let plural = if warnings.len() == 1 { "" } else { "s" };
+        eprintln!(
+            "error: {} warning{plural} (--fail-on-warning):",
+            warnings.len()
+        );
+        for warning in &warnings {
+            eprintln!("  {warning}");
+        }
+        std::process::exit(EXIT_WARNINGS);
+    }
+    if entities.is_empty() && !matches!(command, Command::List) {
+        summary::no_entities(&root, &stats, &config, !config.allow_empty);
+        if !config.allow_empty {
+            std::process::exit(EXIT_NO_ENTITIES);
+        }
+    }
+
+    match command {
+        Command::Serve if config.dry_run => {
+            summary::dry_run(&entities, &stats, &config);
+            return Ok(());
+        }
+        Command::Serve if config.dump_db.is_some() => {
+            let path = config.dump_db.as_deref().unwrap();
+            store::dump(&entities, &config, path)?;
+            println!("wrote {}", path.display());
+            return Ok(());
+        }
+        Command::Serve => (),
+        Command::Snapshot { out, check: false } => {
+            return snapshot::write(&entities, &config, &out);
+        }
+        Command::Routes { format } => {
+            route_table::print(&entities, &config, format);
+            return Ok(());
+        }
+        Command::Fake {
+            name,
+            count,
+            ndjson,
+        } => {
+            let Some(entity) = sample::find(&entities, &name) else {
+                eprintln!(
+                    "error: no entity `{name}`; available: {}",
+                    sample::available(&entities)
+                );
+                std::process::exit(EXIT_NOT_FOUND);
+            };
+            sample::print(entity, &config, count, ndjson)?;
+            return Ok(());
+        }
+        Command::Scan { emit_ir } => {
+            return ir::write(&entities, &root, emit_ir.as_deref());
+        }
+        Command::List => {
+            summary::scan(&stats, &entities);
+            summary::entities(&entities, &config);
+            if entities.is_empty() {
+                summary::no_entities(&root, &stats, &config, false);
+            }
+            return Ok(());
+        }
+        Command::Snapshot { out, check: true } => {
+            if !snapshot::check(&entities, &config, &out)? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+    }
+
+    summary::scan(&stats, &entities);
+    summary::entities(&entities, &config);
+    if config.print_entities {
+        summary::entity_details(&entities, &config);
+    }
+    let manifest_entities = web::Data::new(entities.clone());
+    // One file for every instance, so a trace holds the whole run.
+    let trace = match &config.record {
+        Some(path) => Some(web::Data::new(recording::Trace::open(path).map_err(
+            |err| format!("can't open --record file {}: {err}", path.display()),
+        )?)),
+        None => None,
+    };
+    let (hooks, transforms, serializers) = options.into_parts();
+    if let Err(error) = serializers.check_formats(&entities) {
+        eprintln!("error: {error}");
+        std::process::exit(EXIT_SCAN_FAILED);
+    }
+    let hooks = web::Data::new(hooks);
+    let transforms = web::Data::new(transforms);
+    let serializers = web::Data::new(serializers);
+    let routes: Vec<(String, Vec<Entity>)> = route_groups(&entities)
+        .into_iter()
+        .map(|(route, group)| (route.to_string(), group.into_iter().cloned().collect()))
+        .collect();
+
+    // Instances share the entities, hooks and seed; each gets its own port
+    // and its own store, history, metrics, replay and idempotency keys.
+    let instances = config.instances;
+    let mut servers = Vec::new();
+    for instance in 0..instances {
+        let port = config.port + instance;
+        let config = web::Data::new(Config {
+            port,
+            instance,
+            ..config.clone()
+        });
+        let recorder = web::Data::new(recording::Recorder::new(config.request_history));
+        let replay = match &config.replay {
+            Some(path) => Some(web::Data::new(
+                replay::Replay::load(path).map_err(|err| format!("--replay: {err}"))?,
+            )),
+            None => None,
+        };
+        let metrics = web::Data::new(metrics::Metrics::new());
+        let store = web::Data::new(store::Store::default());
+        let validation = web::Data::new(validation::Report::default());
+        let idempotency = web::Data::new(idempotency::Idempotency::new(
+            config.idempotency_keys,
+            config.idempotency_ttl,
+        ));
+        if config.stateful {
+            store.reset(&entities, &config);
+        }
+
+        let manifest_entities = manifest_entities.clone();
+        let trace = trace.clone();
+        let hooks = hooks.clone();
+        let transforms = transforms.clone();
+        let serializers = serializers.clone();
+        let routes = routes.clone();
+        let app = HttpServer::new(move || {
+            let mut app = App::new()
+                .wrap(middleware::from_fn(format::json_charset))
+                .wrap(middleware::from_fn(idempotency::replay))
+                .wrap(middleware::from_fn(replay::respond))
+                .wrap(middleware::from_fn(throttle::jitter))
+                .wrap(middleware::from_fn(recording::record))
+                .wrap(middleware::from_fn(metrics::count))
+                .wrap(middleware::from_fn(logging::log_requests))
+                .app_data(config.clone())
+                .app_data(recorder.clone())
+                .app_data(metrics.clone())
+                .app_data(store.clone())
+                .app_data(validation.clone())
+                .app_data(hooks.clone())
+                .app_data(transforms.clone())
+                .app_data(serializers.clone())
+                .app_data(idempotency.clone())
+                .app_data(json_config(config.max_body_bytes))
+                .app_data(manifest_entities.clone())
+                .route("/_openapi.json", web::get().to(openapi::serve_openapi))
+                .route("/_manifest", web::get().to(openapi::serve_manifest))
+                .configure(|cfg| admin::configure(cfg, &config));
+            if let Some(trace) = &trace {
+                app = app.app_data(trace.clone());
+            }
+            if let Some(replay) = &replay {
+                app = app.app_data(replay.clone());
+            }
+            for (route, group) in &routes {
+                let group: Vec<&Entity> = group.iter().collect();
+                app = register_route(app, route, &group, &config);
+            }
+            app
+        });
+
+        let server = match app.bind((HOST, port)) {
+            Ok(server) => server,
+            Err(err) => {
+                port::report_bind_failure(&err, HOST, port);
+                std::process::exit(EXIT_BIND_FAILED);
+            }
+        };
+        for addr in server.addrs() {
+            if instances == 1 {
+                assets::set_origin(addr);
+                println!("listening on http://{addr}");
+            } else {
+                println!("instance {instance} listening on http://{addr}");
+            }
+        }
+        servers.push(server.run());
+    }
+    futures_util::future::try_join_all(servers).await?;
+
+    Ok(())
+}
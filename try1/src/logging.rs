@@ -0,0 +1,36 @@
+use std::time::Instant;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::StatusCode,
+    middleware::Next,
+    web, Error,
+};
+
+use crate::cli::Config;
+
+/// Logs one line per request: method, path, status and elapsed time.
+pub async fn log_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let started = Instant::now();
+    let method = req.method().clone();
+    let path = req.path().to_string();
+    let quiet_404 = req
+        .app_data::<web::Data<Config>>()
+        .is_some_and(|config| config.quiet_404);
+
+    let res = next.call(req).await?;
+
+    let status = res.status();
+    if !(quiet_404 && status == StatusCode::NOT_FOUND) {
+        println!(
+            "{method} {path} {} {:.1}ms",
+            status.as_u16(),
+            started.elapsed().as_secs_f64() * 1000.0
+        );
+    }
+    Ok(res)
+}
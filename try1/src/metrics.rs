@@ -0,0 +1,68 @@
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex, time::Instant};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    web, Error, HttpResponse,
+};
+
+use crate::{cli::Config, Entity};
+
+/// Counters behind the metrics endpoint, covering entity traffic only.
+pub struct Metrics {
+    started: Instant,
+    responses: Mutex<BTreeMap<u16, u64>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Metrics {
+            started: Instant::now(),
+            responses: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+pub async fn count(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let metrics = req.app_data::<web::Data<Metrics>>().cloned();
+    let internal = req
+        .app_data::<web::Data<Config>>()
+        .is_some_and(|config| config.is_internal(req.path()));
+
+    let res = next.call(req).await?;
+    if let Some(metrics) = metrics.filter(|_| !internal) {
+        *metrics
+            .responses
+            .lock()
+            .unwrap()
+            .entry(res.status().as_u16())
+            .or_default() += 1;
+    }
+    Ok(res)
+}
+
+/// Prometheus text exposition of the counters.
+pub async fn serve(metrics: web::Data<Metrics>, entities: web::Data<Vec<Entity>>) -> HttpResponse {
+    let mut body = String::new();
+    body.push_str("# TYPE ssg_requests_total counter\n");
+    for (status, count) in metrics.responses.lock().unwrap().iter() {
+        let _ = writeln!(body, "ssg_requests_total{{status=\"{status}\"}} {count}");
+    }
+    let _ = writeln!(
+        body,
+        "# TYPE ssg_entities gauge\nssg_entities {}",
+        entities.len()
+    );
+    let _ = writeln!(
+        body,
+        "# TYPE ssg_uptime_seconds gauge\nssg_uptime_seconds {:.3}",
+        metrics.started.elapsed().as_secs_f64()
+    );
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(body)
+}
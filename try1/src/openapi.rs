@@ -0,0 +1,325 @@
+use actix_web::{web, HttpResponse};
+use serde_json::{json, Map, Value};
+
+use crate::{
+    auth::Auth,
+    cli::Config,
+    generate::{self, FakerKind, NumberFormat},
+    route_groups, versioning, Entity, Prop, TProp,
+};
+
+pub const METHODS: [&str; 3] = ["get", "post", "put"];
+
+pub fn json_schema(entity: &Entity, config: &Config) -> Value {
+    let mut schema = object_schema(&entity.props, config);
+    schema["title"] = json!(entity.name);
+    if let Some(description) = &entity.description {
+        schema["description"] = json!(description);
+    }
+    schema
+}
+
+fn object_schema(props: &[Prop], config: &Config) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+
+    for prop in props {
+        properties.insert(prop.id.clone(), prop_schema(prop, config));
+        if !prop.optional {
+            required.push(Value::String(prop.id.clone()));
+        }
+    }
+
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+fn prop_schema(prop: &Prop, config: &Config) -> Value {
+    let mut schema = value_schema(prop, config);
+    if let Some(description) = &prop.description {
+        schema["description"] = json!(description);
+    }
+    schema
+}
+
+fn value_schema(prop: &Prop, config: &Config) -> Value {
+    if let Some(item) = prop.item() {
+        let mut schema = json!({ "type": "array", "items": value_schema(&item, config) });
+        if let Some(min) = prop.min_items {
+            schema["minItems"] = json!(min);
+        }
+        if let Some(max) = prop.max_items {
+            schema["maxItems"] = json!(max);
+        }
+        return schema;
+    }
+
+    let decimals = prop
+        .number_format
+        .as_ref()
+        .map_or(0, NumberFormat::precision);
+    let mut schema = match &prop.ty {
+        _ if generate::as_string(prop, config) && decimals > 0 => {
+            json!({ "type": "string", "pattern": "^-?[0-9]+(\\.[0-9]+)?$" })
+        }
+        _ if generate::as_string(prop, config) => {
+            json!({ "type": "string", "pattern": "^-?[0-9]+$" })
+        }
+        TProp::Boolean => json!({ "type": "boolean" }),
+        TProp::Number if decimals > 0 => json!({ "type": "number" }),
+        TProp::Number => json!({ "type": "integer" }),
+        TProp::BigInt => json!({ "type": "integer", "format": "int64" }),
+        TProp::String => json!({ "type": "string" }),
+        TProp::Enum(values) => json!({ "enum": values }),
+        TProp::Object { props, .. } => object_schema(props, config),
+        TProp::Array(_) => unreachable!("arrays are handled above"),
+        TProp::Date => json!({ "type": "string", "format": "date-time" }),
+    };
+    if let Some(format) = prop.faker.and_then(FakerKind::format) {
+        if schema["type"] == "string" {
+            schema["format"] = json!(format);
+        }
+    }
+    if let Some(NumberFormat::Currency(code)) = &prop.number_format {
+        schema["x-currency"] = json!(code);
+    }
+    // Bounds only apply to JSON numbers.
+    if schema["type"] == "integer" || schema["type"] == "number" {
+        if let Some(min) = prop.min {
+            schema["minimum"] = json!(min);
+        }
+        if let Some(max) = prop.max {
+            schema["maximum"] = json!(max);
+        }
+    }
+    schema
+}
+
+/// Versions of one route are grouped: under `--version-prefix` each gets its
+/// own path tagged with the shared route, otherwise they share the path and
+/// are told apart by their `application/json; version=N` media type.
+pub fn document(entities: &[Entity], config: &Config) -> Value {
+    let version_prefix = config.version_prefix;
+    let mut paths = Map::new();
+    let mut schemas = Map::new();
+    let mut security_schemes = Map::new();
+
+    for entity in entities {
+        schemas.insert(schema_name(entity, entities), json_schema(entity, config));
+        if let Some(auth) = &entity.auth {
+            security_schemes.insert(security_scheme_name(auth), security_scheme(auth));
+        }
+    }
+
+    for (route, group) in route_groups(entities) {
+        let versioned = group.iter().any(|e| e.version.is_some());
+        if version_prefix || !versioned {
+            for entity in &group {
+                let mut content = Map::new();
+                content.insert("application/json".into(), schema_ref(entity, entities));
+                let path = entity.served_route(version_prefix);
+                let mut item = path_item(entity, &path, &content);
+                if versioned {
+                    for operation in item.values_mut() {
+                        if let Some(tags) = operation["tags"].as_array_mut() {
+                            tags.push(json!(route));
+                        } else {
+                            operation["tags"] = json!([route]);
+                        }
+                    }
+                }
+                paths.insert(path, Value::Object(item));
+            }
+        } else {
+            let default = versioning::default_entity(&group);
+            let mut content = Map::new();
+            content.insert("application/json".into(), schema_ref(default, entities));
+            for entity in &group {
+                if let Some(version) = entity.version {
+                    content.insert(
+                        format!("application/json; version={version}"),
+                        schema_ref(entity, entities),
+                    );
+                }
+            }
+            paths.insert(
+                route.to_string(),
+                Value::Object(path_item(default, route, &content)),
+            );
+        }
+    }
+
+    let mut components = json!({ "schemas": schemas });
+    if !security_schemes.is_empty() {
+        components["securitySchemes"] = Value::Object(security_schemes);
+    }
+    json!({
+        "openapi": "3.0.3",
+        "info": { "title": "ssg mock server", "version": env!("CARGO_PKG_VERSION") },
+        "paths": paths,
+        "components": components,
+    })
+}
+
+fn security_scheme_name(auth: &Auth) -> String {
+    match auth {
+        Auth::Bearer => "bearer".to_string(),
+        Auth::ApiKey(header) => header.to_string(),
+    }
+}
+
+fn security_scheme(auth: &Auth) -> Value {
+    match auth {
+        Auth::Bearer => json!({ "type": "http", "scheme": "bearer" }),
+        Auth::ApiKey(header) => {
+            json!({ "type": "apiKey", "in": "header", "name": header.as_str() })
+        }
+    }
+}
+
+fn path_item(entity: &Entity, path: &str, content: &Map<String, Value>) -> Map<String, Value> {
+    // List entities return an array from GET; writes still take one item.
+    let list_content: Map<String, Value> = content
+        .iter()
+        .map(|(media_type, item)| {
+            let schema = json!({ "type": "array", "items": item["schema"] });
+            (media_type.clone(), json!({ "schema": schema }))
+        })
+        .collect();
+    let mut operations = Map::new();
+    for method in METHODS {
+        let status = if method == "post" { "201" } else { "200" };
+        let raw_content;
+        let response_content = match &entity.content_type {
+            Some(content_type) if method == "get" => {
+                let schema = match entity.body_from {
+                    Some(_) => json!({ "schema": { "type": "string" } }),
+                    None => content.values().next().cloned().unwrap_or_default(),
+                };
+                raw_content = Map::from_iter([(content_type.clone(), schema)]);
+                &raw_content
+            }
+            _ if entity.list && method == "get" => &list_content,
+            _ => content,
+        };
+        let mut operation = json!({
+            "operationId": operation_id(entity, method, path),
+            "summary": summary(entity, method, path),
+            "responses": {
+                status: {
+                    "description": format!("A generated {}", entity.name),
+                    "content": response_content,
+                },
+            },
+        });
+        if method != "get" {
+            operation["requestBody"] = json!({ "content": content });
+        }
+        if let Some(description) = operation_description(entity) {
+            operation["description"] = json!(description);
+        }
+        if !entity.tags.is_empty() {
+            operation["tags"] = json!(entity.tags);
+        }
+        if let Some(auth) = &entity.auth {
+            operation["security"] = json!([{ security_scheme_name(auth): [] }]);
+            operation["responses"]["401"] =
+                json!({ "description": "Missing or unknown credentials" });
+            if !entity.roles.is_empty() {
+                operation["responses"]["403"] = json!({
+                    "description": format!("Credentials without any of the roles {}", entity.roles.join(", ")),
+                });
+            }
+        }
+        operations.insert(method.to_string(), operation);
+    }
+    operations
+}
+
+/// The route comment's first line, or the method and path.
+pub fn summary(entity: &Entity, method: &str, path: &str) -> String {
+    entity
+        .summary
+        .clone()
+        .unwrap_or_else(|| format!("{} {path}", method.to_uppercase()))
+}
+
+/// What the route comment says beyond its summary line.
+pub fn operation_description(entity: &Entity) -> Option<&str> {
+    let description = entity.description.as_deref()?;
+    let rest = match &entity.summary {
+        Some(summary) => description.strip_prefix(summary.as_str())?.trim_start(),
+        None => description,
+    };
+    (!rest.is_empty()).then_some(rest)
+}
+
+/// `@operationId` for GET and prefixed by the method otherwise (`listUsers`,
+/// `postListUsers`); without one, the method and path (`getApiUsers`).
+pub fn operation_id(entity: &Entity, method: &str, path: &str) -> String {
+    match &entity.operation_id {
+        Some(id) if method == "get" => id.clone(),
+        Some(id) => format!("{method}{}", capitalize(id)),
+        None => path
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .map(capitalize)
+            .fold(method.to_string(), |id, segment| id + &segment),
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+/// Component name for an entity; versions sharing an interface name get a
+/// `_v{n}` suffix to stay distinct.
+fn schema_name(entity: &Entity, entities: &[Entity]) -> String {
+    let shared = entities.iter().filter(|e| e.name == entity.name).count() > 1;
+    match entity.version {
+        Some(version) if shared => format!("{}_v{version}", entity.name),
+        _ => entity.name.clone(),
+    }
+}
+
+fn schema_ref(entity: &Entity, entities: &[Entity]) -> Value {
+    json!({
+        "schema": { "$ref": format!("#/components/schemas/{}", schema_name(entity, entities)) }
+    })
+}
+
+pub fn manifest(entities: &[Entity], config: &Config) -> Value {
+    let entities: Vec<Value> = entities
+        .iter()
+        .map(|entity| {
+            json!({
+                "name": entity.name,
+                "route": entity.route,
+                "version": entity.version,
+                "description": entity.description,
+                "methods": METHODS.map(str::to_uppercase),
+                "schema": json_schema(entity, config),
+            })
+        })
+        .collect();
+    json!({ "entities": entities })
+}
+
+pub async fn serve_openapi(
+    entities: web::Data<Vec<Entity>>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(document(&entities, &config))
+}
+
+pub async fn serve_manifest(
+    entities: web::Data<Vec<Entity>>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(manifest(&entities, &config))
+}
@@ -0,0 +1,73 @@
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::{
+    hooks::{Hook, Hooked, Hooks, RouteMatch, Transform, Transforms},
+    serializer::{ResponseSerializer, Serializers},
+    Entity,
+};
+
+/// Settings for embedding the server rather than running it from the
+/// command line.
+#[derive(Default)]
+pub struct ServerOptions {
+    hooks: Vec<Hook>,
+    transforms: Vec<Transform>,
+    serializers: Serializers,
+}
+
+impl ServerOptions {
+    /// Adds a hook that sees each entity response after violations are
+    /// applied and before it's serialized: generated, data-backed or read
+    /// from the `--stateful` store. List routes pass the whole list, which
+    /// is then built in memory rather than streamed. It may edit or replace
+    /// the value, or answer with its own status; under `--stateful` that
+    /// edits the response, not the stored item. Hooks run in the order they
+    /// were added.
+    pub fn on_request(
+        mut self,
+        hook: impl Fn(&RouteMatch, &mut Value) -> Hooked + Send + Sync + 'static,
+    ) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Adds a transform that reshapes every value an entity responds with,
+    /// after it's generated (or read from the `--stateful` store) and
+    /// before request hooks and serialization. List routes pass each item
+    /// on its own. Transforms run in the order they were added.
+    ///
+    /// ```
+    /// let options = ssg::ServerOptions::default().transform(|entity, value| {
+    ///     if entity.name() == "User" {
+    ///         if let Some(email) = value.get_mut("email") {
+    ///             *email = "redacted@example.com".into();
+    ///         }
+    ///     }
+    /// });
+    /// ```
+    pub fn transform(
+        mut self,
+        transform: impl Fn(&Entity, &mut Value) + Send + Sync + 'static,
+    ) -> Self {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// Serves responses as `serializer`'s media type when `Accept` or an
+    /// entity's `@format` asks for it. A serializer for a media type that
+    /// already has one replaces it, JSON and XML included.
+    pub fn serializer(mut self, serializer: impl ResponseSerializer + 'static) -> Self {
+        self.serializers.register(Arc::new(serializer));
+        self
+    }
+
+    pub(crate) fn into_parts(self) -> (Hooks, Transforms, Serializers) {
+        (
+            Hooks(self.hooks),
+            Transforms(self.transforms),
+            self.serializers,
+        )
+    }
+}
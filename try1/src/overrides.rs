@@ -0,0 +1,72 @@
+use actix_web::{web, HttpRequest};
+use serde_json::Value;
+
+use crate::{cli::Config, store};
+
+/// Header holding a JSON object merged over one GET response.
+pub const OVERRIDE_HEADER: &str = "X-Mock-Override";
+
+/// The `X-Mock-Override` object, when the request sends one and
+/// `--no-request-overrides` doesn't turn them off. The header holds the
+/// JSON as is, or base64-encoded so it fits on one line.
+pub fn requested(req: &HttpRequest) -> Result<Option<Value>, String> {
+    let enabled = req
+        .app_data::<web::Data<Config>>()
+        .is_none_or(|config| config.request_overrides);
+    let Some(header) = req.headers().get(OVERRIDE_HEADER).filter(|_| enabled) else {
+        return Ok(None);
+    };
+    let text = header
+        .to_str()
+        .map_err(|_| format!("{OVERRIDE_HEADER} isn't valid text"))?
+        .trim();
+    let json = if text.starts_with('{') {
+        text.as_bytes().to_vec()
+    } else {
+        decode_base64(text).ok_or_else(|| {
+            format!("{OVERRIDE_HEADER} is neither a JSON object nor base64 of one")
+        })?
+    };
+    match serde_json::from_slice(&json) {
+        Ok(Value::Object(fields)) => Ok(Some(Value::Object(fields))),
+        Ok(_) => Err(format!("{OVERRIDE_HEADER} must be a JSON object")),
+        Err(err) => Err(format!("{OVERRIDE_HEADER} isn't valid JSON: {err}")),
+    }
+}
+
+/// Merges the override over a response the way PATCH merges a body over a
+/// stored item; a list response gets it merged into every item.
+pub fn apply(data: &mut Value, patch: &Value) {
+    match data {
+        Value::Array(items) => {
+            for item in items {
+                store::merge(item, patch.clone());
+            }
+        }
+        data => store::merge(data, patch.clone()),
+    }
+}
+
+/// Standard or URL-safe base64, padded or not.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bits = 0u32;
+    let mut count = 0;
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    for byte in text.trim_end_matches('=').bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        bits = (bits << 6) | u32::from(value);
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+        }
+    }
+    Some(out)
+}
@@ -0,0 +1,1926 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use oxc::{
+    allocator::Allocator,
+    ast::{
+        ast::{
+            Argument, BindingPatternKind, ClassElement, Declaration, Decorator, Expression,
+            ImportDeclarationSpecifier, ObjectPropertyKind, Program, Statement, TSEnumDeclaration,
+            TSIndexedAccessType, TSIntersectionType, TSSignature, TSType, TSTypeName,
+            TSTypeOperatorOperator, TSTypeQuery, TSTypeQueryExprName, VariableDeclaration,
+            VariableDeclarationKind,
+        },
+        Comment,
+    },
+    parser::{ParseOptions, Parser},
+    span::{GetSpan, SourceType},
+    syntax::operator::UnaryOperator,
+};
+use serde_json::{json, Value};
+
+use crate::{
+    auth::Auth,
+    cli::{self, Config},
+    concurrency::{self, Concurrency},
+    date,
+    defaults::{self, Defaults, Origin},
+    format,
+    generate::{FakerKind, NumberFormat},
+    infer, jsonschema,
+    resolve::Resolver,
+    scenario::Scenario,
+    serializer,
+    sidecar::{self, Sidecar},
+    store, throttle, validation,
+    view::View,
+    warning, Entity, Prop, TProp,
+};
+
+pub fn parse_typescript_file(
+    path: &Path,
+    source_text: &str,
+    allocator: &Allocator,
+    scan: &mut ScanContext,
+) -> Vec<Entity> {
+    let source_type = SourceType::from_path(path).unwrap();
+    let ret = Parser::new(allocator, source_text, source_type)
+        .with_options(ParseOptions::default())
+        .parse();
+    let source = Source {
+        path,
+        text: source_text,
+        program: &ret.program,
+    };
+
+    let keywords = &scan.config.route_keywords;
+    let sidecar = scan.sidecar(path);
+    let mut defaults = scan.directory_defaults(path.parent().unwrap_or(Path::new("")));
+    defaults.layer(&file_directives(&source), Origin::File);
+    let mut entities = Vec::new();
+    // Where comments attach to declarations ssg reads.
+    let mut declaration_starts: Vec<u32> = Vec::new();
+
+    for statement in &ret.program.body {
+        let Some(declaration) = top_level_declaration(statement) else {
+            continue;
+        };
+        let (name, decorators): (&str, &[Decorator]) = match declaration {
+            Declaration::TSInterfaceDeclaration(interface) => (&interface.id.name, &[]),
+            Declaration::TSTypeAliasDeclaration(alias) => (&alias.id.name, &[]),
+            Declaration::ClassDeclaration(class) => match &class.id {
+                Some(id) => (&id.name, &class.decorators),
+                None => continue,
+            },
+            Declaration::VariableDeclaration(variable) => match variable.declarations.as_slice() {
+                [declarator] => match &declarator.id.kind {
+                    BindingPatternKind::BindingIdentifier(id) => (&id.name, &[]),
+                    _ => continue,
+                },
+                _ => continue,
+            },
+            _ => continue,
+        };
+
+        // Comments lead the statement, or a decorator written ahead of it.
+        let starts: Vec<u32> = std::iter::once(statement.span().start)
+            .chain(decorators.iter().map(|d| d.span.start))
+            .collect();
+        declaration_starts.extend(&starts);
+        let source_comments: Vec<&Comment> = leading_comments(&ret.program.comments, &starts)
+            .filter(|c| !is_directive(c.content_span().source_text(source_text)))
+            .collect();
+        let mut comments: Vec<&str> = source_comments
+            .iter()
+            .map(|c| c.content_span().source_text(source_text))
+            .collect();
+        let sidecar_route;
+        let mut sidecar_origin = None;
+        if let Some(annotations) = sidecar.get(name) {
+            if decorator_route(decorators).is_some() || is_annotated(&comments, keywords) {
+                scan.error(format!(
+                    "{}: `{name}` is annotated both in source and in {}",
+                    path.display(),
+                    annotations.origin.display()
+                ));
+                continue;
+            }
+            if let Some(route) = &annotations.route {
+                sidecar_route = format!("{} {route}", keywords[0]);
+                comments.push(&sidecar_route);
+                sidecar_origin = Some(annotations.origin.clone());
+            }
+            comments.push(&annotations.doc);
+        }
+        let route_line = comments.iter().enumerate().find_map(|(index, text)| {
+            let words = route_words(comment_lines(text).next()?, keywords)?;
+            (!words.is_empty()).then_some((index, words))
+        });
+        let line_of = |offset: u32| source_text[..offset as usize].matches('\n').count() + 1;
+        let (route, options, location) = match &route_line {
+            Some((index, words)) => {
+                let location = match source_comments.get(*index) {
+                    Some(comment) => format!("{}:{}", path.display(), line_of(comment.span.start)),
+                    None => sidecar_origin
+                        .as_deref()
+                        .unwrap_or(path)
+                        .display()
+                        .to_string(),
+                };
+                (words[0], &words[1..], location)
+            }
+            None => match decorator_route(decorators) {
+                Some((route, start)) => (
+                    route,
+                    &[][..],
+                    format!("{}:{}", path.display(), line_of(start)),
+                ),
+                None => continue,
+            },
+        };
+        // A word after the path that isn't an option continues the path.
+        if let Some(word) = options.first().filter(|word| !word.contains('=')) {
+            scan.error(format!(
+                "{location}: route `{route} {word}` on `{name}` can't contain spaces"
+            ));
+            continue;
+        }
+        let route_prefix = scan.route_prefix(path).filter(|_| !route.starts_with('/'));
+        let joined = match &route_prefix {
+            Some(prefix) => match route.trim_start_matches("./") {
+                "" | "." => prefix.clone(),
+                route => format!("{}/{route}", prefix.trim_end_matches('/')),
+            },
+            None => route.to_string(),
+        };
+        let route = match normalize_route(&joined) {
+            Ok((normalized, fixes)) => {
+                if !fixes.is_empty() {
+                    warning::warn(
+                        path,
+                        format!(
+                            "route `{route}` on `{name}` served as `{normalized}`: {}",
+                            fixes.join(", ")
+                        ),
+                    );
+                    scan.stats.route_fixes.push(RouteFix {
+                        location,
+                        written: route.to_string(),
+                        route: normalized.clone(),
+                        fixes,
+                    });
+                }
+                normalized
+            }
+            Err(error) => {
+                scan.error(format!("{location}: route `{route}` on `{name}`: {error}"));
+                continue;
+            }
+        };
+
+        let doc = parse_doc(
+            comments
+                .iter()
+                .flat_map(|text| comment_lines(text))
+                .filter(|line| route_words(line, keywords).is_none()),
+        );
+        let mut entity = Entity {
+            route,
+            route_prefix,
+            name: name.to_string(),
+            source: path.to_path_buf(),
+            description: doc.description(),
+            summary: doc.prose.first().cloned(),
+            ..Default::default()
+        };
+        for option in options {
+            apply_route_option(&mut entity, option, path);
+        }
+        let mut layered = defaults.clone();
+        let entity_tags: Vec<(String, String)> = doc
+            .tags
+            .iter()
+            .filter(|(tag, _)| ENTITY_TAGS.contains(&tag.as_str()))
+            .cloned()
+            .collect();
+        layered.layer(&entity_tags, Origin::Entity);
+        for setting in layered.settings() {
+            apply_entity_tag(&mut entity, &setting.tag, &setting.value, path);
+            entity.settings.push(setting);
+        }
+        for (_, value) in doc.tags.iter().filter(|(tag, _)| tag == "scenario") {
+            match Scenario::parse(value) {
+                Ok(scenario) if entity.scenarios.iter().any(|s| s.name == scenario.name) => {
+                    warning::warn(
+                        path,
+                        format!(
+                            "@scenario `{}` on `{}` is declared twice, keeping the first",
+                            scenario.name, entity.name
+                        ),
+                    )
+                }
+                Ok(scenario) => entity.scenarios.push(scenario),
+                Err(error) => {
+                    warning::warn(path, format!("@scenario on `{}`: {error}", entity.name))
+                }
+            }
+        }
+
+        scan.stack.push((path.to_path_buf(), entity.name.clone()));
+        let props = match declaration {
+            Declaration::TSTypeAliasDeclaration(alias) => {
+                match prop_type(&source, &alias.type_annotation, scan) {
+                    Some(TProp::Array(item)) => match *item {
+                        TProp::Object { props, .. } => {
+                            entity.list = true;
+                            if let Some(element) = element_name(&alias.type_annotation) {
+                                entity.name = element.to_string();
+                            }
+                            Some(props)
+                        }
+                        _ => None,
+                    },
+                    _ => None,
+                }
+            }
+            Declaration::VariableDeclaration(variable) => {
+                const_data(&source, variable, &mut entity, scan)
+            }
+            declaration => Some(declaration_props(&source, declaration, scan)),
+        };
+        let props = props.map(|mut props| {
+            for (_, value) in doc.tags.iter().filter(|(tag, _)| tag == "compose") {
+                props.extend(compose_props(&source, name, value, scan));
+            }
+            props
+        });
+        scan.stack.pop();
+        let Some(props) = props else {
+            warning::warn(
+                path,
+                format!(
+                    "route on `{name}` needs an interface, a class, an array of one or a literal const"
+                ),
+            );
+            continue;
+        };
+        entity.props = props;
+        if let Some((_, file)) = doc.tags.iter().find(|(tag, _)| tag == "transform") {
+            let file = path
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(file.trim_start_matches("./"));
+            match View::load(&file, &entity.props) {
+                Ok(view) => entity.view = Some(view),
+                Err(error) => {
+                    scan.error(format!(
+                        "{}: @transform on `{name}`: {error}",
+                        path.display()
+                    ));
+                    continue;
+                }
+            }
+        }
+        if let Err(error) = check_content_type(&entity)
+            .and_then(|()| check_soft_delete(&entity))
+            .and_then(|()| check_concurrency(&entity))
+            .and_then(|()| check_key(&entity))
+        {
+            scan.error(format!("{}: {error}", path.display()));
+            continue;
+        }
+        entities.push(entity);
+    }
+
+    for name in sidecar.keys() {
+        if source.declaration(name).is_none() {
+            warning::warn(
+                path,
+                format!(
+                    "no interface or class `{name}` for the annotations in {}",
+                    sidecar[name].origin.display()
+                ),
+            );
+        }
+    }
+    scan.stats.stray_routes += ret
+        .program
+        .comments
+        .iter()
+        .filter(|c| !declaration_starts.contains(&c.attached_to))
+        .filter(|c| mentions_route(c.content_span().source_text(source_text), keywords))
+        .count();
+    entities
+}
+
+/// Whether a comment names a route keyword anywhere, in any case.
+fn mentions_route(text: &str, keywords: &[String]) -> bool {
+    let text = text.to_ascii_lowercase();
+    keywords
+        .iter()
+        .any(|keyword| text.contains(&keyword.to_ascii_lowercase()))
+}
+
+/// Whether comments already route or tag a declaration, so a sidecar can't.
+fn is_annotated(comments: &[&str], keywords: &[String]) -> bool {
+    comments
+        .iter()
+        .flat_map(|text| comment_lines(text))
+        .any(|line| {
+            route_words(line, keywords).is_some()
+                || !parse_doc(std::iter::once(line)).tags.is_empty()
+        })
+}
+
+/// The declaration a top-level statement makes, looking through `export`.
+fn top_level_declaration<'s, 'a>(statement: &'s Statement<'a>) -> Option<&'s Declaration<'a>> {
+    match statement {
+        Statement::ExportNamedDeclaration(export) => export.declaration.as_ref(),
+        statement => statement.as_declaration(),
+    }
+}
+
+/// The type name in `User[]` or `Array<User>`, which names a list route's
+/// items.
+fn element_name<'a>(ty: &'a TSType) -> Option<&'a str> {
+    let element = match ty {
+        TSType::TSArrayType(array) => &array.element_type,
+        TSType::TSTypeReference(reference) => reference.type_parameters.as_ref()?.params.first()?,
+        _ => return None,
+    };
+    match element {
+        TSType::TSTypeReference(reference) => match &reference.type_name {
+            TSTypeName::IdentifierReference(id) => Some(id.name.as_str()),
+            TSTypeName::QualifiedName(_) => None,
+        },
+        _ => None,
+    }
+}
+
+/// The path of a `@Route('/users')` class decorator, and where it starts.
+fn decorator_route<'a>(decorators: &'a [Decorator]) -> Option<(&'a str, u32)> {
+    decorators.iter().find_map(|decorator| {
+        let Expression::CallExpression(call) = &decorator.expression else {
+            return None;
+        };
+        match (&call.callee, call.arguments.first()) {
+            (Expression::Identifier(callee), Some(Argument::StringLiteral(path)))
+                if callee.name == "Route" =>
+            {
+                Some((path.value.as_str(), decorator.span.start))
+            }
+            _ => None,
+        }
+    })
+}
+
+/// State shared across the files of one scan: type resolution and the
+/// directory defaults seen so far.
+pub struct ScanContext<'c> {
+    config: &'c Config,
+    root: PathBuf,
+    resolver: Resolver,
+    /// Effective `ssg.defaults.toml` layers per directory.
+    directory_defaults: HashMap<PathBuf, Defaults>,
+    /// Interfaces being expanded, to catch self-referencing types.
+    stack: Vec<(PathBuf, String)>,
+    /// `ssg.toml` entity annotations by source file.
+    project_sidecars: HashMap<PathBuf, Sidecar>,
+    /// Combined sidecar annotations per source file read so far.
+    sidecars: HashMap<PathBuf, Sidecar>,
+    /// Problems that fail the scan once every file has been read.
+    errors: Vec<String>,
+    /// Aliases already warned about, so each is reported once.
+    warned_aliases: HashSet<(PathBuf, String)>,
+    stats: ScanStats,
+}
+
+/// What a scan read and how long it took, reported at startup.
+#[derive(Debug, Default)]
+pub struct ScanStats {
+    /// Source and schema files found.
+    pub files: usize,
+    /// Files read and parsed; the rest were skipped by size or encoding.
+    pub parsed: usize,
+    /// TypeScript files among `parsed`.
+    pub typescript: usize,
+    /// Comments mentioning a route keyword that lead no interface, type,
+    /// class or `const`, so they route nothing.
+    pub stray_routes: usize,
+    /// Imported files parsed again to resolve a type.
+    pub imports: usize,
+    pub elapsed: Duration,
+    /// Routes served differently from how they were written.
+    pub route_fixes: Vec<RouteFix>,
+}
+
+/// A route annotation normalized before serving, for `--dry-run`.
+#[derive(Debug)]
+pub struct RouteFix {
+    /// `file:line` of the annotation, or the sidecar it came from.
+    pub location: String,
+    pub written: String,
+    pub route: String,
+    pub fixes: Vec<&'static str>,
+}
+
+impl<'c> ScanContext<'c> {
+    pub fn new(config: &'c Config, root: &Path) -> Self {
+        ScanContext {
+            config,
+            root: root.to_path_buf(),
+            resolver: Resolver::default(),
+            directory_defaults: HashMap::new(),
+            stack: Vec::new(),
+            project_sidecars: HashMap::new(),
+            sidecars: HashMap::new(),
+            errors: Vec::new(),
+            warned_aliases: HashSet::new(),
+            stats: ScanStats::default(),
+        }
+    }
+
+    /// Annotations for the declarations in `path` from its `.ssg` file and
+    /// `ssg.toml`. A broken sidecar fails the scan.
+    fn sidecar(&mut self, path: &Path) -> Sidecar {
+        let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Some(sidecar) = self.sidecars.get(&key) {
+            return sidecar.clone();
+        }
+        let project = self.project_sidecars.get(&key).cloned();
+        let sidecar = sidecar::read_file(path)
+            .and_then(|file| sidecar::merge(file.into_iter().chain(project)))
+            .unwrap_or_else(|err| {
+                self.error(err);
+                Sidecar::new()
+            });
+        self.sidecars.insert(key, sidecar.clone());
+        sidecar
+    }
+
+    /// What `--routes-from-dirs` puts before relative routes declared in
+    /// `path`: its location under the routes directory, without the
+    /// extension or an `index`/`_index` file name (`admin/users.ts` gives
+    /// `/admin/users`), and with `[id]` segments as `{id}` params, as in
+    /// file-based routers. `None` when the flag is off or `path` is elsewhere.
+    fn route_prefix(&self, path: &Path) -> Option<String> {
+        let routes_dir = self.root.join(self.config.routes_from_dirs.as_ref()?);
+        let relative = path.strip_prefix(routes_dir).ok()?.with_extension("");
+        let mut segments: Vec<String> = relative
+            .iter()
+            .map(|segment| {
+                let segment = segment.to_string_lossy();
+                match segment.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                    Some(param) => format!("{{{param}}}"),
+                    None => segment.into_owned(),
+                }
+            })
+            .collect();
+        if segments
+            .last()
+            .is_some_and(|last| last == "index" || last == "_index")
+        {
+            segments.pop();
+        }
+        Some(format!("/{}", segments.join("/")))
+    }
+
+    /// Records a problem once, however often the type is expanded.
+    fn error(&mut self, error: String) {
+        if !self.errors.contains(&error) {
+            self.errors.push(error);
+        }
+    }
+
+    /// Defaults from `ssg.defaults.toml` in `dir` and its ancestors up to the
+    /// scan root, nearer files overriding farther ones.
+    fn directory_defaults(&mut self, dir: &Path) -> Defaults {
+        if let Some(defaults) = self.directory_defaults.get(dir) {
+            return defaults.clone();
+        }
+        let mut defaults = match dir.parent() {
+            Some(parent) if dir != self.root && dir.starts_with(&self.root) => {
+                self.directory_defaults(parent)
+            }
+            _ => Defaults::default(),
+        };
+        if let Some(tags) = defaults::read_directory_file(dir) {
+            let file = dir.join(defaults::DIRECTORY_FILE);
+            defaults.layer(&known_directives(tags, &file), Origin::Directory(file));
+        }
+        self.directory_defaults
+            .insert(dir.to_path_buf(), defaults.clone());
+        defaults
+    }
+}
+
+/// The `// ssg:defaults @tag value ...` blocks ahead of the file's first
+/// statement.
+fn file_directives(source: &Source) -> Vec<(String, String)> {
+    let first_statement = source
+        .program
+        .body
+        .first()
+        .map_or(u32::MAX, |statement| statement.span().start);
+    let mut tags = Vec::new();
+    for comment in &source.program.comments {
+        let text = comment.content_span().source_text(source.text);
+        let Some(rest) = text
+            .trim_start_matches(['*', ' '])
+            .strip_prefix(defaults::FILE_DIRECTIVE)
+        else {
+            continue;
+        };
+        if comment.span.start > first_statement {
+            warning::warn(
+                source.path,
+                format!(
+                    "`{}` only applies at the top of the file",
+                    defaults::FILE_DIRECTIVE
+                ),
+            );
+            continue;
+        }
+        tags.extend(parse_doc(comment_lines(rest)).tags);
+    }
+    known_directives(tags, source.path)
+}
+
+fn is_directive(comment: &str) -> bool {
+    comment
+        .trim_start_matches(['*', ' '])
+        .starts_with(defaults::FILE_DIRECTIVE)
+}
+
+/// Drops (with a warning) defaults that aren't entity tags.
+fn known_directives(tags: Vec<(String, String)>, path: &Path) -> Vec<(String, String)> {
+    tags.into_iter()
+        .filter(|(tag, _)| {
+            let known = ENTITY_TAGS.contains(&tag.as_str());
+            if !known {
+                warning::warn(path, format!("unknown default `@{tag}`"));
+            }
+            known
+        })
+        .collect()
+}
+
+/// A parsed file, for looking up the declarations its types refer to.
+struct Source<'s, 'a> {
+    path: &'s Path,
+    text: &'s str,
+    program: &'s Program<'a>,
+}
+
+impl<'s, 'a> Source<'s, 'a> {
+    /// A type declared (or exported) at the top level of the file.
+    fn declaration(&self, name: &str) -> Option<&'s Declaration<'a>> {
+        self.program.body.iter().find_map(|statement| {
+            let declaration = top_level_declaration(statement)?;
+            let declared = match declaration {
+                Declaration::TSInterfaceDeclaration(interface) => &interface.id.name,
+                Declaration::TSEnumDeclaration(declaration) => &declaration.id.name,
+                Declaration::TSTypeAliasDeclaration(alias) => &alias.id.name,
+                Declaration::ClassDeclaration(class) => &class.id.as_ref()?.name,
+                _ => return None,
+            };
+            (declared == name).then_some(declaration)
+        })
+    }
+
+    /// The literal a top-level `const` is initialized with, looking through
+    /// `as const` and `satisfies`.
+    fn constant(&self, name: &str) -> Option<Value> {
+        self.program.body.iter().find_map(|statement| {
+            let Declaration::VariableDeclaration(variable) = top_level_declaration(statement)?
+            else {
+                return None;
+            };
+            if variable.kind != VariableDeclarationKind::Const {
+                return None;
+            }
+            let declarator = variable.declarations.iter().find(|declarator| {
+                matches!(&declarator.id.kind, BindingPatternKind::BindingIdentifier(id) if id.name == name)
+            })?;
+            let mut init = declarator.init.as_ref()?;
+            loop {
+                match init {
+                    Expression::TSSatisfiesExpression(expression) => init = &expression.expression,
+                    Expression::TSAsExpression(expression) => init = &expression.expression,
+                    Expression::ParenthesizedExpression(expression) => {
+                        init = &expression.expression
+                    }
+                    _ => break,
+                }
+            }
+            literal_value(init)
+        })
+    }
+
+    /// The module specifier and exported name behind an imported `local` name.
+    fn import(&self, local: &str) -> Option<(&'s str, &'s str)> {
+        self.program.body.iter().find_map(|statement| {
+            let Statement::ImportDeclaration(import) = statement else {
+                return None;
+            };
+            import
+                .specifiers
+                .iter()
+                .flatten()
+                .find_map(|specifier| match specifier {
+                    ImportDeclarationSpecifier::ImportSpecifier(s) if s.local.name == local => {
+                        Some((import.source.value.as_str(), s.imported.name().as_str()))
+                    }
+                    _ => None,
+                })
+        })
+    }
+}
+
+/// Props of an interface's property signatures or a class's typed,
+/// non-static property declarations.
+fn declaration_props(
+    source: &Source,
+    declaration: &Declaration,
+    scan: &mut ScanContext,
+) -> Vec<Prop> {
+    let owner = match declaration {
+        Declaration::TSInterfaceDeclaration(interface) => Some(interface.id.name.as_str()),
+        Declaration::ClassDeclaration(class) => class.id.as_ref().map(|id| id.name.as_str()),
+        _ => None,
+    };
+    let sidecar_props = owner
+        .and_then(|owner| scan.sidecar(source.path).remove(owner))
+        .map(|annotations| annotations.props)
+        .unwrap_or_default();
+    let props: Vec<Prop> = match declaration {
+        Declaration::TSInterfaceDeclaration(interface) => interface
+            .body
+            .body
+            .iter()
+            .filter_map(|signature| match signature {
+                TSSignature::TSPropertySignature(prop) => Some(prop),
+                _ => None,
+            })
+            .filter_map(|prop| {
+                let name = prop.key.name()?;
+                build_prop(
+                    source,
+                    name.as_ref(),
+                    &prop.type_annotation.as_ref()?.type_annotation,
+                    prop.optional,
+                    &[prop.span.start],
+                    sidecar_props.get(name.as_ref()).map(String::as_str),
+                    scan,
+                )
+            })
+            .collect(),
+        Declaration::ClassDeclaration(class) => class
+            .body
+            .body
+            .iter()
+            .filter_map(|element| match element {
+                ClassElement::PropertyDefinition(prop) if !prop.r#static && !prop.computed => {
+                    Some(prop)
+                }
+                _ => None,
+            })
+            .filter_map(|prop| {
+                let starts: Vec<u32> = std::iter::once(prop.span.start)
+                    .chain(prop.decorators.iter().map(|d| d.span.start))
+                    .collect();
+                let name = prop.key.static_name()?;
+                build_prop(
+                    source,
+                    name.as_ref(),
+                    &prop.type_annotation.as_ref()?.type_annotation,
+                    prop.optional,
+                    &starts,
+                    sidecar_props.get(name.as_ref()).map(String::as_str),
+                    scan,
+                )
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    for name in sidecar_props.keys() {
+        if !props.iter().any(|prop| &prop.id == name) {
+            warning::warn(
+                source.path,
+                format!(
+                    "`{}` has no prop `{name}` to annotate",
+                    owner.unwrap_or_default()
+                ),
+            );
+        }
+    }
+    props
+}
+
+/// A prop from its name and type, documented by the comments attached at
+/// any of `starts` or by its sidecar annotation. Types that don't map to a
+/// `TProp` are skipped.
+fn build_prop(
+    source: &Source,
+    name: &str,
+    ty: &TSType,
+    optional: bool,
+    starts: &[u32],
+    sidecar_doc: Option<&str>,
+    scan: &mut ScanContext,
+) -> Option<Prop> {
+    let alias = match ty {
+        TSType::TSTypeReference(reference) => match &reference.type_name {
+            TSTypeName::IdentifierReference(id) => Some(id.name.as_str()),
+            TSTypeName::QualifiedName(_) => None,
+        },
+        _ => None,
+    };
+    let Some(ty) = prop_type(source, ty, scan) else {
+        if matches!(
+            unparenthesized(ty),
+            TSType::TSTypeOperatorType(_) | TSType::TSTypeQuery(_) | TSType::TSIndexedAccessType(_)
+        ) {
+            let span = ty.span();
+            warning::warn(
+                source.path,
+                format!(
+                    "line {}: can't resolve `{}` for prop `{name}`, skipping it",
+                    source.text[..span.start as usize].matches('\n').count() + 1,
+                    span.source_text(source.text)
+                ),
+            );
+        }
+        return None;
+    };
+    let comments: Vec<&str> = leading_comments(&source.program.comments, starts)
+        .map(|c| c.content_span().source_text(source.text))
+        .collect();
+    if sidecar_doc.is_some()
+        && !parse_doc(comments.iter().flat_map(|c| comment_lines(c)))
+            .tags
+            .is_empty()
+    {
+        scan.error(format!(
+            "{}: prop `{name}` is annotated both in source and in a sidecar",
+            source.path.display()
+        ));
+    }
+    let doc = parse_doc(
+        comments
+            .into_iter()
+            .chain(sidecar_doc)
+            .flat_map(comment_lines),
+    );
+    let mut prop = Prop {
+        id: name.to_string(),
+        ty,
+        optional,
+        description: doc.description(),
+        ..Default::default()
+    };
+    for (tag, value) in &doc.tags {
+        apply_prop_tag(&mut prop, tag, value, source.path);
+    }
+    if let Some(alias) = alias {
+        apply_alias_hint(&mut prop, alias);
+    }
+    apply_name_hint(&mut prop);
+    Some(prop)
+}
+
+fn prop_type(source: &Source, ty: &TSType, scan: &mut ScanContext) -> Option<TProp> {
+    match ty {
+        TSType::TSBooleanKeyword(_) => Some(TProp::Boolean),
+        TSType::TSNumberKeyword(_) => Some(TProp::Number),
+        TSType::TSBigIntKeyword(_) => Some(TProp::BigInt),
+        TSType::TSStringKeyword(_) => Some(TProp::String),
+        TSType::TSIntersectionType(intersection) => branded_type(source, intersection, scan),
+        TSType::TSArrayType(array) => {
+            prop_type(source, &array.element_type, scan).map(|item| TProp::Array(Box::new(item)))
+        }
+        TSType::TSTypeReference(reference) => match &reference.type_name {
+            TSTypeName::IdentifierReference(id) if id.name == "Date" => Some(TProp::Date),
+            TSTypeName::IdentifierReference(id) if id.name == "Array" => {
+                let item = reference.type_parameters.as_ref()?.params.first()?;
+                prop_type(source, item, scan).map(|item| TProp::Array(Box::new(item)))
+            }
+            TSTypeName::IdentifierReference(id) => referenced_type(source, &id.name, scan),
+            TSTypeName::QualifiedName(_) => None,
+        },
+        TSType::TSParenthesizedType(ty) => prop_type(source, &ty.type_annotation, scan),
+        TSType::TSTypeOperatorType(operator) => match operator.operator {
+            TSTypeOperatorOperator::Keyof => type_keys(source, &operator.type_annotation)
+                .map(|keys| TProp::Enum(keys.into_iter().map(Value::String).collect())),
+            TSTypeOperatorOperator::Readonly => prop_type(source, &operator.type_annotation, scan),
+            TSTypeOperatorOperator::Unique => None,
+        },
+        TSType::TSTypeQuery(query) => {
+            queried_literal(source, query).map(|(name, value)| literal_type(&name, &value))
+        }
+        TSType::TSIndexedAccessType(access) => indexed_type(source, access),
+        _ => None,
+    }
+}
+
+fn unparenthesized<'t, 'a>(mut ty: &'t TSType<'a>) -> &'t TSType<'a> {
+    while let TSType::TSParenthesizedType(inner) = ty {
+        ty = &inner.type_annotation;
+    }
+    ty
+}
+
+/// The keys `keyof` gives: the props of an interface or type literal
+/// declared in this file, or the fields of a `typeof` const object.
+fn type_keys(source: &Source, ty: &TSType) -> Option<Vec<String>> {
+    let signature_keys = |members: &[TSSignature]| {
+        members
+            .iter()
+            .filter_map(|member| match member {
+                TSSignature::TSPropertySignature(prop) => Some(prop.key.name()?.into_owned()),
+                _ => None,
+            })
+            .collect()
+    };
+    match unparenthesized(ty) {
+        TSType::TSTypeLiteral(literal) => Some(signature_keys(&literal.members)),
+        TSType::TSTypeQuery(query) => match queried_literal(source, query)?.1 {
+            Value::Object(fields) => Some(fields.keys().cloned().collect()),
+            _ => None,
+        },
+        TSType::TSTypeReference(reference) => match &reference.type_name {
+            TSTypeName::IdentifierReference(id) => match source.declaration(&id.name)? {
+                Declaration::TSInterfaceDeclaration(interface) => {
+                    Some(signature_keys(&interface.body.body))
+                }
+                Declaration::TSTypeAliasDeclaration(alias) => {
+                    type_keys(source, &alias.type_annotation)
+                }
+                _ => None,
+            },
+            TSTypeName::QualifiedName(_) => None,
+        },
+        _ => None,
+    }
+}
+
+/// The name and literal of the `const` a `typeof NAME` refers to.
+fn queried_literal(source: &Source, query: &TSTypeQuery) -> Option<(String, Value)> {
+    let TSTypeQueryExprName::IdentifierReference(id) = &query.expr_name else {
+        return None;
+    };
+    Some((id.name.to_string(), source.constant(&id.name)?))
+}
+
+/// The type `typeof` gives a literal, narrowed as `as const` would: each
+/// scalar can only be itself, and an array's items any of its elements.
+fn literal_type(name: &str, value: &Value) -> TProp {
+    match value {
+        Value::Object(fields) => TProp::Object {
+            name: name.to_string(),
+            props: fields
+                .iter()
+                .map(|(id, value)| Prop {
+                    id: id.clone(),
+                    ty: literal_type(id, value),
+                    ..Default::default()
+                })
+                .collect(),
+        },
+        Value::Array(items) => TProp::Array(Box::new(match literal_values(items) {
+            Some(values) => TProp::Enum(values),
+            None => literal_type(name, &items[0]),
+        })),
+        scalar => TProp::Enum(vec![scalar.clone()]),
+    }
+}
+
+/// `values` without repeats, when they're all scalars.
+fn literal_values(values: &[Value]) -> Option<Vec<Value>> {
+    let mut distinct = Vec::new();
+    for value in values {
+        if value.is_object() || value.is_array() {
+            return None;
+        }
+        if !distinct.contains(value) {
+            distinct.push(value.clone());
+        }
+    }
+    Some(distinct)
+}
+
+/// `(typeof ROLES)[number]` is any element of a const array, and
+/// `(typeof ROLES)[keyof typeof ROLES]` any value of a const object.
+fn indexed_type(source: &Source, access: &TSIndexedAccessType) -> Option<TProp> {
+    let TSType::TSTypeQuery(query) = unparenthesized(&access.object_type) else {
+        return None;
+    };
+    let (_, literal) = queried_literal(source, query)?;
+    let values: Vec<Value> = match (literal, unparenthesized(&access.index_type)) {
+        (Value::Array(items), TSType::TSNumberKeyword(_)) => items,
+        (Value::Object(fields), TSType::TSTypeOperatorType(operator))
+            if operator.operator == TSTypeOperatorOperator::Keyof =>
+        {
+            fields.into_iter().map(|(_, value)| value).collect()
+        }
+        _ => return None,
+    };
+    literal_values(&values).map(TProp::Enum)
+}
+
+/// Props from `@compose user:User stats:Stats notes?:Note[]`: each names a
+/// type to generate under that key, resolved like any other reference.
+fn compose_props(source: &Source, owner: &str, value: &str, scan: &mut ScanContext) -> Vec<Prop> {
+    value
+        .split_whitespace()
+        .filter_map(|part| {
+            let Some((key, type_name)) = part.split_once(':') else {
+                warning::warn(
+                    source.path,
+                    format!("@compose on `{owner}` expects `key:Type`, got `{part}`"),
+                );
+                return None;
+            };
+            let (id, optional) = match key.strip_suffix('?') {
+                Some(id) => (id, true),
+                None => (key, false),
+            };
+            let (element, array) = match type_name.strip_suffix("[]") {
+                Some(element) => (element, true),
+                None => (type_name, false),
+            };
+            let Some(ty) = referenced_type(source, element, scan) else {
+                warning::warn(
+                    source.path,
+                    format!("@compose on `{owner}` cannot resolve `{element}`"),
+                );
+                return None;
+            };
+            Some(Prop {
+                id: id.to_string(),
+                ty: if array {
+                    TProp::Array(Box::new(ty))
+                } else {
+                    ty
+                },
+                optional,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Resolves a reference to an interface or enum declared in this file or
+/// imported from another one.
+fn referenced_type(source: &Source, name: &str, scan: &mut ScanContext) -> Option<TProp> {
+    if let Some(declaration) = source.declaration(name) {
+        return declared_type(source, declaration, scan);
+    }
+
+    let (specifier, imported) = source.import(name)?;
+    let path = scan.resolver.resolve(source.path, specifier)?;
+    let source_text = read_source(&path, scan.config).ok()??;
+    scan.stats.imports += 1;
+    let allocator = Allocator::default();
+    let source_type = SourceType::from_path(&path).unwrap_or_default();
+    let ret = Parser::new(&allocator, &source_text, source_type).parse();
+    let imported_source = Source {
+        path: &path,
+        text: &source_text,
+        program: &ret.program,
+    };
+    match imported_source.declaration(imported) {
+        Some(declaration) => declared_type(&imported_source, declaration, scan),
+        None => {
+            warning::warn(
+                source.path,
+                format!(
+                    "`{imported}` imported from `{specifier}` is not a type ssg understands in {}",
+                    path.display()
+                ),
+            );
+            None
+        }
+    }
+}
+
+/// Interfaces and classes become nested objects, enums their set of member
+/// values, and aliases whatever they stand for.
+fn declared_type(
+    source: &Source,
+    declaration: &Declaration,
+    scan: &mut ScanContext,
+) -> Option<TProp> {
+    match declaration {
+        Declaration::TSInterfaceDeclaration(interface) => {
+            nested_object(source, &interface.id.name, declaration, scan)
+        }
+        Declaration::ClassDeclaration(class) => {
+            nested_object(source, &class.id.as_ref()?.name, declaration, scan)
+        }
+        Declaration::TSEnumDeclaration(declaration) => {
+            Some(TProp::Enum(enum_values(source, declaration)))
+        }
+        Declaration::TSTypeAliasDeclaration(alias) => {
+            let ty = prop_type(source, &alias.type_annotation, scan);
+            if ty.is_none() && matches!(alias.type_annotation, TSType::TSIntersectionType(_)) {
+                let name = alias.id.name.to_string();
+                if scan
+                    .warned_aliases
+                    .insert((source.path.to_path_buf(), name.clone()))
+                {
+                    warning::warn(
+                        source.path,
+                        format!("`{name}` isn't a branded primitive, skipping props of that type"),
+                    );
+                }
+            }
+            ty
+        }
+        _ => None,
+    }
+}
+
+/// A branded primitive such as `number & { __brand: "UserId" }` is its
+/// primitive: object-literal members only tag it for the type checker.
+fn branded_type(
+    source: &Source,
+    intersection: &TSIntersectionType,
+    scan: &mut ScanContext,
+) -> Option<TProp> {
+    let mut primitives = intersection
+        .types
+        .iter()
+        .filter(|ty| !matches!(ty, TSType::TSTypeLiteral(_)));
+    let primitive = primitives.next()?;
+    if primitives.next().is_some() {
+        return None;
+    }
+    match prop_type(source, primitive, scan)? {
+        ty @ (TProp::Boolean | TProp::Number | TProp::BigInt | TProp::String) => Some(ty),
+        _ => None,
+    }
+}
+
+/// What a number prop's own name says about it, when no format tag did:
+/// `price` has cents, `latitude` stays on the globe. Explicit `@min`/`@max`
+/// win over the implied range.
+fn apply_name_hint(prop: &mut Prop) {
+    if prop.number_format.is_some() || !matches!(item_type(&prop.ty), TProp::Number) {
+        return;
+    }
+    if let Some((format, min, max)) = NumberFormat::from_name(&prop.id) {
+        prop.number_format = Some(format);
+        prop.min.get_or_insert(min);
+        prop.max.get_or_insert(max);
+    }
+}
+
+/// The type of an array's innermost items, or `ty` itself.
+fn item_type(mut ty: &TProp) -> &TProp {
+    while let TProp::Array(item) = ty {
+        ty = item;
+    }
+    ty
+}
+
+/// What an alias's name says about its values, for props without tags of
+/// their own: `Email` strings are emails, `UserId` numbers positive.
+fn apply_alias_hint(prop: &mut Prop, alias: &str) {
+    match prop.ty {
+        TProp::String if prop.faker.is_none() => prop.faker = FakerKind::parse(alias),
+        TProp::Number | TProp::BigInt
+            if prop.min.is_none() && (alias.ends_with("Id") || alias.ends_with("ID")) =>
+        {
+            prop.min = Some(1.0)
+        }
+        _ => (),
+    }
+}
+
+/// A routed `const` serves its literal as is. A `satisfies` type gives the
+/// entity its props and is checked against the literal, warning on
+/// mismatches without refusing to serve it.
+fn const_data(
+    source: &Source,
+    variable: &VariableDeclaration,
+    entity: &mut Entity,
+    scan: &mut ScanContext,
+) -> Option<Vec<Prop>> {
+    let mut init = variable.declarations.first()?.init.as_ref()?;
+    let mut satisfies = None;
+    loop {
+        match init {
+            Expression::TSSatisfiesExpression(expression) => {
+                satisfies = Some(&expression.type_annotation);
+                init = &expression.expression;
+            }
+            Expression::TSAsExpression(expression) => init = &expression.expression,
+            Expression::ParenthesizedExpression(expression) => init = &expression.expression,
+            _ => break,
+        }
+    }
+    let data = literal_value(init)?;
+    let props = match satisfies.and_then(|ty| prop_type(source, ty, scan)) {
+        Some(TProp::Object { props, .. }) => props,
+        Some(TProp::Array(item)) => match *item {
+            TProp::Object { props, .. } => props,
+            _ => Vec::new(),
+        },
+        _ => Vec::new(),
+    };
+
+    let shape = Entity {
+        props: props.clone(),
+        ..Default::default()
+    };
+    let items = match &data {
+        Value::Array(items) => items.iter().collect(),
+        data => vec![data],
+    };
+    for item in items {
+        let missing = props
+            .iter()
+            .filter(|prop| !prop.optional && item.get(&prop.id).is_none());
+        for prop in missing {
+            warning::warn(
+                source.path,
+                format!(
+                    "`{}` doesn't satisfy its type: `{}` is missing",
+                    entity.name, prop.id
+                ),
+            );
+        }
+        for failure in validation::check(&shape, item, scan.config, validation::Body::Partial) {
+            warning::warn(
+                source.path,
+                format!(
+                    "`{}` doesn't satisfy its type: `{}` should be {}, got {}",
+                    entity.name, failure.field, failure.expected, failure.got
+                ),
+            );
+        }
+    }
+    entity.list = data.is_array();
+    entity.data = Some(data);
+    Some(props)
+}
+
+/// A JSON value from a literal expression; `None` when any part of it
+/// needs evaluating.
+fn literal_value(expression: &Expression) -> Option<Value> {
+    let number = |n: f64| {
+        if n.fract() == 0.0 && n.abs() < 2f64.powi(53) {
+            json!(n as i64)
+        } else {
+            json!(n)
+        }
+    };
+    let value = match expression {
+        Expression::NullLiteral(_) => Value::Null,
+        Expression::BooleanLiteral(b) => json!(b.value),
+        Expression::StringLiteral(s) => json!(s.value.as_str()),
+        Expression::NumericLiteral(n) => number(n.value),
+        Expression::UnaryExpression(unary) if unary.operator == UnaryOperator::UnaryNegation => {
+            match &unary.argument {
+                Expression::NumericLiteral(n) => number(-n.value),
+                _ => return None,
+            }
+        }
+        Expression::TemplateLiteral(template) if template.expressions.is_empty() => {
+            json!(template.quasis.first()?.value.cooked?.as_str())
+        }
+        Expression::ArrayExpression(array) => Value::Array(
+            array
+                .elements
+                .iter()
+                .map(|element| literal_value(element.as_expression()?))
+                .collect::<Option<_>>()?,
+        ),
+        Expression::ObjectExpression(object) => {
+            let mut fields = serde_json::Map::new();
+            for property in &object.properties {
+                let ObjectPropertyKind::ObjectProperty(property) = property else {
+                    return None;
+                };
+                if property.computed {
+                    return None;
+                }
+                fields.insert(
+                    property.key.static_name()?.into_owned(),
+                    literal_value(&property.value)?,
+                );
+            }
+            Value::Object(fields)
+        }
+        Expression::TSAsExpression(expression) => literal_value(&expression.expression)?,
+        Expression::TSSatisfiesExpression(expression) => literal_value(&expression.expression)?,
+        Expression::ParenthesizedExpression(expression) => literal_value(&expression.expression)?,
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Member values as TypeScript assigns them: string initializers as given,
+/// numeric members counting up from the previous one (or zero).
+fn enum_values(source: &Source, declaration: &TSEnumDeclaration) -> Vec<Value> {
+    let mut values = Vec::new();
+    let mut next = 0.0;
+    for member in &declaration.members {
+        let value = match &member.initializer {
+            None => Some(next),
+            Some(Expression::StringLiteral(s)) => {
+                values.push(Value::String(s.value.to_string()));
+                continue;
+            }
+            Some(Expression::NumericLiteral(n)) => Some(n.value),
+            Some(Expression::UnaryExpression(unary))
+                if unary.operator == UnaryOperator::UnaryNegation =>
+            {
+                match &unary.argument {
+                    Expression::NumericLiteral(n) => Some(-n.value),
+                    _ => None,
+                }
+            }
+            Some(_) => None,
+        };
+        match value {
+            Some(n) => {
+                values.push(if n.fract() == 0.0 {
+                    json!(n as i64)
+                } else {
+                    json!(n)
+                });
+                next = n + 1.0;
+            }
+            None => warning::warn(
+                source.path,
+                format!(
+                    "unsupported initializer for `{}.{}`, skipping the member",
+                    declaration.id.name,
+                    member.id.static_name()
+                ),
+            ),
+        }
+    }
+    values
+}
+
+fn nested_object(
+    source: &Source,
+    name: &str,
+    declaration: &Declaration,
+    scan: &mut ScanContext,
+) -> Option<TProp> {
+    let key = (source.path.to_path_buf(), name.to_string());
+    if scan.stack.contains(&key) {
+        warning::warn(
+            source.path,
+            format!("`{}` refers to itself, skipping the recursive prop", key.1),
+        );
+        return None;
+    }
+    scan.stack.push(key);
+    let props = declaration_props(source, declaration, scan);
+    scan.stack.pop();
+    Some(TProp::Object {
+        name: name.to_string(),
+        props,
+    })
+}
+
+/// Whether a comment's first word marks a route, ignoring case, a leading
+/// `@` and a trailing `:` (`route`, `@Route`, `endpoint:`).
+fn is_route_keyword(word: &str, keywords: &[String]) -> bool {
+    let word = word.trim_start_matches('@').trim_end_matches(':');
+    keywords.iter().any(|k| k.eq_ignore_ascii_case(word))
+}
+
+/// The words after a route keyword when `line` starts with one, the path
+/// first. The keyword's colon may stand apart (`route : /users`) or run
+/// into the path (`route:/users`).
+fn route_words<'a>(line: &'a str, keywords: &[String]) -> Option<Vec<&'a str>> {
+    let mut words = line.split_whitespace();
+    let first = words.next()?;
+    let mut rest: Vec<&str> = match first.split_once(':') {
+        Some((keyword, path)) if !path.is_empty() && is_route_keyword(keyword, keywords) => {
+            std::iter::once(path).chain(words).collect()
+        }
+        _ if is_route_keyword(first, keywords) => words.collect(),
+        _ => return None,
+    };
+    if rest.first() == Some(&":") {
+        rest.remove(0);
+    }
+    Some(rest)
+}
+
+/// Characters a route may hold besides letters and digits: those a URL
+/// path allows, and the braces of `{param}`s.
+const ROUTE_PUNCTUATION: &str = "/-._~!$&'()*+,;=:@%{}";
+
+/// `route` as it's served, with the fixes made to it: a missing leading
+/// `/` added, repeated `/`s collapsed and a trailing `/` dropped. Paths
+/// with characters a URL path can't hold, or malformed `{param}`s, are
+/// refused.
+fn normalize_route(route: &str) -> Result<(String, Vec<&'static str>), String> {
+    if let Some(c) = route
+        .chars()
+        .find(|c| !c.is_ascii_alphanumeric() && !ROUTE_PUNCTUATION.contains(*c))
+    {
+        return Err(format!("`{c}` isn't allowed in a route"));
+    }
+    let mut params: Vec<&str> = Vec::new();
+    let mut rest = route;
+    while let Some(open) = rest.find(['{', '}']) {
+        if rest[open..].starts_with('}') {
+            return Err("`}` without a `{`".to_string());
+        }
+        let Some(close) = rest[open + 1..].find(['{', '}']).map(|i| open + 1 + i) else {
+            return Err("`{` without a `}`".to_string());
+        };
+        if rest[close..].starts_with('{') {
+            return Err("`{` inside a `{param}`".to_string());
+        }
+        let param = &rest[open + 1..close];
+        if param.is_empty() {
+            return Err("`{}` needs a param name".to_string());
+        }
+        if !param.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(format!(
+                "param `{{{param}}}` can only hold letters, digits and `_`"
+            ));
+        }
+        if params.contains(&param) {
+            return Err(format!("param `{{{param}}}` appears twice"));
+        }
+        params.push(param);
+        rest = &rest[close + 1..];
+    }
+
+    let mut fixes = Vec::new();
+    if !route.starts_with('/') {
+        fixes.push("added the leading `/`");
+    }
+    let segments: Vec<&str> = route.split('/').filter(|s| !s.is_empty()).collect();
+    if route.trim_matches('/').contains("//") {
+        fixes.push("collapsed repeated `/`");
+    }
+    if route.len() > 1 && route.ends_with('/') {
+        fixes.push("dropped the trailing `/`");
+    }
+    Ok((format!("/{}", segments.join("/")), fixes))
+}
+
+/// Comments attached to any of `starts`: a declaration and its decorators.
+fn leading_comments<'c>(
+    comments: &'c [Comment],
+    starts: &'c [u32],
+) -> impl Iterator<Item = &'c Comment> {
+    comments.iter().filter(|c| starts.contains(&c.attached_to))
+}
+
+/// Lines of a comment's content with JSDoc `*` gutters stripped.
+fn comment_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(|line| line.trim().trim_start_matches('*').trim())
+        .filter(|line| !line.is_empty())
+}
+
+/// A doc comment split into free text and `@tag value` pairs.
+#[derive(Debug, Default)]
+struct Doc {
+    prose: Vec<String>,
+    tags: Vec<(String, String)>,
+}
+
+impl Doc {
+    /// The free text, followed by any explicit `@description`.
+    fn description(&self) -> Option<String> {
+        let explicit = self
+            .tags
+            .iter()
+            .filter(|(tag, _)| tag == "description")
+            .map(|(_, value)| value.clone());
+        let text = self
+            .prose
+            .iter()
+            .cloned()
+            .chain(explicit)
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        (!text.is_empty()).then_some(text)
+    }
+}
+
+/// A tag's value runs until the next tag or the end of its line; words that
+/// don't belong to a tag are the comment's prose.
+fn parse_doc<'a>(lines: impl Iterator<Item = &'a str>) -> Doc {
+    let mut doc = Doc::default();
+
+    for line in lines {
+        let mut in_tag = false;
+        let mut prose = Vec::new();
+        for word in line.split_whitespace() {
+            if let Some(tag) = word.strip_prefix('@').filter(|t| !t.is_empty()) {
+                doc.tags.push((tag.to_string(), String::new()));
+                in_tag = true;
+            } else if in_tag {
+                let (_, value) = doc.tags.last_mut().unwrap();
+                if !value.is_empty() {
+                    value.push(' ');
+                }
+                value.push_str(word);
+            } else {
+                prose.push(word);
+            }
+        }
+        if !prose.is_empty() {
+            doc.prose.push(prose.join(" "));
+        }
+    }
+    doc
+}
+
+/// Handles `key=value` words following the path on the route line.
+fn apply_route_option(entity: &mut Entity, option: &str, path: &Path) {
+    match option.split_once('=') {
+        Some(("version", v)) => match v.trim_start_matches('v').parse() {
+            Ok(version) => entity.version = Some(version),
+            Err(_) => warning::warn(path, format!("invalid version `{v}` on `{}`", entity.name)),
+        },
+        _ => warning::warn(
+            path,
+            format!("unknown route option `{option}` on `{}`", entity.name),
+        ),
+    }
+}
+
+/// Tags that configure an entity, and so can also come from defaults.
+const ENTITY_TAGS: [&str; 12] = [
+    "bandwidth",
+    "tag",
+    "operationId",
+    "content-type",
+    "body-from",
+    "soft-delete",
+    "format",
+    "mediaType",
+    "concurrency",
+    "auth",
+    "role",
+    "consistency-lag",
+];
+
+fn apply_entity_tag(entity: &mut Entity, tag: &str, value: &str, path: &Path) {
+    match tag {
+        "bandwidth" => match throttle::parse_bandwidth(value) {
+            Ok(rate) => entity.bandwidth = Some(rate),
+            Err(error) => warning::warn(path, format!("@bandwidth on `{}`: {error}", entity.name)),
+        },
+        "tag" => entity
+            .tags
+            .extend(value.split_whitespace().map(String::from)),
+        "operationId" if !value.is_empty() => entity.operation_id = Some(value.to_string()),
+        "content-type" if !value.is_empty() => entity.content_type = Some(value.to_string()),
+        "body-from" if !value.is_empty() => entity.body_from = Some(value.to_string()),
+        "soft-delete" => entity.soft_delete = true,
+        "concurrency" => match Concurrency::parse(value) {
+            Some(concurrency) => entity.concurrency = concurrency,
+            None => warning::warn(
+                path,
+                format!(
+                    "@concurrency on `{}` expects etag, version or off, got `{value}`",
+                    entity.name
+                ),
+            ),
+        },
+        "format" | "mediaType" if !value.is_empty() => {
+            entity.format = Some(serializer::format_media_type(value))
+        }
+        "auth" => match Auth::parse(value) {
+            Ok(auth) => entity.auth = Some(auth),
+            Err(error) => warning::warn(path, format!("@auth on `{}`: {error}", entity.name)),
+        },
+        "consistency-lag" => match cli::parse_duration(value) {
+            Ok(lag) => entity.consistency_lag = Some(lag),
+            Err(error) => warning::warn(
+                path,
+                format!("@consistency-lag on `{}`: {error}", entity.name),
+            ),
+        },
+        // A role needs a credential to hold it, so it implies bearer auth.
+        "role" => {
+            entity.roles.extend(
+                value
+                    .split([',', ' '])
+                    .filter(|role| !role.is_empty())
+                    .map(String::from),
+            );
+            entity.auth.get_or_insert(Auth::Bearer);
+        }
+        _ => (),
+    }
+}
+
+/// A non-JSON `@content-type` sends one scalar prop, named by `@body-from`,
+/// as the body; JSON-only shapes like list routes can't be sent that way.
+fn check_content_type(entity: &Entity) -> Result<(), String> {
+    if let (Some(content_type), Some(format)) = (&entity.content_type, &entity.format) {
+        return Err(format!(
+            "`{}` has both @content-type {content_type} and @format {format}",
+            entity.name
+        ));
+    }
+    let json = entity.content_type.as_deref().is_none_or(format::is_json);
+    let Some(body_from) = &entity.body_from else {
+        return match &entity.content_type {
+            Some(content_type) if !json => Err(format!(
+                "`{}` has @content-type {content_type} but no @body-from prop",
+                entity.name
+            )),
+            _ => Ok(()),
+        };
+    };
+    if json {
+        return Err(format!(
+            "@body-from on `{}` needs a non-JSON @content-type",
+            entity.name
+        ));
+    }
+    if entity.list {
+        return Err(format!(
+            "list route `{}` can only be served as JSON, not {}",
+            entity.name,
+            entity.content_type.as_deref().unwrap_or_default()
+        ));
+    }
+    match entity.props.iter().find(|prop| &prop.id == body_from) {
+        None => Err(format!(
+            "@body-from on `{}` names no prop `{body_from}`",
+            entity.name
+        )),
+        Some(prop) if matches!(prop.ty, TProp::Object { .. } | TProp::Array(_)) => Err(format!(
+            "@body-from on `{}` needs a string, number or boolean prop, `{body_from}` isn't one",
+            entity.name
+        )),
+        Some(_) => Ok(()),
+    }
+}
+
+/// Items have one key, and it has to be something a path segment can name.
+/// The detail route names that segment after the key, so the key can't
+/// share its name with a param of the route.
+fn check_key(entity: &Entity) -> Result<(), String> {
+    let keys: Vec<&Prop> = entity.props.iter().filter(|prop| prop.key).collect();
+    match keys.as_slice() {
+        [] => Ok(()),
+        [prop]
+            if entity
+                .route
+                .split(['{', '}'])
+                .skip(1)
+                .step_by(2)
+                .any(|param| param == prop.id) =>
+        {
+            Err(format!(
+                "@key `{}` on `{}` has the name of a param of its route `{}`",
+                prop.id, entity.name, entity.route
+            ))
+        }
+        [prop] => match prop.ty {
+            TProp::String | TProp::Number | TProp::BigInt | TProp::Date | TProp::Enum(_)
+                if !prop.optional =>
+            {
+                Ok(())
+            }
+            _ => Err(format!(
+                "@key `{}` on `{}` must be a required string, number, bigint, date or enum",
+                prop.id, entity.name
+            )),
+        },
+        keys => Err(format!(
+            "`{}` has more than one @key: {}",
+            entity.name,
+            keys.iter()
+                .map(|prop| format!("`{}`", prop.id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
+    }
+}
+
+/// `@soft-delete` manages `deletedAt` itself; when the interface declares
+/// it, it has to hold a timestamp.
+fn check_soft_delete(entity: &Entity) -> Result<(), String> {
+    if !entity.soft_delete {
+        return Ok(());
+    }
+    match entity
+        .props
+        .iter()
+        .find(|prop| prop.id == store::DELETED_AT)
+    {
+        Some(prop) if !matches!(prop.ty, TProp::Date | TProp::String) => Err(format!(
+            "@soft-delete on `{}` needs `{}` to be a Date or a string",
+            entity.name,
+            store::DELETED_AT
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// `@concurrency version` counts updates in `version`; when the interface
+/// declares it, it has to be a number.
+fn check_concurrency(entity: &Entity) -> Result<(), String> {
+    if entity.concurrency != Concurrency::Version {
+        return Ok(());
+    }
+    match entity
+        .props
+        .iter()
+        .find(|prop| prop.id == concurrency::VERSION_PROP)
+    {
+        Some(prop) if !matches!(prop.ty, TProp::Number | TProp::BigInt) => Err(format!(
+            "@concurrency version on `{}` needs `{}` to be a number",
+            entity.name,
+            concurrency::VERSION_PROP
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn apply_prop_tag(prop: &mut Prop, tag: &str, value: &str, path: &Path) {
+    match tag {
+        "faker" => match FakerKind::parse(value) {
+            Some(kind) => prop.faker = Some(kind),
+            None => warning::warn(path, format!("unknown @faker `{value}` on `{}`", prop.id)),
+        },
+        "min" | "max" => match value.parse::<f64>() {
+            Ok(n) if n.is_finite() && tag == "min" => prop.min = Some(n),
+            Ok(n) if n.is_finite() => prop.max = Some(n),
+            _ => warning::warn(
+                path,
+                format!(
+                    "@{tag} on `{}` expects a finite number, got `{value}`",
+                    prop.id
+                ),
+            ),
+        },
+        "asString" => prop.as_string = true,
+        "key" => prop.key = true,
+        "int" => prop.number_format = Some(NumberFormat::Int),
+        "float" => {
+            let precision = value.trim().trim_start_matches("precision=");
+            match precision {
+                "" => prop.number_format = Some(NumberFormat::Float { precision: 2 }),
+                precision => match precision.parse::<u32>() {
+                    Ok(precision) if precision <= 15 => {
+                        prop.number_format = Some(NumberFormat::Float { precision })
+                    }
+                    _ => warning::warn(
+                        path,
+                        format!(
+                            "@float on `{}` expects precision=0..15, got `{value}`",
+                            prop.id
+                        ),
+                    ),
+                },
+            }
+        }
+        "currency" => {
+            let code = match value.trim() {
+                "" => "USD".to_string(),
+                code => code.to_ascii_uppercase(),
+            };
+            prop.number_format = Some(NumberFormat::Currency(code));
+        }
+        "minItems" | "maxItems" => match value.parse::<usize>() {
+            Ok(n) if tag == "minItems" => prop.min_items = Some(n),
+            Ok(n) => prop.max_items = Some(n),
+            Err(_) => warning::warn(
+                path,
+                format!("@{tag} on `{}` expects a count, got `{value}`", prop.id),
+            ),
+        },
+        "emptyRate" => match cli::parse_rate(value) {
+            Ok(rate) => prop.empty_rate = Some(rate),
+            Err(error) => warning::warn(path, format!("@emptyRate on `{}`: {error}", prop.id)),
+        },
+        "minDate" | "maxDate" => match date::parse(value) {
+            Some(date) if tag == "minDate" => prop.min_date = Some(date),
+            Some(date) => prop.max_date = Some(date),
+            None => warning::warn(
+                path,
+                format!(
+                    "@{tag} on `{}` expects a date like 2020-01-01, got `{value}`",
+                    prop.id
+                ),
+            ),
+        },
+        "presentRate" => match cli::parse_rate(value) {
+            Ok(rate) => prop.present_rate = Some(rate),
+            Err(error) => warning::warn(path, format!("@presentRate on `{}`: {error}", prop.id)),
+        },
+        _ => (),
+    }
+}
+
+pub fn scan_dir(
+    dir: &Path,
+    allocator: &Allocator,
+    config: &Config,
+) -> Result<(Vec<Entity>, ScanStats), Box<dyn Error>> {
+    let started = Instant::now();
+    let mut entities = Vec::new();
+    let mut scan = ScanContext::new(config, dir);
+    scan.project_sidecars = sidecar::read_project_file(dir)?
+        .into_iter()
+        .map(|(path, sidecar)| (fs::canonicalize(&path).unwrap_or(path), sidecar))
+        .collect();
+    let mut dirs_to_visit = vec![dir.to_path_buf()];
+    let mut routes = HashMap::new();
+
+    while let Some(current_dir) = dirs_to_visit.pop() {
+        for entry in fs::read_dir(&current_dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs_to_visit.push(path);
+            } else if let Some(ext) = path.extension() {
+                if ext == "ts" || ext == "tsx" {
+                    scan.stats.files += 1;
+                    if let Some(source_text) = read_source(&path, config)? {
+                        scan.stats.parsed += 1;
+                        scan.stats.typescript += 1;
+                        let found =
+                            parse_typescript_file(&path, &source_text, allocator, &mut scan);
+                        warn_duplicate_routes(&mut routes, &path, &found);
+                        entities.extend(found);
+                    }
+                } else if path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.ends_with(".schema.json"))
+                {
+                    scan.stats.files += 1;
+                    if let Some(source_text) = read_source(&path, config)? {
+                        scan.stats.parsed += 1;
+                        let found = jsonschema::parse_json_schema(&path, &source_text);
+                        warn_duplicate_routes(&mut routes, &path, &found);
+                        entities.extend(found);
+                    }
+                }
+            }
+        }
+    }
+    if let Some(examples_dir) = &config.infer {
+        for (route, paths) in infer::examples(&dir.join(examples_dir))? {
+            let mut samples = Vec::new();
+            for path in &paths {
+                scan.stats.files += 1;
+                let Some(source_text) = read_source(path, config)? else {
+                    continue;
+                };
+                match serde_json::from_str(&source_text) {
+                    Ok(sample) => {
+                        scan.stats.parsed += 1;
+                        samples.push(sample);
+                    }
+                    Err(err) => warning::warn(path, format!("invalid JSON: {err}")),
+                }
+            }
+            let found: Vec<Entity> = infer::entity(route, &paths, &samples).into_iter().collect();
+            warn_duplicate_routes(&mut routes, &paths[0], &found);
+            entities.extend(found);
+        }
+    }
+    if !scan.errors.is_empty() {
+        return Err(scan.errors.join("\n").into());
+    }
+    scan.stats.elapsed = started.elapsed();
+    Ok((entities, scan.stats))
+}
+
+/// Warns about entities claiming a route (and version) an earlier one
+/// already serves; only the first would ever answer.
+fn warn_duplicate_routes(
+    routes: &mut HashMap<(String, Option<u32>), PathBuf>,
+    path: &Path,
+    found: &[Entity],
+) {
+    for entity in found {
+        let key = (entity.route.clone(), entity.version);
+        match routes.get(&key) {
+            Some(first) => warning::warn(
+                path,
+                format!(
+                    "`{}` declares route `{}`, already served from {}",
+                    entity.name,
+                    entity.served_route(true),
+                    first.display()
+                ),
+            ),
+            None => {
+                routes.insert(key, path.to_path_buf());
+            }
+        }
+    }
+}
+
+/// Reads a source file, skipping (with a warning) files over `--max-file-size`
+/// and files that aren't UTF-8, unless `--lossy-utf8` allows replacing the
+/// bad bytes.
+fn read_source(path: &Path, config: &Config) -> Result<Option<String>, Box<dyn Error>> {
+    let size = fs::metadata(path)?.len();
+    if size > config.max_file_size as u64 {
+        warning::warn(
+            path,
+            format!(
+                "skipping, {size} bytes exceeds --max-file-size of {}",
+                config.max_file_size
+            ),
+        );
+        return Ok(None);
+    }
+
+    let bytes = fs::read(path)?;
+    match String::from_utf8(bytes) {
+        Ok(source_text) => Ok(Some(source_text)),
+        Err(err) if config.lossy_utf8 => {
+            warning::warn(path, "invalid UTF-8 replaced (--lossy-utf8)");
+            Ok(Some(String::from_utf8_lossy(err.as_bytes()).into_owned()))
+        }
+        Err(err) => {
+            warning::warn(
+                path,
+                format!("skipping, not valid UTF-8 ({})", err.utf8_error()),
+            );
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endless_bounds_warn_and_are_dropped() {
+        let dir = std::env::temp_dir().join(format!("ssg-bounds-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = "// route /items\n\
+                      interface Item {\n\
+                        /** @min -inf @max 10 */ low: number;\n\
+                        /** @min 1 @max 1e400 */ high: number;\n\
+                      }\n";
+        fs::write(dir.join("items.ts"), source).unwrap();
+        let allocator = Allocator::default();
+        let scanned = scan_dir(&dir, &allocator, &Config::default());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (entities, _) = scanned.unwrap();
+        let bounds: Vec<_> = entities[0]
+            .props
+            .iter()
+            .map(|prop| (prop.min, prop.max))
+            .collect();
+        assert_eq!(bounds, [(None, Some(10.0)), (Some(1.0), None)]);
+        let warnings = warning::collected();
+        for warning in [
+            "@min on `low` expects a finite number, got `-inf`",
+            "@max on `high` expects a finite number, got `1e400`",
+        ] {
+            assert!(
+                warnings.iter().any(|w| w.ends_with(warning)),
+                "{warnings:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn a_consistency_lag_too_long_for_a_duration_warns() {
+        let dir = std::env::temp_dir().join(format!("ssg-lag-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = "/**\n * route /notes\n * @consistency-lag 99999999999999999999999h\n */\n\
+                      interface Note { id: number; }\n";
+        fs::write(dir.join("notes.ts"), source).unwrap();
+        let allocator = Allocator::default();
+        let scanned = scan_dir(&dir, &allocator, &Config::default());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (entities, _) = scanned.unwrap();
+        assert_eq!(entities[0].consistency_lag, None);
+        let warning = "@consistency-lag on `Note`: invalid duration `99999999999999999999999h`";
+        assert!(
+            warning::collected().iter().any(|w| w.ends_with(warning)),
+            "{:?}",
+            warning::collected()
+        );
+    }
+}
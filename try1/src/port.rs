@@ -0,0 +1,71 @@
+use std::io;
+
+/// Explains why listening on `host:port` failed and what to try instead.
+pub fn report_bind_failure(err: &io::Error, host: &str, port: u16) {
+    match err.kind() {
+        io::ErrorKind::AddrInUse => {
+            match owner(port) {
+                Some(owner) => eprintln!("error: {host}:{port} is already in use by {owner}"),
+                None => eprintln!("error: {host}:{port} is already in use"),
+            }
+            eprintln!("hint: pass `--port <n>` for another port, or `--port 0` for any free one");
+        }
+        io::ErrorKind::PermissionDenied => {
+            eprintln!("error: not allowed to listen on {host}:{port}");
+            eprintln!("hint: ports below 1024 usually need elevated privileges; try `--port 0`");
+        }
+        _ => eprintln!("error: cannot listen on {host}:{port}: {err}"),
+    }
+}
+
+/// Best effort: the process listening on `port`, as `name (pid n)`. Only
+/// Linux exposes this without extra tools, and only for processes we may
+/// inspect.
+#[cfg(target_os = "linux")]
+fn owner(port: u16) -> Option<String> {
+    use std::fs;
+
+    let inodes: Vec<String> = ["/proc/net/tcp", "/proc/net/tcp6"]
+        .iter()
+        .filter_map(|table| fs::read_to_string(table).ok())
+        .flat_map(|table| {
+            table
+                .lines()
+                .skip(1)
+                .filter_map(|line| {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    let local_port = fields.get(1)?.rsplit(':').next()?;
+                    // State 0A is LISTEN.
+                    (u16::from_str_radix(local_port, 16).ok()? == port && fields.get(3)? == &"0A")
+                        .then(|| fields.get(9).map(|inode| format!("socket:[{inode}]")))
+                        .flatten()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    if inodes.is_empty() {
+        return None;
+    }
+
+    fs::read_dir("/proc").ok()?.flatten().find_map(|process| {
+        let pid = process.file_name().to_str()?.parse::<u32>().ok()?;
+        let owns = fs::read_dir(process.path().join("fd"))
+            .ok()?
+            .flatten()
+            .filter_map(|fd| fs::read_link(fd.path()).ok())
+            .any(|target| {
+                inodes
+                    .iter()
+                    .any(|inode| target.as_os_str() == inode.as_str())
+            });
+        owns.then(|| {
+            let name = fs::read_to_string(process.path().join("comm")).unwrap_or_default();
+            format!("{} (pid {pid})", name.trim())
+        })
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn owner(_port: u16) -> Option<String> {
+    None
+}
@@ -0,0 +1,104 @@
+use std::{fs, path::Path};
+
+use crate::sidecar::PROJECT_FILE;
+
+/// Tables of `ssg.toml` that aren't settings.
+const NOT_SETTINGS: [&str; 2] = ["entities", "profiles"];
+
+/// Subcommands, which stay first when settings are added.
+const COMMANDS: [&str; 6] = ["serve", "snapshot", "routes", "fake", "list", "scan"];
+
+/// `args` with the settings of `root/ssg.toml` in front: its top-level keys,
+/// overlaid key by key with `[profiles.<name>]` when `--profile <name>`
+/// picks one. Each key is a flag without its dashes (`jitter = 500`,
+/// `stateful = true`, `no-admin = true`), `_` standing for `-`; `false`
+/// leaves a flag out, so a profile can turn off one the base sets, and an
+/// array repeats it. Flags given on the command line come later and win.
+pub fn with_file_settings(root: &Path, args: Vec<String>) -> Result<Vec<String>, String> {
+    let profile = args
+        .iter()
+        .position(|arg| arg == "--profile")
+        .and_then(|i| args.get(i + 1))
+        .cloned();
+    let path = root.join(PROJECT_FILE);
+    let Ok(text) = fs::read_to_string(&path) else {
+        return match profile {
+            Some(name) => Err(format!(
+                "`--profile {name}` needs a {PROJECT_FILE} with [profiles.{name}]"
+            )),
+            None => Ok(args),
+        };
+    };
+    let file: toml::Table = text
+        .parse()
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+
+    let mut settings: toml::Table = file
+        .iter()
+        .filter(|(key, _)| !NOT_SETTINGS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    if let Some(name) = &profile {
+        let profiles = file.get("profiles").and_then(toml::Value::as_table);
+        let Some(overlay) = profiles.and_then(|profiles| profiles.get(name)) else {
+            let known: Vec<&str> = profiles
+                .map(|profiles| profiles.keys().map(String::as_str).collect())
+                .unwrap_or_default();
+            return Err(format!(
+                "{}: no [profiles.{name}]; profiles are: {}",
+                path.display(),
+                if known.is_empty() {
+                    "none".to_string()
+                } else {
+                    known.join(", ")
+                }
+            ));
+        };
+        let overlay = overlay
+            .as_table()
+            .ok_or_else(|| format!("{}: `profiles.{name}` must be a table", path.display()))?;
+        settings.extend(
+            overlay
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+    }
+
+    let mut file_args = Vec::new();
+    for (key, value) in &settings {
+        flag_args(
+            &mut file_args,
+            &format!("--{}", key.replace('_', "-")),
+            value,
+        )
+        .map_err(|what| format!("{}: `{key}` {what}", path.display()))?;
+    }
+    let command = args
+        .first()
+        .filter(|arg| COMMANDS.contains(&arg.as_str()))
+        .is_some() as usize;
+    let mut args = args;
+    args.splice(command..command, file_args);
+    Ok(args)
+}
+
+fn flag_args(args: &mut Vec<String>, flag: &str, value: &toml::Value) -> Result<(), &'static str> {
+    match value {
+        toml::Value::Boolean(true) => args.push(flag.to_string()),
+        toml::Value::Boolean(false) => (),
+        toml::Value::String(s) => args.extend([flag.to_string(), s.clone()]),
+        toml::Value::Integer(n) => args.extend([flag.to_string(), n.to_string()]),
+        toml::Value::Float(n) => args.extend([flag.to_string(), n.to_string()]),
+        toml::Value::Datetime(date) => args.extend([flag.to_string(), date.to_string()]),
+        toml::Value::Array(values) => {
+            for value in values {
+                if matches!(value, toml::Value::Array(_) | toml::Value::Table(_)) {
+                    return Err("can only list plain values");
+                }
+                flag_args(args, flag, value)?;
+            }
+        }
+        toml::Value::Table(_) => return Err("must be a value or a list of them, not a table"),
+    }
+    Ok(())
+}
@@ -0,0 +1,637 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use actix_web::{http::header, web, HttpRequest, HttpResponse, HttpResponseBuilder};
+use serde_json::{json, Number, Value};
+
+use crate::{cli::Config, date, generate, Entity, Prop, TProp};
+
+/// Header carrying how many items matched the filters, across all pages.
+pub const TOTAL_COUNT: &str = "X-Total-Count";
+
+/// Header carrying how many pages those items make at `?per_page=`.
+pub const TOTAL_PAGES: &str = "X-Total-Pages";
+
+/// Items per page when `?page=` comes without `?per_page=`.
+pub const DEFAULT_PER_PAGE: usize = 10;
+
+/// Query params the pipeline reads itself; any other param named after a
+/// prop filters on it.
+const RESERVED: [&str; 5] = ["count", "include_deleted", "page", "per_page", "sort"];
+
+/// What a list request asks of the collection: `?prop=value` filters,
+/// `?sort=name,-age` and `?page=`/`?per_page=`, applied in that order,
+/// along with `?count=` and `?include_deleted=`.
+#[derive(Debug, Default)]
+pub struct CollectionQuery {
+    filters: Vec<(String, Value)>,
+    /// Props to sort by, descending when `true`.
+    sort: Vec<(String, bool)>,
+    /// 1-based page and its size.
+    page: Option<(usize, usize)>,
+    /// Items to generate, for a list that isn't `--stateful`.
+    pub count: Option<usize>,
+    /// Whether soft-deleted items are listed too.
+    pub include_deleted: bool,
+}
+
+impl CollectionQuery {
+    /// Reads every param the list pipeline takes, each checked against what
+    /// it expects: filters against their prop's type, `?sort=` against the
+    /// entity's props. All the bad ones come back together.
+    pub fn requested(
+        req: &HttpRequest,
+        entity: &Entity,
+        config: &Config,
+    ) -> Result<Self, BadParams> {
+        let params = params(req);
+        let mut bad = BadParams::default();
+        let mut query = CollectionQuery::default();
+
+        // By name, so bad filters are always listed in the same order.
+        let mut filters: Vec<(&String, &String)> = params
+            .iter()
+            .filter(|(name, _)| !RESERVED.contains(&name.as_str()))
+            .collect();
+        filters.sort();
+        for (name, raw) in filters {
+            if let Some(prop) = entity.props.iter().find(|prop| &prop.id == name) {
+                match coerce(prop, config, raw) {
+                    Ok(value) => query.filters.push((name.clone(), value)),
+                    Err(expected) => bad.push(name, raw, expected),
+                }
+            }
+        }
+
+        if let Some(sort) = params.get("sort") {
+            for field in sort.split(',').filter(|field| !field.is_empty()) {
+                let (name, descending) = match field.strip_prefix('-') {
+                    Some(name) => (name, true),
+                    None => (field, false),
+                };
+                if entity.props.iter().any(|prop| prop.id == name) {
+                    query.sort.push((name.to_string(), descending));
+                } else {
+                    let props: Vec<&str> = entity.props.iter().map(|p| p.id.as_str()).collect();
+                    bad.push(
+                        "sort",
+                        field,
+                        format!(
+                            "props of `{}`, `-` first for descending: {}",
+                            entity.name,
+                            props.join(", ")
+                        ),
+                    );
+                }
+            }
+        }
+
+        let positive = |raw: &str| raw.parse::<usize>().ok().filter(|n| *n > 0);
+        let page = bad.read(&params, "page", "a whole number from 1", positive);
+        let per_page = bad.read(&params, "per_page", "a whole number from 1", positive);
+        if page.is_some() || per_page.is_some() {
+            query.page = Some((page.unwrap_or(1), per_page.unwrap_or(DEFAULT_PER_PAGE)));
+        }
+        query.count = bad.read(&params, "count", "a whole number", |raw| raw.parse().ok());
+        query.include_deleted = bad
+            .read(&params, "include_deleted", "true or false", boolean)
+            .unwrap_or(false);
+        bad.into_result(query)
+    }
+
+    /// Whether the request asked for no filter, order or page, and gets the
+    /// list as is.
+    pub fn is_empty(&self) -> bool {
+        self.filters.is_empty() && self.sort.is_empty() && self.page.is_none()
+    }
+
+    /// Filters, sorts and pages `items`, returning the page and how many
+    /// items passed the filters. Sorting is stable, so items that compare
+    /// equal keep their order and the same query over the same items always
+    /// gives the same page.
+    pub fn apply(&self, items: Vec<Value>) -> (Vec<Value>, usize) {
+        let mut items: Vec<Value> = items
+            .into_iter()
+            .filter(|item| {
+                self.filters
+                    .iter()
+                    .all(|(name, value)| same_value(&item[name], value))
+            })
+            .collect();
+        let total = items.len();
+        if !self.sort.is_empty() {
+            items.sort_by(|a, b| {
+                self.sort
+                    .iter()
+                    .map(|(name, descending)| compare(&a[name], &b[name], *descending))
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        if let Some((page, per_page)) = self.page {
+            items = items
+                .into_iter()
+                .skip((page - 1).saturating_mul(per_page))
+                .take(per_page)
+                .collect();
+        }
+        (items, total)
+    }
+
+    /// Describes the page beside it: `X-Total-Count` and, when paged,
+    /// `X-Total-Pages` and an RFC 8288 `Link` to the first, previous, next
+    /// and last pages. All of it comes from the `total` `apply` returned,
+    /// so it agrees with the page whatever the filters, sort or `?count=`.
+    /// `next` is left out on the last page, `prev` on the first.
+    pub fn describe(&self, response: &mut HttpResponseBuilder, req: &HttpRequest, total: usize) {
+        response.insert_header((TOTAL_COUNT, total));
+        let Some((page, per_page)) = self.page else {
+            return;
+        };
+        // No matches still make one (empty) page, as `last` links to.
+        let last = total.div_ceil(per_page).max(1);
+        let mut links = vec![("first", 1)];
+        if page > 1 {
+            links.push(("prev", (page - 1).min(last)));
+        }
+        if page < last {
+            links.push(("next", page + 1));
+        }
+        links.push(("last", last));
+        let links: Vec<String> = links
+            .into_iter()
+            .map(|(rel, page)| format!("<{}>; rel=\"{rel}\"", page_url(req, page)))
+            .collect();
+        response.insert_header((TOTAL_PAGES, last));
+        response.insert_header((header::LINK, links.join(", ")));
+    }
+}
+
+/// The request's own path and query with `?page=` set to `page`.
+fn page_url(req: &HttpRequest, page: usize) -> String {
+    let mut params: Vec<String> = req
+        .query_string()
+        .split('&')
+        .filter(|param| !param.is_empty() && param.split('=').next() != Some("page"))
+        .map(String::from)
+        .collect();
+    params.push(format!("page={page}"));
+    format!("{}?{}", req.path(), params.join("&"))
+}
+
+/// The request's query params; of a repeated one, the last.
+pub fn params(req: &HttpRequest) -> HashMap<String, String> {
+    web::Query::<HashMap<String, String>>::from_query(req.query_string())
+        .map(|q| q.into_inner())
+        .unwrap_or_default()
+}
+
+/// `true` or `false`, also written `1` or `0`.
+pub fn boolean(raw: &str) -> Option<bool> {
+    match raw {
+        "true" | "1" => Some(true),
+        "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// A query param whose value isn't what the param takes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BadParam {
+    pub param: String,
+    pub value: String,
+    /// What it takes, as in "a whole number from 1".
+    pub expected: String,
+}
+
+/// The bad params of one request, answered together in a single 400.
+#[derive(Debug, Default)]
+pub struct BadParams(Vec<BadParam>);
+
+impl BadParams {
+    pub fn push(&mut self, param: &str, value: &str, expected: impl Into<String>) {
+        self.0.push(BadParam {
+            param: param.to_string(),
+            value: value.to_string(),
+            expected: expected.into(),
+        });
+    }
+
+    /// `params[name]` through `parse`, or `None` when it's missing or noted
+    /// as bad because `parse` refused it.
+    pub fn read<T>(
+        &mut self,
+        params: &HashMap<String, String>,
+        name: &str,
+        expected: &str,
+        parse: impl Fn(&str) -> Option<T>,
+    ) -> Option<T> {
+        let raw = params.get(name)?;
+        let value = parse(raw);
+        if value.is_none() {
+            self.push(name, raw, expected);
+        }
+        value
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// `value` when no param was bad.
+    pub fn into_result<T>(self, value: T) -> Result<T, BadParams> {
+        if self.is_empty() {
+            Ok(value)
+        } else {
+            Err(self)
+        }
+    }
+
+    /// The 400: `error` sums every bad param up in a sentence, `params`
+    /// lists each with the value it got and what it expected.
+    pub fn response(&self) -> HttpResponse {
+        let error: Vec<String> = self
+            .0
+            .iter()
+            .map(|bad| {
+                format!(
+                    "`{}` expects {}, got `{}`",
+                    bad.param, bad.expected, bad.value
+                )
+            })
+            .collect();
+        let params: Vec<Value> = self
+            .0
+            .iter()
+            .map(|bad| {
+                json!({
+                    "param": bad.param,
+                    "value": bad.value,
+                    "expected": bad.expected,
+                })
+            })
+            .collect();
+        HttpResponse::BadRequest().json(json!({
+            "error": error.join("; "),
+            "params": params,
+        }))
+    }
+}
+
+/// `raw` from a query string or path as a value of `prop`'s type, so it
+/// can be compared with generated values. Fails with what `prop` takes.
+pub fn coerce(prop: &Prop, config: &Config, raw: &str) -> Result<Value, String> {
+    match &prop.ty {
+        TProp::Number | TProp::BigInt if !generate::as_string(prop, config) => raw
+            .parse::<u64>()
+            .map(Value::from)
+            .or_else(|_| raw.parse::<i64>().map(Value::from))
+            .ok()
+            .or_else(|| {
+                raw.parse::<f64>()
+                    .ok()
+                    .and_then(Number::from_f64)
+                    .map(Value::Number)
+            })
+            .ok_or_else(|| "a number".to_string()),
+        TProp::Boolean => match raw {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => Err("true or false".to_string()),
+        },
+        TProp::Enum(values) => values
+            .iter()
+            .find(|value| as_text(value).as_deref() == Some(raw))
+            .cloned()
+            .ok_or_else(|| {
+                let values: Vec<String> = values.iter().filter_map(as_text).collect();
+                format!("one of {}", values.join(", "))
+            }),
+        TProp::Date => date::parse(raw)
+            .map(|_| Value::String(raw.to_string()))
+            .ok_or_else(|| "a date like 2024-06-01 or 2024-06-01T12:00:00Z".to_string()),
+        TProp::Array(_) => Err("nothing: it holds a list, which can't be compared".to_string()),
+        TProp::Object { .. } => {
+            Err("nothing: it holds an object, which can't be compared".to_string())
+        }
+        _ => Ok(Value::String(raw.to_string())),
+    }
+}
+
+/// Whether two values are the same: equal as numbers, or the same once
+/// written in a URL, so `1` and `"1"` match.
+pub fn same_value(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => as_text(a).is_some_and(|a| as_text(b) == Some(a)),
+    }
+}
+
+/// A scalar as it's written in a URL.
+pub fn as_text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Numbers by value, strings and booleans in their own order; values of
+/// different kinds by kind. Missing and `null` values go last either way.
+fn compare(a: &Value, b: &Value, descending: bool) -> Ordering {
+    let ordering = match (a, b) {
+        (Value::Null, Value::Null) => return Ordering::Equal,
+        (Value::Null, _) => return Ordering::Greater,
+        (_, Value::Null) => return Ordering::Less,
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (a, b) => kind(a).cmp(&kind(b)),
+    };
+    if descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+fn kind(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    fn prop(id: &str, ty: TProp) -> Prop {
+        Prop {
+            id: id.to_string(),
+            ty,
+            ..Prop::default()
+        }
+    }
+
+    fn users() -> Entity {
+        Entity {
+            name: "User".to_string(),
+            route: "/users".to_string(),
+            list: true,
+            props: vec![
+                prop("id", TProp::Number),
+                prop("name", TProp::String),
+                prop("admin", TProp::Boolean),
+                prop("role", TProp::Enum(vec![json!("owner"), json!("guest")])),
+                prop("joined", TProp::Date),
+            ],
+            ..Entity::default()
+        }
+    }
+
+    /// The params of `/users?{query}` that `requested` finds bad.
+    fn bad(query: &str) -> Vec<BadParam> {
+        let req = TestRequest::get()
+            .uri(&format!("/users?{query}"))
+            .to_http_request();
+        match CollectionQuery::requested(&req, &users(), &Config::default()) {
+            Ok(_) => Vec::new(),
+            Err(BadParams(bad)) => bad,
+        }
+    }
+
+    fn one(param: &str, value: &str, expected: &str) -> Vec<BadParam> {
+        vec![BadParam {
+            param: param.to_string(),
+            value: value.to_string(),
+            expected: expected.to_string(),
+        }]
+    }
+
+    #[test]
+    fn good_params_are_read() {
+        assert_eq!(
+            bad("id=3&admin=true&role=guest&joined=2024-06-01&sort=-name,id&page=2&per_page=5"),
+            Vec::new()
+        );
+    }
+
+    #[test]
+    fn filters_are_checked_against_their_prop_type() {
+        assert_eq!(bad("admin=yes"), one("admin", "yes", "true or false"));
+        assert_eq!(bad("id=three"), one("id", "three", "a number"));
+        assert_eq!(
+            bad("role=admin"),
+            one("role", "admin", "one of owner, guest")
+        );
+        assert_eq!(
+            bad("joined=2024-13-01"),
+            one(
+                "joined",
+                "2024-13-01",
+                "a date like 2024-06-01 or 2024-06-01T12:00:00Z"
+            )
+        );
+    }
+
+    #[test]
+    fn params_that_name_no_prop_are_ignored() {
+        assert_eq!(bad("nickname=x"), Vec::new());
+    }
+
+    #[test]
+    fn sort_fields_must_be_props() {
+        assert_eq!(
+            bad("sort=name,-age"),
+            one(
+                "sort",
+                "-age",
+                "props of `User`, `-` first for descending: id, name, admin, role, joined"
+            )
+        );
+    }
+
+    #[test]
+    fn pages_are_whole_numbers_from_one() {
+        for (param, value) in [
+            ("page", "two"),
+            ("page", "0"),
+            ("page", "-1"),
+            ("per_page", "1.5"),
+            ("per_page", "0"),
+        ] {
+            assert_eq!(
+                bad(&format!("{param}={value}")),
+                one(param, value, "a whole number from 1")
+            );
+        }
+        assert_eq!(bad("count=-1"), one("count", "-1", "a whole number"));
+        assert_eq!(
+            bad("include_deleted=maybe"),
+            one("include_deleted", "maybe", "true or false")
+        );
+    }
+
+    #[actix_web::test]
+    async fn bad_params_are_answered_together() {
+        let req = TestRequest::get()
+            .uri("/users?page=0&role=admin&sort=age&id=x")
+            .to_http_request();
+        let Err(bad) = CollectionQuery::requested(&req, &users(), &Config::default()) else {
+            panic!("expected bad params");
+        };
+        let res = bad.response();
+        assert_eq!(res.status(), 400);
+        let body = actix_web::body::to_bytes(res.into_body()).await.unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let params: Vec<&str> = body["params"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|bad| bad["param"].as_str().unwrap())
+            .collect();
+        assert_eq!(params, ["id", "role", "sort", "page"]);
+        assert_eq!(
+            body["error"],
+            "`id` expects a number, got `x`; \
+             `role` expects one of owner, guest, got `admin`; \
+             `sort` expects props of `User`, `-` first for descending: \
+             id, name, admin, role, joined, got `age`; \
+             `page` expects a whole number from 1, got `0`"
+        );
+    }
+
+    /// The ids on the page `/users?{query}` gives of users 1 to 23, where
+    /// the odd ones are admins, and its `X-Total-Count`, `X-Total-Pages`
+    /// and `Link`.
+    fn page(query: &str) -> (Vec<u64>, [Option<String>; 3]) {
+        let items: Vec<Value> = (1..=23)
+            .map(|id| json!({ "id": id, "admin": id % 2 == 1 }))
+            .collect();
+        let req = TestRequest::get()
+            .uri(&format!("/users?{query}"))
+            .to_http_request();
+        let query = CollectionQuery::requested(&req, &users(), &Config::default()).unwrap();
+        let (items, total) = query.apply(items);
+        let mut response = HttpResponse::Ok();
+        query.describe(&mut response, &req, total);
+        let response = response.finish();
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .map(|value| value.to_str().unwrap().to_string())
+        };
+        (
+            items
+                .iter()
+                .map(|item| item["id"].as_u64().unwrap())
+                .collect(),
+            [
+                header(TOTAL_COUNT),
+                header(TOTAL_PAGES),
+                header(header::LINK.as_str()),
+            ],
+        )
+    }
+
+    fn links(query: &str, rels: &[(&str, usize)]) -> Option<String> {
+        let links: Vec<String> = rels
+            .iter()
+            .map(|(rel, page)| format!("</users?{query}&page={page}>; rel=\"{rel}\""))
+            .collect();
+        Some(links.join(", "))
+    }
+
+    #[test]
+    fn unpaged_lists_only_count_what_the_filters_pass() {
+        let (ids, [count, pages, link]) = page("admin=true&sort=-id");
+        assert_eq!(ids.len(), 12);
+        assert_eq!(ids[..3], [23, 21, 19]);
+        assert_eq!(count.as_deref(), Some("12"));
+        assert_eq!((pages, link), (None, None));
+    }
+
+    #[test]
+    fn the_first_page_links_forward() {
+        let query = "admin=true&sort=-id&per_page=5";
+        let (ids, headers) = page(&format!("{query}&page=1"));
+        assert_eq!(ids, [23, 21, 19, 17, 15]);
+        assert_eq!(
+            headers,
+            [
+                Some("12".to_string()),
+                Some("3".to_string()),
+                links(query, &[("first", 1), ("next", 2), ("last", 3)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_middle_page_links_both_ways() {
+        let query = "admin=true&sort=-id&per_page=5";
+        let (ids, [_, _, link]) = page(&format!("page=2&{query}"));
+        assert_eq!(ids, [13, 11, 9, 7, 5]);
+        assert_eq!(
+            link,
+            links(
+                query,
+                &[("first", 1), ("prev", 1), ("next", 3), ("last", 3)]
+            )
+        );
+    }
+
+    #[test]
+    fn the_last_page_is_partial_and_links_back() {
+        let query = "admin=true&sort=-id&per_page=5";
+        let (ids, headers) = page(&format!("{query}&page=3"));
+        assert_eq!(ids, [3, 1]);
+        assert_eq!(
+            headers,
+            [
+                Some("12".to_string()),
+                Some("3".to_string()),
+                links(query, &[("first", 1), ("prev", 2), ("last", 3)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_page_past_the_end_is_empty_and_links_to_the_last() {
+        let query = "admin=true&sort=-id&per_page=5";
+        let (ids, headers) = page(&format!("{query}&page=9"));
+        assert_eq!(ids, Vec::<u64>::new());
+        assert_eq!(
+            headers,
+            [
+                Some("12".to_string()),
+                Some("3".to_string()),
+                links(query, &[("first", 1), ("prev", 3), ("last", 3)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_matches_make_one_empty_page() {
+        let (ids, headers) = page("id=99&page=1");
+        assert_eq!(ids, Vec::<u64>::new());
+        assert_eq!(
+            headers,
+            [
+                Some("0".to_string()),
+                Some("1".to_string()),
+                links("id=99", &[("first", 1), ("last", 1)]),
+            ]
+        );
+    }
+}
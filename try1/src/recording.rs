@@ -0,0 +1,487 @@
+use std::{
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    body::{BodySize, BoxBody, MessageBody},
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    error::PayloadError,
+    http::header::HeaderMap,
+    middleware::Next,
+    web::{self, Bytes},
+    Error, HttpMessage, HttpResponse,
+};
+use futures_util::{stream, StreamExt};
+use serde_json::{json, Map, Value};
+
+use crate::cli::Config;
+
+type BoxedPayloadStream =
+    std::pin::Pin<Box<dyn futures_util::Stream<Item = Result<Bytes, PayloadError>>>>;
+
+/// A request as seen by the mock, kept for inspection and assertions.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub id: u64,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u128,
+    pub method: String,
+    pub path: String,
+    pub query: String,
+    pub headers: Map<String, Value>,
+    /// Parsed JSON when the body is JSON, the raw text otherwise.
+    pub body: Option<Value>,
+    pub status: u16,
+    /// Until the whole response was sent, delays and throttling included.
+    pub duration_ms: f64,
+    pub response: RecordedResponse,
+}
+
+/// What the mock answered a recorded request with.
+#[derive(Debug, Clone, Default)]
+pub struct RecordedResponse {
+    pub headers: Map<String, Value>,
+    /// The body's first `--max-body-bytes`.
+    pub body: Vec<u8>,
+    /// The whole body's size.
+    pub size: usize,
+    /// Until the status and headers were ready.
+    pub wait_ms: f64,
+}
+
+impl RecordedRequest {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "timestamp": self.timestamp,
+            "method": self.method,
+            "path": self.path,
+            "query": self.query,
+            "headers": self.headers,
+            "body": self.body,
+            "status": self.status,
+            "duration_ms": self.duration_ms,
+        })
+    }
+}
+
+/// Bounded history of recent requests; the oldest entries are dropped first.
+pub struct Recorder {
+    capacity: usize,
+    inner: Mutex<RecorderState>,
+}
+
+struct RecorderState {
+    next_id: u64,
+    requests: VecDeque<RecordedRequest>,
+}
+
+impl Recorder {
+    pub fn new(capacity: usize) -> Self {
+        Recorder {
+            capacity,
+            inner: Mutex::new(RecorderState {
+                next_id: 1,
+                requests: VecDeque::new(),
+            }),
+        }
+    }
+
+    fn push(&self, mut request: RecordedRequest) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut state = self.inner.lock().unwrap();
+        request.id = state.next_id;
+        state.next_id += 1;
+        if state.requests.len() == self.capacity {
+            state.requests.pop_front();
+        }
+        state.requests.push_back(request);
+    }
+
+    pub fn snapshot(&self) -> Vec<RecordedRequest> {
+        self.inner
+            .lock()
+            .unwrap()
+            .requests
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    pub fn clear(&self) {
+        self.inner.lock().unwrap().requests.clear();
+    }
+}
+
+/// The `--record` file: one JSON line per request, holding the request
+/// and the response the mock gave it. A response body over
+/// `--max-body-bytes` is cut there and marked `"truncated": true`.
+pub struct Trace(Mutex<File>);
+
+impl Trace {
+    /// Opens `path` for appending, so several sessions add up to one trace.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Trace(Mutex::new(file)))
+    }
+
+    /// Writes one line. A failed write is reported and the request served
+    /// anyway; the trace is a by-product, not part of the response.
+    fn append(&self, line: &Value) {
+        let mut text = line.to_string();
+        text.push('\n');
+        if let Err(err) = self.0.lock().unwrap().write_all(text.as_bytes()) {
+            eprintln!("error: writing --record trace failed: {err}");
+        }
+    }
+}
+
+pub async fn record(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let recorder = req.app_data::<web::Data<Recorder>>().cloned();
+    let trace = req.app_data::<web::Data<Trace>>().cloned();
+    let config = req.app_data::<web::Data<Config>>().cloned();
+    // The mock's own endpoints aren't recorded.
+    let internal = config
+        .as_ref()
+        .is_some_and(|config| config.is_internal(req.path()));
+    if internal || (recorder.is_none() && trace.is_none()) {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let started = Instant::now();
+    let limit = config.map_or(usize::MAX, |config| config.max_body_bytes);
+    let (body, payload) = read_body(req.take_payload(), limit).await;
+    let mut request = RecordedRequest {
+        id: 0,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis()),
+        method: req.method().to_string(),
+        path: req.path().to_string(),
+        query: req.query_string().to_string(),
+        headers: header_map(req.headers()),
+        body: body.and_then(|body| parse_body(&body)),
+        status: 0,
+        duration_ms: 0.0,
+        response: RecordedResponse::default(),
+    };
+    req.set_payload(payload);
+
+    let res = next.call(req).await?;
+    request.status = res.status().as_u16();
+    request.response.headers = header_map(res.headers());
+    request.response.wait_ms = started.elapsed().as_secs_f64() * 1000.0;
+    let mut capture = Capture {
+        request,
+        recorder,
+        trace,
+        limit,
+        started,
+    };
+
+    // A body that's already whole is recorded now; a streamed one as it's
+    // sent, so it still streams.
+    let (req, res) = res.into_parts();
+    let (head, body) = res.into_parts();
+    let body = match body.try_into_bytes() {
+        Ok(bytes) => {
+            capture.extend(&bytes);
+            capture.finish();
+            BoxBody::new(bytes)
+        }
+        Err(body) => BoxBody::new(Teed {
+            body: body.boxed(),
+            capture: Some(capture),
+        }),
+    };
+    Ok(ServiceResponse::new(req, head.set_body(body)))
+}
+
+fn header_map(headers: &HeaderMap) -> Map<String, Value> {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            (
+                name.to_string(),
+                Value::String(value.to_str().unwrap_or_default().to_string()),
+            )
+        })
+        .collect()
+}
+
+/// A request whose response is being sent, and what of it has gone out.
+struct Capture {
+    request: RecordedRequest,
+    recorder: Option<web::Data<Recorder>>,
+    trace: Option<web::Data<Trace>>,
+    /// Response bytes the history and the trace keep.
+    limit: usize,
+    started: Instant,
+}
+
+impl Capture {
+    fn extend(&mut self, chunk: &[u8]) {
+        let response = &mut self.request.response;
+        response.size += chunk.len();
+        let keep = chunk
+            .len()
+            .min(self.limit.saturating_sub(response.body.len()));
+        response.body.extend_from_slice(&chunk[..keep]);
+    }
+
+    /// Records the exchange once the response is sent, or abandoned.
+    fn finish(mut self) {
+        self.request.duration_ms = self.started.elapsed().as_secs_f64() * 1000.0;
+        let request = &self.request;
+        if let Some(trace) = &self.trace {
+            let mut line = json!({
+                "timestamp": request.timestamp,
+                "request": {
+                    "method": request.method,
+                    "path": request.path,
+                    "query": request.query,
+                    "headers": request.headers,
+                    "body": request.body,
+                },
+                "response": {
+                    "status": request.status,
+                    "headers": request
+                        .response
+                        .headers
+                        .iter()
+                        .filter(|(name, _)| !matches!(name.as_str(), "content-length" | "transfer-encoding"))
+                        .map(|(name, value)| (name.clone(), value.clone()))
+                        .collect::<Map<String, Value>>(),
+                    // As sent, so replay can send the same bytes.
+                    "text": String::from_utf8_lossy(&request.response.body),
+                },
+                "duration_ms": request.duration_ms,
+            });
+            // Like the history, the trace keeps `--max-body-bytes` of the
+            // body, so a huge response isn't held whole; replay skips one
+            // that was cut.
+            if request.response.body.len() < request.response.size {
+                line["response"]["size"] = json!(request.response.size);
+                line["response"]["truncated"] = json!(true);
+            }
+            trace.append(&line);
+        }
+        if let Some(recorder) = self.recorder {
+            recorder.push(self.request);
+        }
+    }
+}
+
+/// A streamed response body that copies what it sends for the record.
+struct Teed {
+    body: BoxBody,
+    capture: Option<Capture>,
+}
+
+impl MessageBody for Teed {
+    type Error = Box<dyn std::error::Error>;
+
+    fn size(&self) -> BodySize {
+        self.body.size()
+    }
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Bytes, Self::Error>>> {
+        let poll = Pin::new(&mut self.body).poll_next(cx);
+        match &poll {
+            Poll::Ready(Some(Ok(chunk))) => {
+                if let Some(capture) = &mut self.capture {
+                    capture.extend(chunk);
+                }
+            }
+            Poll::Ready(None) => {
+                if let Some(capture) = self.capture.take() {
+                    capture.finish();
+                }
+            }
+            _ => (),
+        }
+        poll
+    }
+}
+
+impl Drop for Teed {
+    /// A client that hangs up early still gets its request recorded, with
+    /// what was sent of the response.
+    fn drop(&mut self) {
+        if let Some(capture) = self.capture.take() {
+            capture.finish();
+        }
+    }
+}
+
+/// Reads up to `limit` bytes of the body for the record and returns a payload
+/// that replays them followed by whatever wasn't read, so the handler (and
+/// its own size limit) sees the original request. The recorded body is
+/// `None` when it was larger than `limit` or failed to arrive.
+pub async fn read_body(mut payload: Payload, limit: usize) -> (Option<Vec<u8>>, Payload) {
+    let mut chunks: Vec<Result<Bytes, PayloadError>> = Vec::new();
+    let mut size = 0;
+    let mut complete = false;
+
+    while size <= limit {
+        match payload.next().await {
+            Some(Ok(chunk)) => {
+                size += chunk.len();
+                chunks.push(Ok(chunk));
+            }
+            Some(Err(err)) => {
+                chunks.push(Err(err));
+                break;
+            }
+            None => {
+                complete = true;
+                break;
+            }
+        }
+    }
+
+    let body = (complete && size <= limit).then(|| {
+        chunks
+            .iter()
+            .flatten()
+            .flat_map(|chunk| chunk.iter().copied())
+            .collect()
+    });
+    let replay: BoxedPayloadStream = Box::pin(stream::iter(chunks).chain(payload));
+    (body, Payload::from(replay))
+}
+
+pub fn parse_body(body: &[u8]) -> Option<Value> {
+    if body.is_empty() {
+        return None;
+    }
+    serde_json::from_slice(body)
+        .ok()
+        .or_else(|| Some(Value::String(String::from_utf8_lossy(body).into_owned())))
+}
+
+pub async fn list(recorder: web::Data<Recorder>) -> HttpResponse {
+    let requests: Vec<Value> = recorder
+        .snapshot()
+        .iter()
+        .map(RecordedRequest::to_json)
+        .collect();
+    HttpResponse::Ok().json(requests)
+}
+
+pub async fn clear(recorder: web::Data<Recorder>) -> HttpResponse {
+    recorder.clear();
+    HttpResponse::NoContent().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use actix_web::{middleware, test as http, App};
+
+    use super::*;
+
+    const BODY: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
+
+    /// Serves `BODY` at `/whole` and in small chunks at `/streamed`,
+    /// recording to a fresh trace that keeps `limit` bytes of each body.
+    struct Traced {
+        path: PathBuf,
+        limit: usize,
+    }
+
+    impl Traced {
+        fn new(name: &str, limit: usize) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("ssg-trace-{name}-{}.jsonl", std::process::id()));
+            let _ = fs::remove_file(&path);
+            Traced { path, limit }
+        }
+
+        async fn get(&self, uri: &str) -> Bytes {
+            let config = web::Data::new(Config {
+                max_body_bytes: self.limit,
+                ..Config::default()
+            });
+            let trace = web::Data::new(Trace::open(&self.path).unwrap());
+            let app = App::new()
+                .wrap(middleware::from_fn(record))
+                .app_data(config)
+                .app_data(trace)
+                .route(
+                    "/whole",
+                    web::get().to(|| async { HttpResponse::Ok().body(BODY) }),
+                )
+                .route(
+                    "/streamed",
+                    web::get().to(|| async {
+                        let chunks = BODY
+                            .as_bytes()
+                            .chunks(5)
+                            .map(|chunk| Ok::<_, Error>(Bytes::copy_from_slice(chunk)));
+                        HttpResponse::Ok().streaming(stream::iter(chunks))
+                    }),
+                );
+            let app = http::init_service(app).await;
+            let req = http::TestRequest::get().uri(uri).to_request();
+            http::read_body(http::call_service(&app, req).await).await
+        }
+
+        fn lines(&self) -> Vec<Value> {
+            fs::read_to_string(&self.path)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    impl Drop for Traced {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+
+    #[actix_web::test]
+    async fn the_trace_keeps_bodies_within_the_limit_whole() {
+        let traced = Traced::new("whole", 1024);
+        traced.get("/whole").await;
+        traced.get("/streamed").await;
+        for line in traced.lines() {
+            assert_eq!(line["response"]["text"], BODY);
+            assert_eq!(line["response"].get("truncated"), None);
+        }
+    }
+
+    #[actix_web::test]
+    async fn the_trace_cuts_bodies_over_the_limit_and_says_so() {
+        let traced = Traced::new("cut", 12);
+        for uri in ["/whole", "/streamed"] {
+            // The client still gets the whole body.
+            assert_eq!(traced.get(uri).await, BODY);
+        }
+        let lines = traced.lines();
+        assert_eq!(lines.len(), 2);
+        for line in lines {
+            assert_eq!(line["response"]["text"], BODY[..12]);
+            assert_eq!(line["response"]["size"], BODY.len());
+            assert_eq!(line["response"]["truncated"], true);
+        }
+    }
+}
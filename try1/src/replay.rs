@@ -0,0 +1,307 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+    sync::Mutex,
+};
+
+use actix_web::{
+    body::{BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{
+        header::{HeaderName, HeaderValue},
+        StatusCode,
+    },
+    middleware::Next,
+    web, Error, HttpMessage, HttpResponse,
+};
+use serde_json::{json, Value};
+
+use crate::{cli::Config, recording, warning};
+
+/// A `--record` trace loaded for `--replay`: the recorded exchanges by
+/// method, path and query, each list in the order it was recorded.
+pub struct Replay {
+    exchanges: HashMap<String, Vec<Exchange>>,
+    /// The indexes of each list's exchanges that have been served. Once
+    /// all a request matches have been, the last of them repeats.
+    served: Mutex<HashMap<String, HashSet<usize>>>,
+}
+
+/// A recorded response body.
+enum Body {
+    /// The text as it was sent.
+    Text(String),
+    /// Parsed, from a trace written before bodies were kept as text.
+    Parsed(Option<Value>),
+}
+
+struct Exchange {
+    request_body: Option<Value>,
+    status: StatusCode,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    body: Body,
+    /// Cut at `--max-body-bytes` when recorded.
+    truncated: bool,
+}
+
+impl Replay {
+    /// Reads a JSONL trace as `--record` writes it. Blank lines are skipped;
+    /// a line that isn't a recorded exchange fails the load. An exchange
+    /// whose response was cut when recorded is left out with a warning, as
+    /// replaying it would send part of a body.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let mut exchanges: HashMap<String, Vec<Exchange>> = HashMap::new();
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (key, exchange) = exchange(line)
+                .map_err(|error| format!("{}:{}: {error}", path.display(), index + 1))?;
+            if exchange.truncated {
+                warning::warn(
+                    path,
+                    format!(
+                        "line {}: the response to {key} was cut by --max-body-bytes \
+                         when recorded, so it isn't replayed",
+                        index + 1
+                    ),
+                );
+                continue;
+            }
+            exchanges.entry(key).or_default().push(exchange);
+        }
+        Ok(Replay {
+            exchanges,
+            served: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The next recorded response to a request, in recording order, so a
+    /// GET recorded before and after a write replays both answers. With
+    /// `match_body`, only exchanges recorded with the same body count, each
+    /// served once whatever other bodies were sent in between.
+    fn next(&self, key: &str, body: Option<&Value>, match_body: bool) -> Option<HttpResponse> {
+        let mut candidates = self
+            .exchanges
+            .get(key)?
+            .iter()
+            .enumerate()
+            .filter(|(_, exchange)| !match_body || exchange.request_body.as_ref() == body);
+        let mut served = self.served.lock().unwrap();
+        let served = served.entry(key.to_string()).or_default();
+        let (index, exchange) = candidates
+            .clone()
+            .find(|(index, _)| !served.contains(index))
+            .or_else(|| candidates.next_back())?;
+        served.insert(index);
+
+        let mut response = HttpResponse::build(exchange.status);
+        for header in &exchange.headers {
+            response.append_header(header.clone());
+        }
+        let is_json = exchange.headers.iter().any(|(name, value)| {
+            name == "content-type" && value.to_str().is_ok_and(|v| v.contains("json"))
+        });
+        Some(match &exchange.body {
+            Body::Text(text) => response.body(text.clone()),
+            Body::Parsed(None) => response.finish(),
+            Body::Parsed(Some(Value::String(text))) if !is_json => response.body(text.clone()),
+            Body::Parsed(Some(body)) => response.body(body.to_string()),
+        })
+    }
+}
+
+/// One line of the trace, keyed for lookup.
+fn exchange(line: &str) -> Result<(String, Exchange), String> {
+    let entry: Value = serde_json::from_str(line).map_err(|err| format!("invalid JSON: {err}"))?;
+    let request = &entry["request"];
+    let response = &entry["response"];
+    let (Some(method), Some(path)) = (request["method"].as_str(), request["path"].as_str()) else {
+        return Err("expected `request.method` and `request.path`".to_string());
+    };
+    let status = response["status"]
+        .as_u64()
+        .and_then(|status| StatusCode::from_u16(status as u16).ok())
+        .ok_or("expected a `response.status`")?;
+    let mut headers = Vec::new();
+    for (name, value) in response["headers"].as_object().into_iter().flatten() {
+        let value = value.as_str().map(HeaderValue::from_str);
+        match (HeaderName::from_bytes(name.as_bytes()), value) {
+            (Ok(name), Some(Ok(value))) => headers.push((name, value)),
+            _ => return Err(format!("header `{name}` must be a valid string header")),
+        }
+    }
+    let key = request_key(method, path, request["query"].as_str().unwrap_or_default());
+    Ok((
+        key,
+        Exchange {
+            request_body: request.get("body").cloned().filter(|body| !body.is_null()),
+            status,
+            headers,
+            body: match response["text"].as_str() {
+                Some(text) => Body::Text(text.to_string()),
+                None => Body::Parsed(response.get("body").cloned().filter(|body| !body.is_null())),
+            },
+            truncated: response["truncated"] == true,
+        },
+    ))
+}
+
+/// Method, path and query, the query's params sorted so their order
+/// doesn't matter.
+fn request_key(method: &str, path: &str, query: &str) -> String {
+    let mut params: Vec<&str> = query.split('&').filter(|p| !p.is_empty()).collect();
+    params.sort_unstable();
+    let method = method.to_ascii_uppercase();
+    if params.is_empty() {
+        format!("{method} {path}")
+    } else {
+        format!("{method} {path}?{}", params.join("&"))
+    }
+}
+
+/// Under `--replay`, answers requests the trace recorded with the recorded
+/// response, headers and all. Headers never take part in matching, so
+/// dates, tokens and user agents can change between runs; the body does
+/// only with `--replay-match-body`. Unmatched requests are generated as
+/// usual, or get a 404 with `--replay-strict`.
+pub async fn respond(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, Error> {
+    let replay = req.app_data::<web::Data<Replay>>().cloned();
+    let config = req.app_data::<web::Data<Config>>().cloned();
+    let (Some(replay), Some(config)) = (replay, config) else {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    };
+    if config.is_internal(req.path()) {
+        return Ok(next.call(req).await?.map_into_boxed_body());
+    }
+
+    let body = if config.replay_match_body {
+        let (body, payload) = recording::read_body(req.take_payload(), config.max_body_bytes).await;
+        req.set_payload(payload);
+        body.and_then(|body| recording::parse_body(&body))
+    } else {
+        None
+    };
+    let key = request_key(req.method().as_str(), req.path(), req.query_string());
+    match replay.next(&key, body.as_ref(), config.replay_match_body) {
+        Some(response) => Ok(req.into_response(response)),
+        None if config.replay_strict => {
+            let response = HttpResponse::NotFound().json(json!({
+                "error": format!("no recorded response to {key}"),
+            }));
+            Ok(req.into_response(response))
+        }
+        None => Ok(next.call(req).await?.map_into_boxed_body()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::header, middleware, test as http, App};
+
+    use super::*;
+    use crate::recording::Trace;
+
+    /// `lines` loaded as a `--replay` trace.
+    fn load(name: &str, lines: &[Value]) -> Replay {
+        let path =
+            std::env::temp_dir().join(format!("ssg-replay-{name}-{}.jsonl", std::process::id()));
+        let text: Vec<String> = lines.iter().map(Value::to_string).collect();
+        fs::write(&path, text.join("\n")).unwrap();
+        let replay = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        replay.unwrap()
+    }
+
+    fn line(path: &str, response: Value) -> Value {
+        json!({ "request": { "method": "GET", "path": path }, "response": response })
+    }
+
+    #[test]
+    fn responses_cut_when_recorded_are_not_replayed() {
+        let replay = load(
+            "truncated",
+            &[
+                line("/whole", json!({ "status": 200, "body": "all of it" })),
+                line(
+                    "/cut",
+                    json!({ "status": 200, "body": "part", "size": 9, "truncated": true }),
+                ),
+            ],
+        );
+        assert!(replay.next("GET /whole", None, false).is_some());
+        assert!(replay.next("GET /cut", None, false).is_none());
+    }
+
+    /// Bodies that re-serializing their JSON would change.
+    const PRICE: &str = "{\"price\": 1.50, \"tags\" : [ ]}\n";
+    const NOTE: &str = "  plain text, \"quoted\"  ";
+
+    /// The status, content type and body of `res`.
+    async fn parts<B: MessageBody>(res: ServiceResponse<B>) -> (StatusCode, String, web::Bytes) {
+        let content_type = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        let content_type = content_type.to_string();
+        (res.status(), content_type, http::read_body(res).await)
+    }
+
+    #[actix_web::test]
+    async fn recorded_bodies_replay_byte_for_byte() {
+        let path = std::env::temp_dir().join(format!(
+            "ssg-replay-round-trip-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let uris = ["/price", "/note"];
+
+        let recording = App::new()
+            .wrap(middleware::from_fn(recording::record))
+            .app_data(web::Data::new(Config::default()))
+            .app_data(web::Data::new(Trace::open(&path).unwrap()))
+            .route(
+                "/price",
+                web::get().to(|| async {
+                    HttpResponse::Created()
+                        .content_type("application/json")
+                        .body(PRICE)
+                }),
+            )
+            .route(
+                "/note",
+                web::get()
+                    .to(|| async { HttpResponse::Ok().content_type("text/plain").body(NOTE) }),
+            );
+        let recording = http::init_service(recording).await;
+        let mut recorded = Vec::new();
+        for uri in uris {
+            let req = http::TestRequest::get().uri(uri).to_request();
+            recorded.push(parts(http::call_service(&recording, req).await).await);
+        }
+
+        let config = Config {
+            replay_strict: true,
+            ..Config::default()
+        };
+        let replay = Replay::load(&path);
+        fs::remove_file(&path).unwrap();
+        let replaying = App::new()
+            .wrap(middleware::from_fn(respond))
+            .app_data(web::Data::new(config))
+            .app_data(web::Data::new(replay.unwrap()));
+        let replaying = http::init_service(replaying).await;
+        for (uri, recorded) in uris.into_iter().zip(recorded) {
+            let req = http::TestRequest::get().uri(uri).to_request();
+            let replayed = parts(http::call_service(&replaying, req).await).await;
+            assert_eq!(replayed, recorded, "{uri}");
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use serde_json::Value;
+
+use crate::warning;
+
+/// The parts of a `tsconfig.json` that affect module resolution.
+#[derive(Debug)]
+struct TsConfig {
+    path: PathBuf,
+    /// `compilerOptions.baseUrl`, made absolute.
+    base_url: Option<PathBuf>,
+    /// `compilerOptions.paths` patterns and their substitutions, in order.
+    paths: Vec<(String, Vec<String>)>,
+}
+
+impl TsConfig {
+    fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let json: Value =
+            serde_json::from_str(&strip_jsonc(&text)).map_err(|err| err.to_string())?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let options = json.get("compilerOptions");
+
+        let base_url = options
+            .and_then(|o| o.get("baseUrl"))
+            .and_then(Value::as_str)
+            .map(|base| dir.join(base));
+        let paths = options
+            .and_then(|o| o.get("paths"))
+            .and_then(Value::as_object)
+            .map(|paths| {
+                paths
+                    .iter()
+                    .map(|(pattern, targets)| {
+                        let targets = targets
+                            .as_array()
+                            .map(|t| {
+                                t.iter()
+                                    .filter_map(Value::as_str)
+                                    .map(String::from)
+                                    .collect()
+                            })
+                            .unwrap_or_default();
+                        (pattern.clone(), targets)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(TsConfig {
+            path: path.to_path_buf(),
+            base_url,
+            paths,
+        })
+    }
+
+    /// The directory `paths` substitutions are relative to.
+    fn paths_base(&self) -> &Path {
+        self.base_url
+            .as_deref()
+            .unwrap_or_else(|| self.path.parent().unwrap_or(Path::new("")))
+    }
+
+    /// The `paths` entry for `specifier` and what its `*` captured: an exact
+    /// pattern wins, otherwise the wildcard pattern with the longest prefix.
+    fn matching_paths<'s>(&self, specifier: &'s str) -> Option<(&[String], &'s str)> {
+        if let Some((_, targets)) = self.paths.iter().find(|(p, _)| p == specifier) {
+            return Some((targets, ""));
+        }
+        self.paths
+            .iter()
+            .filter_map(|(pattern, targets)| {
+                let (prefix, suffix) = pattern.split_once('*')?;
+                let captured = specifier.strip_prefix(prefix)?.strip_suffix(suffix)?;
+                Some((prefix.len(), targets.as_slice(), captured))
+            })
+            .max_by_key(|(prefix_len, _, _)| *prefix_len)
+            .map(|(_, targets, captured)| (targets, captured))
+    }
+}
+
+/// Resolves import specifiers to source files, honouring the `baseUrl` and
+/// `paths` of the nearest `tsconfig.json` above the importing file.
+#[derive(Default)]
+pub struct Resolver {
+    /// Nearest tsconfig for each directory looked up so far.
+    tsconfigs: HashMap<PathBuf, Option<Rc<TsConfig>>>,
+}
+
+impl Resolver {
+    /// The file `specifier` refers to when imported from `from`. Bare
+    /// specifiers no tsconfig maps are packages and resolve to `None`
+    /// quietly; aliases and relative imports that lead nowhere warn.
+    pub fn resolve(&mut self, from: &Path, specifier: &str) -> Option<PathBuf> {
+        let dir = from.parent().unwrap_or(Path::new(""));
+        if specifier.starts_with("./") || specifier.starts_with("../") {
+            let resolved = source_file(&dir.join(specifier));
+            if resolved.is_none() {
+                warning::warn(from, format!("cannot resolve import `{specifier}`"));
+            }
+            return resolved;
+        }
+
+        let tsconfig = self.nearest_tsconfig(dir)?;
+        if let Some((targets, captured)) = tsconfig.matching_paths(specifier) {
+            let resolved = targets.iter().find_map(|target| {
+                source_file(
+                    &tsconfig
+                        .paths_base()
+                        .join(target.replacen('*', captured, 1)),
+                )
+            });
+            if resolved.is_none() {
+                warning::warn(
+                    from,
+                    format!(
+                        "cannot resolve import `{specifier}` with the paths in {}",
+                        tsconfig.path.display()
+                    ),
+                );
+            }
+            return resolved;
+        }
+        tsconfig
+            .base_url
+            .as_ref()
+            .and_then(|base| source_file(&base.join(specifier)))
+    }
+
+    fn nearest_tsconfig(&mut self, dir: &Path) -> Option<Rc<TsConfig>> {
+        if let Some(cached) = self.tsconfigs.get(dir) {
+            return cached.clone();
+        }
+        let candidate = dir.join("tsconfig.json");
+        let found = if candidate.is_file() {
+            match TsConfig::load(&candidate) {
+                Ok(tsconfig) => Some(Rc::new(tsconfig)),
+                Err(err) => {
+                    warning::warn(&candidate, err);
+                    None
+                }
+            }
+        } else {
+            dir.parent()
+                .and_then(|parent| self.nearest_tsconfig(parent))
+        };
+        self.tsconfigs.insert(dir.to_path_buf(), found.clone());
+        found
+    }
+}
+
+/// The source file an extensionless (or `.js`) module path refers to.
+fn source_file(base: &Path) -> Option<PathBuf> {
+    let stem = match base.extension().and_then(|ext| ext.to_str()) {
+        Some("js" | "jsx" | "mjs") => base.with_extension(""),
+        _ => base.to_path_buf(),
+    };
+    let stem = stem.to_string_lossy();
+    [
+        format!("{stem}.ts"),
+        format!("{stem}.tsx"),
+        format!("{stem}.d.ts"),
+        format!("{stem}/index.ts"),
+        format!("{stem}/index.tsx"),
+    ]
+    .into_iter()
+    .map(PathBuf::from)
+    .chain(
+        [base.to_path_buf()]
+            .into_iter()
+            .filter(|p| p.extension().is_some_and(|ext| ext == "ts" || ext == "tsx")),
+    )
+    .find(|p| p.is_file())
+}
+
+/// tsconfig files are JSONC: drops comments and trailing commas so that the
+/// result parses as JSON.
+fn strip_jsonc(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                '\\' => out.extend(chars.next()),
+                '"' => in_string = false,
+                _ => (),
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => while chars.next_if(|&c| c != '\n').is_some() {},
+            ('/', Some('*')) => {
+                chars.next();
+                while let Some(c) = chars.next() {
+                    if c == '*' && chars.next_if_eq(&'/').is_some() {
+                        break;
+                    }
+                }
+            }
+            (',', _) => {
+                let rest = chars.clone().find(|c| !c.is_whitespace());
+                if !matches!(rest, Some('}' | ']')) {
+                    out.push(c);
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
@@ -0,0 +1,202 @@
+use std::collections::BTreeSet;
+
+use serde_json::json;
+
+use crate::{actix_path, cli::Config, route_groups, wildcard_prefix, Entity};
+
+/// Output of `ssg routes --format`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RouteFormat {
+    Json,
+    Nginx,
+    Caddy,
+}
+
+impl RouteFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "json" => Some(RouteFormat::Json),
+            "nginx" => Some(RouteFormat::Nginx),
+            "caddy" => Some(RouteFormat::Caddy),
+            _ => None,
+        }
+    }
+}
+
+/// Upstream name the proxy snippets send requests to.
+const UPSTREAM: &str = "ssg";
+
+/// Prints every (method, path) the server answers, sorted so the output
+/// can be committed and diffed.
+pub fn print(entities: &[Entity], config: &Config, format: RouteFormat) {
+    let table = table(entities, config);
+    match format {
+        RouteFormat::Json => {
+            let routes: Vec<_> = table
+                .iter()
+                .map(|(path, method)| json!({ "method": method, "path": path }))
+                .collect();
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&routes).unwrap_or_default()
+            );
+        }
+        RouteFormat::Nginx => {
+            println!(
+                "# upstream {UPSTREAM} {{ server 127.0.0.1:{}; }}",
+                config.port
+            );
+            for (path, methods) in by_path(&table) {
+                println!("\n# {}", methods.join(" "));
+                println!("location {} {{", nginx_location(path));
+                println!("    proxy_pass http://{UPSTREAM};");
+                println!("}}");
+            }
+        }
+        RouteFormat::Caddy => {
+            for (index, (path, methods)) in by_path(&table).into_iter().enumerate() {
+                if index > 0 {
+                    println!();
+                }
+                let name = matcher_name(path);
+                println!("# {}", methods.join(" "));
+                println!("@{name} {}", caddy_matcher(path));
+                println!("reverse_proxy @{name} 127.0.0.1:{}", config.port);
+            }
+        }
+    }
+}
+
+/// (path, method) pairs as registered: entities answer GET, POST and PUT,
+/// and under `--stateful` also GET, PUT, PATCH and DELETE on `{path}/{id}`
+/// (or the `@key`), plus DELETE on `{path}/{id}/purge` for `@soft-delete`.
+fn table(entities: &[Entity], config: &Config) -> BTreeSet<(String, &'static str)> {
+    let mut table = BTreeSet::new();
+    for (_, group) in route_groups(entities) {
+        for entity in group {
+            for (path, methods) in entity_routes(entity, config) {
+                for method in methods {
+                    table.insert((path.clone(), method));
+                }
+            }
+        }
+    }
+    table
+}
+
+/// The paths one entity is served at, with the methods each answers.
+pub fn entity_routes(entity: &Entity, config: &Config) -> Vec<(String, Vec<&'static str>)> {
+    let path = entity.served_route(config.version_prefix);
+    if !config.stateful || wildcard_prefix(&path).is_some() || entity.data.is_some() {
+        return vec![(path, vec!["GET", "POST", "PUT"])];
+    }
+    let item = entity.item_route(&path);
+    let mut routes = vec![
+        (path, vec!["GET", "POST"]),
+        (item.clone(), vec!["GET", "PUT", "PATCH", "DELETE"]),
+    ];
+    if entity.soft_delete {
+        routes.push((format!("{item}/purge"), vec!["DELETE"]));
+    }
+    routes
+}
+
+fn by_path<'a>(table: &'a BTreeSet<(String, &'static str)>) -> Vec<(&'a str, Vec<&'static str>)> {
+    let mut paths: Vec<(&str, Vec<&str>)> = Vec::new();
+    for (path, method) in table {
+        match paths.last_mut() {
+            Some((last, methods)) if last == path => methods.push(method),
+            _ => paths.push((path, vec![method])),
+        }
+    }
+    paths
+}
+
+/// A segment of a route: literal text, or a `{name}` / `{name:regex}` param.
+enum Segment<'a> {
+    Literal(&'a str),
+    Param(Option<&'a str>),
+}
+
+fn segments(path: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = path;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        segments.push(Segment::Literal(&rest[..start]));
+        let param = &rest[start + 1..end];
+        segments.push(Segment::Param(
+            param.split_once(':').map(|(_, regex)| regex),
+        ));
+        rest = &rest[end + 1..];
+    }
+    segments.push(Segment::Literal(rest));
+    segments
+}
+
+/// The path as an anchored regex, params matching one segment unless they
+/// bring their own pattern.
+fn path_regex(path: &str) -> String {
+    let mut regex = String::from("^");
+    for segment in segments(path) {
+        match segment {
+            Segment::Literal(text) => regex.push_str(&escape_regex(text)),
+            Segment::Param(Some(pattern)) => regex.push_str(&format!("({pattern})")),
+            Segment::Param(None) => regex.push_str("[^/]+"),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+fn escape_regex(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| {
+            let special = r"\.+*?()|[]{}^$".contains(c);
+            special.then_some('\\').into_iter().chain([c])
+        })
+        .collect()
+}
+
+fn has_params(path: &str) -> bool {
+    segments(path)
+        .iter()
+        .any(|s| matches!(s, Segment::Param(_)))
+}
+
+/// `= /users` for exact paths, a prefix for wildcard routes and a regex
+/// for parameterized ones.
+fn nginx_location(path: &str) -> String {
+    if let Some(prefix) = wildcard_prefix(path).filter(|prefix| !has_params(prefix)) {
+        return format!("^~ {prefix}");
+    }
+    if has_params(path) {
+        return format!("~ \"{}\"", path_regex(&actix_path(path)));
+    }
+    format!("= {path}")
+}
+
+/// A matcher name derived from the path, so adding a route doesn't rename
+/// the others: `/users/{id}` is `ssg_users_id`.
+fn matcher_name(path: &str) -> String {
+    let words: Vec<String> = path
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect();
+    if words.is_empty() {
+        return format!("{UPSTREAM}_root");
+    }
+    format!("{UPSTREAM}_{}", words.join("_"))
+}
+
+/// `path` with Caddy's trailing `*` for wildcard routes, `path_regexp` for
+/// parameterized ones.
+fn caddy_matcher(path: &str) -> String {
+    if has_params(path) {
+        return format!("path_regexp {}", path_regex(&actix_path(path)));
+    }
+    format!("path {path}")
+}
@@ -0,0 +1,64 @@
+use std::io::{self, Write};
+
+use serde_json::Value;
+
+use crate::{
+    cli::Config,
+    generate::{self, Context, Memo},
+    Entity,
+};
+
+/// The entity `ssg fake` asked for, by interface name or by route.
+pub fn find<'e>(entities: &'e [Entity], name: &str) -> Option<&'e Entity> {
+    entities
+        .iter()
+        .find(|entity| entity.name == name || entity.route == name)
+        .or_else(|| {
+            entities
+                .iter()
+                .find(|entity| entity.name.eq_ignore_ascii_case(name))
+        })
+}
+
+/// Prints `count` generated objects of `entity` to stdout, as one JSON
+/// array or, with `ndjson`, one object per line. Items share nested
+/// objects by id the way a list response does, and `--seed` makes the
+/// output the same on every run. A routed `const` prints its literal.
+pub fn print(entity: &Entity, config: &Config, count: usize, ndjson: bool) -> io::Result<()> {
+    let items: Vec<Value> = match &entity.data {
+        Some(Value::Array(items)) => items.clone(),
+        Some(data) => vec![data.clone(); count],
+        None => {
+            let mut rng = generate::rng_for(config.seed, &entity.route);
+            let ctx = Context::of(config);
+            let mut memo = Memo::default();
+            (0..count)
+                .map(|_| generate::fake_list_item(entity, config, ctx, &mut memo, &mut rng))
+                .collect()
+        }
+    };
+
+    let mut out = io::stdout().lock();
+    if ndjson {
+        for item in &items {
+            serde_json::to_writer(&mut out, item)?;
+            writeln!(out)?;
+        }
+    } else if config.pretty {
+        serde_json::to_writer_pretty(&mut out, &items)?;
+        writeln!(out)?;
+    } else {
+        serde_json::to_writer(&mut out, &items)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// The entities there are, for when the one asked for isn't among them.
+pub fn available(entities: &[Entity]) -> String {
+    entities
+        .iter()
+        .map(|entity| format!("{} ({})", entity.name, entity.route))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,299 @@
+use std::time::Duration;
+
+use actix_web::{http::StatusCode, web, HttpRequest, HttpResponse};
+use serde_json::{json, Value};
+
+use crate::{
+    cli::{self, Config},
+    diff, generate,
+    invalid::{self, Violation},
+    query::{self, BadParams},
+    reply, route_groups, versioning, Entity,
+};
+
+/// The response the route serves when nothing else is asked for.
+pub const DEFAULT: &str = "default";
+
+/// The query parameter that picks one of an entity's `@scenario`s.
+pub const PARAM: &str = "scenario";
+
+/// `@scenario error status=500 body={"error":"boom"} delay=2s`: a named
+/// override of what GET on the route answers, picked with `?scenario=`.
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub name: String,
+    pub status: Option<StatusCode>,
+    /// Sent instead of the generated data.
+    pub body: Option<Value>,
+    /// Waited before answering.
+    pub delay: Option<Duration>,
+}
+
+impl Scenario {
+    /// Parses the value of a `@scenario` tag: a name, then any of `status=`,
+    /// `body=` (JSON, which may contain spaces) and `delay=` (milliseconds
+    /// unless it has a unit).
+    pub fn parse(value: &str) -> Result<Scenario, String> {
+        let value = value.trim();
+        let (name, mut rest) = value.split_once(char::is_whitespace).unwrap_or((value, ""));
+        if name.is_empty() {
+            return Err("expected a name".to_string());
+        }
+        if name == DEFAULT {
+            return Err(format!("`{DEFAULT}` is the response without a scenario"));
+        }
+        let mut scenario = Scenario {
+            name: name.to_string(),
+            status: None,
+            body: None,
+            delay: None,
+        };
+        loop {
+            rest = rest.trim_start();
+            if rest.is_empty() {
+                return Ok(scenario);
+            }
+            let (key, after) = rest
+                .split_once('=')
+                .ok_or_else(|| format!("expected `key=value`, got `{rest}`"))?;
+            if key == "body" {
+                let mut values = serde_json::Deserializer::from_str(after).into_iter::<Value>();
+                let body = values
+                    .next()
+                    .ok_or_else(|| "`body` expects JSON".to_string())?
+                    .map_err(|err| format!("`body` expects JSON: {err}"))?;
+                scenario.body = Some(body);
+                rest = &after[values.byte_offset()..];
+                continue;
+            }
+            let (value, after) = after.split_once(char::is_whitespace).unwrap_or((after, ""));
+            match key {
+                "status" => {
+                    let status = value
+                        .parse()
+                        .ok()
+                        .and_then(|code| StatusCode::from_u16(code).ok())
+                        .ok_or_else(|| format!("`status` expects an HTTP status, got `{value}`"))?;
+                    scenario.status = Some(status);
+                }
+                "delay" => {
+                    let delay =
+                        cli::parse_millis(value).map_err(|err| format!("`delay`: {err}"))?;
+                    scenario.delay = Some(delay);
+                }
+                _ => return Err(format!("unknown key `{key}`")),
+            }
+            rest = after;
+        }
+    }
+}
+
+/// The `@scenario` a request asks for with `?scenario=`, if any.
+pub fn requested<'a>(
+    req: &HttpRequest,
+    entity: &'a Entity,
+) -> Result<Option<&'a Scenario>, String> {
+    let query = query::params(req);
+    let Some(name) = query.get(PARAM).filter(|name| *name != DEFAULT) else {
+        return Ok(None);
+    };
+    match entity.scenarios.iter().find(|s| &s.name == name) {
+        Some(scenario) => Ok(Some(scenario)),
+        None if entity.scenarios.is_empty() => Err(format!(
+            "unknown scenario `{name}`, `{}` declares none",
+            entity.name
+        )),
+        None => {
+            let names: Vec<&str> = entity.scenarios.iter().map(|s| s.name.as_str()).collect();
+            Err(format!(
+                "unknown scenario `{name}`, expected one of: {}",
+                names.join(", ")
+            ))
+        }
+    }
+}
+
+/// The named variants of an entity's response: the normal one, its
+/// `@scenario`s, then one per violation that applies to at least one of
+/// its props.
+pub fn names(entity: &Entity) -> Vec<&str> {
+    let mut names = vec![DEFAULT];
+    for scenario in &entity.scenarios {
+        names.push(scenario.name.as_str());
+    }
+    for violation in Violation::ALL {
+        if entity.props.iter().any(|prop| violation.applies_to(prop)) {
+            names.push(violation.name());
+        }
+    }
+    names
+}
+
+/// The response for scenario `name`, or `None` if the entity has
+/// no such scenario. Every scenario starts from the same seeded object, so
+/// they differ only where the scenario makes them differ.
+pub fn response(entity: &Entity, name: &str, config: &Config, seed: u64) -> Option<Value> {
+    if !names(entity).contains(&name) {
+        return None;
+    }
+    let mut rng = generate::rng_for(Some(seed), &entity.served_route(config.version_prefix));
+    if let Some(body) = entity
+        .scenarios
+        .iter()
+        .find(|s| s.name == name)
+        .and_then(|s| s.body.clone())
+    {
+        return Some(body);
+    }
+    let mut data = generate::fake_response(
+        entity,
+        config,
+        generate::Context::of(config),
+        None,
+        &mut rng,
+    );
+    if let Some(violation) = Violation::parse(name) {
+        let items = match &mut data {
+            Value::Array(items) => items.iter_mut().collect(),
+            data => vec![data],
+        };
+        for item in items {
+            for prop in entity.props.iter().filter(|p| violation.applies_to(p)) {
+                invalid::apply(item, prop, violation);
+            }
+        }
+    }
+    Some(data)
+}
+
+/// `GET /__diff/{route}?a=default&b=wrong-type`: how the two scenarios'
+/// responses differ, generated from `?seed=` (or `--seed`, or `0`).
+pub async fn serve_diff(
+    req: HttpRequest,
+    route: web::Path<String>,
+    entities: web::Data<Vec<Entity>>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    let query = query::params(&req);
+    let mut bad = BadParams::default();
+    let seed = bad
+        .read(&query, "seed", "a whole number", |raw| raw.parse().ok())
+        .unwrap_or(config.seed.unwrap_or(0));
+    if !bad.is_empty() {
+        return bad.response();
+    }
+
+    let route = format!("/{}", route.trim_start_matches('/'));
+    let Some(entity) = served_entity(&entities, &route, &config) else {
+        let routes: Vec<String> = route_groups(&entities)
+            .into_iter()
+            .flat_map(|(_, group)| served_routes(&group, &config))
+            .collect();
+        return HttpResponse::NotFound().json(json!({
+            "error": format!("unknown route `{route}`"),
+            "routes": routes,
+        }));
+    };
+
+    let scenario = |key: &str| query.get(key).map_or(DEFAULT, String::as_str);
+    let (a, b) = (scenario("a"), scenario("b"));
+    let responses = (
+        response(entity, a, &config, seed),
+        response(entity, b, &config, seed),
+    );
+    let (Some(a_data), Some(b_data)) = responses else {
+        let unknown = if names(entity).contains(&a) { b } else { a };
+        return HttpResponse::NotFound().json(json!({
+            "error": format!("unknown scenario `{unknown}` for `{route}`"),
+            "scenarios": names(entity),
+        }));
+    };
+    reply(
+        &req,
+        HttpResponse::Ok(),
+        &json!({
+            "route": route,
+            "a": a,
+            "b": b,
+            "seed": seed,
+            "diff": diff::diff(&a_data, &b_data),
+        }),
+        None,
+    )
+}
+
+/// The entity answering plain requests to `route`: under shared routes
+/// that's the one served without an `Accept` version.
+fn served_entity<'a>(entities: &'a [Entity], route: &str, config: &Config) -> Option<&'a Entity> {
+    route_groups(entities).into_iter().find_map(|(_, group)| {
+        if config.version_prefix || group.iter().all(|e| e.version.is_none()) {
+            group
+                .into_iter()
+                .find(|e| e.served_route(config.version_prefix) == route)
+        } else {
+            (group[0].route == route).then(|| versioning::default_entity(&group))
+        }
+    })
+}
+
+fn served_routes(group: &[&Entity], config: &Config) -> Vec<String> {
+    let mut routes: Vec<String> = group
+        .iter()
+        .map(|e| e.served_route(config.version_prefix))
+        .collect();
+    routes.dedup();
+    routes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use oxc::allocator::Allocator;
+
+    use super::*;
+    use crate::{parser, warning};
+
+    #[test]
+    fn scenarios_take_a_status_body_and_delay() {
+        let scenario =
+            Scenario::parse(r#"broken status=503 body={"error": "boom"} delay=2s"#).unwrap();
+        assert_eq!(scenario.name, "broken");
+        assert_eq!(scenario.status, Some(StatusCode::SERVICE_UNAVAILABLE));
+        assert_eq!(scenario.body, Some(json!({ "error": "boom" })));
+        assert_eq!(scenario.delay, Some(Duration::from_secs(2)));
+        let slow = Scenario::parse("slow delay=250").unwrap();
+        assert_eq!(slow.delay, Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn delays_that_are_endless_or_too_long_are_refused() {
+        for delay in ["1e30", "inf", "NaN", "99999999999999999999999h"] {
+            assert_eq!(
+                Scenario::parse(&format!("slow delay={delay}")).unwrap_err(),
+                format!("`delay`: invalid duration `{delay}`")
+            );
+        }
+    }
+
+    #[test]
+    fn a_bad_delay_warns_and_drops_the_scenario() {
+        let dir = std::env::temp_dir().join(format!("ssg-scenario-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let source = "/**\n * route /users\n * @scenario slow delay=1e30\n */\n\
+                      interface User { id: number; }\n";
+        fs::write(dir.join("users.ts"), source).unwrap();
+        let allocator = Allocator::default();
+        let scanned = parser::scan_dir(&dir, &allocator, &Config::default());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (entities, _) = scanned.unwrap();
+        assert!(entities[0].scenarios.is_empty());
+        let warning = "@scenario on `User`: `delay`: invalid duration `1e30`";
+        assert!(
+            warning::collected().iter().any(|w| w.ends_with(warning)),
+            "{:?}",
+            warning::collected()
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use std::{collections::HashMap, sync::Arc};
+
+use actix_web::{http::header, web, HttpRequest};
+use serde_json::Value;
+
+use crate::{format, Entity};
+
+/// Turns a response value into the body for one media type. Register more
+/// with `ServerOptions::serializer`; `Accept` picks between them.
+pub trait ResponseSerializer: Send + Sync {
+    /// The `Content-Type` sent, parameters included.
+    fn content_type(&self) -> &str;
+
+    fn serialize(&self, value: &Value) -> Result<Vec<u8>, String>;
+
+    /// The body under `?pretty`, when the format has an indented form.
+    fn serialize_pretty(&self, value: &Value) -> Result<Vec<u8>, String> {
+        self.serialize(value)
+    }
+}
+
+/// The default format.
+pub struct Json;
+
+impl ResponseSerializer for Json {
+    fn content_type(&self) -> &str {
+        format::JSON_UTF8
+    }
+
+    fn serialize(&self, value: &Value) -> Result<Vec<u8>, String> {
+        serde_json::to_vec(value).map_err(|err| err.to_string())
+    }
+
+    fn serialize_pretty(&self, value: &Value) -> Result<Vec<u8>, String> {
+        serde_json::to_vec_pretty(value).map_err(|err| err.to_string())
+    }
+}
+
+/// Objects as elements named by their keys and array items as `<item>`
+/// elements, all under a `<response>` root.
+pub struct Xml;
+
+impl ResponseSerializer for Xml {
+    fn content_type(&self) -> &str {
+        "application/xml; charset=utf-8"
+    }
+
+    fn serialize(&self, value: &Value) -> Result<Vec<u8>, String> {
+        Ok(xml_document(value, false))
+    }
+
+    fn serialize_pretty(&self, value: &Value) -> Result<Vec<u8>, String> {
+        Ok(xml_document(value, true))
+    }
+}
+
+fn xml_document(value: &Value, pretty: bool) -> Vec<u8> {
+    let mut out = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    xml_element(&mut out, "response", value, pretty.then_some(0));
+    out.push('\n');
+    out.into_bytes()
+}
+
+/// Writes `value` as a `<name>` element, on its own indented line when
+/// `depth` is given.
+fn xml_element(out: &mut String, name: &str, value: &Value, depth: Option<usize>) {
+    let name = xml_name(name);
+    let indent = |out: &mut String, depth: Option<usize>| {
+        if let Some(depth) = depth {
+            out.push('\n');
+            out.push_str(&"  ".repeat(depth));
+        }
+    };
+    indent(out, depth);
+    let children: Vec<(&str, &Value)> = match value {
+        Value::Null => {
+            out.push_str(&format!("<{name}/>"));
+            return;
+        }
+        Value::Object(fields) => fields.iter().map(|(k, v)| (k.as_str(), v)).collect(),
+        Value::Array(items) => items.iter().map(|item| ("item", item)).collect(),
+        Value::String(s) => {
+            out.push_str(&format!("<{name}>{}</{name}>", xml_escape(s)));
+            return;
+        }
+        scalar => {
+            out.push_str(&format!("<{name}>{scalar}</{name}>"));
+            return;
+        }
+    };
+    out.push_str(&format!("<{name}>"));
+    for (child, value) in &children {
+        xml_element(out, child, value, depth.map(|depth| depth + 1));
+    }
+    if !children.is_empty() {
+        indent(out, depth);
+    }
+    out.push_str(&format!("</{name}>"));
+}
+
+/// `name` with characters XML doesn't allow in element names replaced by
+/// `_`, and a leading `_` when it would start with a digit or be empty.
+fn xml_name(name: &str) -> String {
+    let mut xml: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || "_-.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if !xml.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        xml.insert(0, '_');
+    }
+    xml
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Serializers by media type, without parameters and in lowercase.
+pub struct Serializers(HashMap<String, Arc<dyn ResponseSerializer>>);
+
+impl Default for Serializers {
+    fn default() -> Self {
+        let mut serializers = Serializers(HashMap::new());
+        serializers.register(Arc::new(Json));
+        serializers.register(Arc::new(Xml));
+        serializers
+    }
+}
+
+impl Serializers {
+    /// Adds `serializer` under its media type, replacing any earlier one.
+    pub fn register(&mut self, serializer: Arc<dyn ResponseSerializer>) {
+        self.0
+            .insert(essence(serializer.content_type()), serializer);
+    }
+
+    pub fn contains(&self, media_type: &str) -> bool {
+        self.0.contains_key(&essence(media_type))
+    }
+
+    /// Fails on the first entity whose `@format` has no serializer, so the
+    /// server refuses to start rather than answer it with JSON.
+    pub fn check_formats(&self, entities: &[Entity]) -> Result<(), String> {
+        for entity in entities {
+            if let Some(format) = entity.format.as_deref() {
+                if !self.contains(format) {
+                    return Err(format!(
+                        "no serializer for @format {format} on `{}`",
+                        entity.name
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The media type `@format` names: `json` and `xml` for short, or any
+/// media type a serializer is registered for.
+pub fn format_media_type(format: &str) -> String {
+    match format.to_ascii_lowercase().as_str() {
+        "json" => "application/json".to_string(),
+        "xml" => "application/xml".to_string(),
+        media_type => media_type.to_string(),
+    }
+}
+
+/// The serializer for the entity's `@format` when it has one, whatever
+/// `Accept` says. Otherwise the first `Accept` entry that has one, ignoring
+/// quality values; JSON when none does.
+pub fn negotiate(req: &HttpRequest) -> Arc<dyn ResponseSerializer> {
+    let registered = req.app_data::<web::Data<Serializers>>();
+    let forced = req
+        .app_data::<web::Data<Entity>>()
+        .and_then(|entity| entity.format.as_deref())
+        .zip(registered)
+        .and_then(|(format, serializers)| serializers.0.get(&essence(format)).cloned());
+    if let Some(serializer) = forced {
+        return serializer;
+    }
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .zip(registered)
+        .and_then(|(accept, serializers)| {
+            accept
+                .split(',')
+                .find_map(|entry| serializers.0.get(&essence(entry)).cloned())
+        })
+        .unwrap_or_else(|| Arc::new(Json))
+}
+
+fn essence(media_type: &str) -> String {
+    media_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::header, test as http, App};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{cli::Config, register_route, Prop, ServerOptions, TProp};
+
+    /// Objects as a header row of their keys and a row of their values.
+    struct Csv;
+
+    impl ResponseSerializer for Csv {
+        fn content_type(&self) -> &str {
+            "text/csv; charset=utf-8"
+        }
+
+        fn serialize(&self, value: &Value) -> Result<Vec<u8>, String> {
+            let object = value.as_object().ok_or("expected an object")?;
+            let keys: Vec<&str> = object.keys().map(String::as_str).collect();
+            let values: Vec<String> = object.values().map(Value::to_string).collect();
+            Ok(format!("{}\n{}\n", keys.join(","), values.join(",")).into_bytes())
+        }
+    }
+
+    /// A user whose props each have one possible value.
+    fn user(format: Option<&str>) -> Entity {
+        let prop = |id: &str, value: Value| Prop {
+            id: id.to_string(),
+            ty: TProp::Enum(vec![value]),
+            ..Prop::default()
+        };
+        Entity {
+            name: "User".to_string(),
+            route: "/users".to_string(),
+            format: format.map(format_media_type),
+            props: vec![prop("id", json!(1)), prop("name", json!("ann"))],
+            ..Entity::default()
+        }
+    }
+
+    /// The content type and body `entity` answers a GET with under `accept`.
+    async fn get(entity: Entity, accept: Option<&str>) -> (String, String) {
+        let (_, _, serializers) = ServerOptions::default().serializer(Csv).into_parts();
+        let config = web::Data::new(Config::default());
+        let app = App::new()
+            .app_data(config.clone())
+            .app_data(web::Data::new(serializers));
+        let app = http::init_service(register_route(app, "/users", &[&entity], &config)).await;
+        let mut req = http::TestRequest::get().uri("/users");
+        if let Some(accept) = accept {
+            req = req.insert_header((header::ACCEPT, accept));
+        }
+        let res = http::call_service(&app, req.to_request()).await;
+        let content_type = res.headers().get(header::CONTENT_TYPE).unwrap();
+        let content_type = content_type.to_str().unwrap().to_string();
+        let body = http::read_body(res).await;
+        (content_type, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[actix_web::test]
+    async fn accept_picks_a_registered_serializer() {
+        assert_eq!(
+            get(user(None), Some("text/html, text/csv;q=0.9")).await,
+            (
+                "text/csv; charset=utf-8".to_string(),
+                "id,name\n1,\"ann\"\n".to_string()
+            )
+        );
+    }
+
+    #[actix_web::test]
+    async fn json_answers_when_accept_names_no_serializer() {
+        for accept in [None, Some("text/html"), Some("application/json")] {
+            let (content_type, body) = get(user(None), accept).await;
+            assert_eq!(content_type, format::JSON_UTF8, "{accept:?}");
+            assert_eq!(body, r#"{"id":1,"name":"ann"}"#, "{accept:?}");
+        }
+    }
+
+    #[actix_web::test]
+    async fn format_forces_its_serializer_over_accept() {
+        let (content_type, body) = get(user(Some("text/csv")), Some("application/json")).await;
+        assert_eq!(content_type, "text/csv; charset=utf-8");
+        assert_eq!(body, "id,name\n1,\"ann\"\n");
+    }
+
+    #[test]
+    fn formats_without_a_serializer_are_refused() {
+        let (_, _, serializers) = ServerOptions::default().into_parts();
+        assert_eq!(
+            serializers.check_formats(&[user(Some("xml")), user(Some("text/csv"))]),
+            Err("no serializer for @format text/csv on `User`".to_string())
+        );
+        let (_, _, serializers) = ServerOptions::default().serializer(Csv).into_parts();
+        assert_eq!(serializers.check_formats(&[user(Some("text/csv"))]), Ok(()));
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// `user.ts.ssg` annotates the declarations in `user.ts`.
+pub const EXTENSION: &str = "ssg";
+/// Project file whose `[entities."path/to/file.ts".Name]` tables do the same
+/// for any source, relative to the scan root.
+pub const PROJECT_FILE: &str = "ssg.toml";
+
+/// What a sidecar says about one declaration, written as the comments on
+/// it would be.
+#[derive(Debug, Clone, Default)]
+pub struct Annotations {
+    /// The route line without its keyword: `/users version=2`.
+    pub route: Option<String>,
+    /// Comment text for the declaration: prose and `@tag value` lines.
+    pub doc: String,
+    /// Comment text for each prop, by name.
+    pub props: HashMap<String, String>,
+    /// The file these came from, for messages.
+    pub origin: PathBuf,
+}
+
+/// Annotations by declaration name.
+pub type Sidecar = HashMap<String, Annotations>;
+
+/// Reads `{source}.ssg`, `None` when there isn't one.
+pub fn read_file(source: &Path) -> Result<Option<Sidecar>, String> {
+    let mut path = source.as_os_str().to_owned();
+    path.push(".");
+    path.push(EXTENSION);
+    let path = PathBuf::from(path);
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Ok(None);
+    };
+    let table: toml::Table = text
+        .parse()
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+    parse_sidecar(&table, &path).map(Some)
+}
+
+/// Reads the `entities` of `root/ssg.toml` into a sidecar per source file.
+pub fn read_project_file(root: &Path) -> Result<HashMap<PathBuf, Sidecar>, String> {
+    let path = root.join(PROJECT_FILE);
+    let Ok(text) = fs::read_to_string(&path) else {
+        return Ok(HashMap::new());
+    };
+    let table: toml::Table = text
+        .parse()
+        .map_err(|err| format!("{}: {err}", path.display()))?;
+    let Some(entities) = table.get("entities") else {
+        return Ok(HashMap::new());
+    };
+    let entities = entities
+        .as_table()
+        .ok_or_else(|| format!("{}: `entities` must be a table", path.display()))?;
+    entities
+        .iter()
+        .map(|(source, declarations)| {
+            let declarations = declarations.as_table().ok_or_else(|| {
+                format!(
+                    "{}: `entities.\"{source}\"` must be a table",
+                    path.display()
+                )
+            })?;
+            Ok((root.join(source), parse_sidecar(declarations, &path)?))
+        })
+        .collect()
+}
+
+fn parse_sidecar(table: &toml::Table, path: &Path) -> Result<Sidecar, String> {
+    table
+        .iter()
+        .map(|(name, value)| {
+            let invalid = |what: &str| format!("{}: `{name}`: {what}", path.display());
+            let table = value
+                .as_table()
+                .ok_or_else(|| invalid("expected a table"))?;
+            let mut annotations = Annotations {
+                origin: path.to_path_buf(),
+                ..Default::default()
+            };
+            for (key, value) in table {
+                match (key.as_str(), value) {
+                    ("route", toml::Value::String(route)) => {
+                        annotations.route = Some(route.clone())
+                    }
+                    ("doc", toml::Value::String(doc)) => annotations.doc = doc.clone(),
+                    ("props", toml::Value::Table(props)) => {
+                        for (prop, doc) in props {
+                            let doc = doc.as_str().ok_or_else(|| {
+                                invalid(&format!("`props.{prop}` must be a string"))
+                            })?;
+                            annotations.props.insert(prop.clone(), doc.to_string());
+                        }
+                    }
+                    ("route" | "doc", _) => {
+                        return Err(invalid(&format!("`{key}` must be a string")))
+                    }
+                    ("props", _) => return Err(invalid("`props` must be a table")),
+                    _ => return Err(invalid(&format!("unknown key `{key}`"))),
+                }
+            }
+            Ok((name.clone(), annotations))
+        })
+        .collect()
+}
+
+/// Combines the sidecars given for one source, rejecting declarations
+/// annotated by more than one.
+pub fn merge(sidecars: impl IntoIterator<Item = Sidecar>) -> Result<Sidecar, String> {
+    let mut merged = Sidecar::new();
+    for sidecar in sidecars {
+        for (name, annotations) in sidecar {
+            if let Some(existing) = merged.get(&name) {
+                return Err(format!(
+                    "`{name}` is annotated in both {} and {}",
+                    existing.origin.display(),
+                    annotations.origin.display()
+                ));
+            }
+            merged.insert(name, annotations);
+        }
+    }
+    Ok(merged)
+}
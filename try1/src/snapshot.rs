@@ -0,0 +1,205 @@
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde_json::{Map, Value};
+
+use crate::{cli::Config, generate, Entity};
+
+/// Seed used for snapshots when `--seed` isn't given, so output is stable.
+const DEFAULT_SEED: u64 = 0;
+
+/// Writes the seeded response of every route under `out`, one file per route.
+pub fn write(entities: &[Entity], config: &Config, out: &Path) -> Result<(), Box<dyn Error>> {
+    for entity in entities {
+        let path = snapshot_path(out, &entity.served_route(true));
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, render(entity, config)?)?;
+        println!("wrote {}", path.display());
+    }
+    Ok(())
+}
+
+/// Regenerates every snapshot and compares it against `out`. Returns whether
+/// everything matched, printing a unified diff for each mismatch.
+pub fn check(entities: &[Entity], config: &Config, out: &Path) -> Result<bool, Box<dyn Error>> {
+    let mut ok = true;
+    let mut expected_files = Vec::new();
+
+    for entity in entities {
+        let path = snapshot_path(out, &entity.served_route(true));
+        let actual = render(entity, config)?;
+        match fs::read_to_string(&path) {
+            Ok(saved) if saved == actual => (),
+            Ok(saved) => {
+                ok = false;
+                println!("--- {}", path.display());
+                println!("+++ {} (regenerated)", entity.route);
+                print!("{}", unified_diff(&saved, &actual, 3));
+            }
+            Err(_) => {
+                ok = false;
+                println!("missing snapshot for {} ({})", entity.route, path.display());
+            }
+        }
+        expected_files.push(path);
+    }
+
+    for stale in list_json_files(out)? {
+        if !expected_files.contains(&stale) {
+            ok = false;
+            println!("stale snapshot {} has no matching route", stale.display());
+        }
+    }
+
+    Ok(ok)
+}
+
+fn render(entity: &Entity, config: &Config) -> Result<String, Box<dyn Error>> {
+    let mut rng = generate::rng_for(Some(config.seed.unwrap_or(DEFAULT_SEED)), &entity.route);
+    let value = sort_keys(generate::fake_response(
+        entity,
+        config,
+        generate::Context::of(config),
+        None,
+        &mut rng,
+    ));
+    Ok(serde_json::to_string_pretty(&value)? + "\n")
+}
+
+/// `/api/users` is stored at `<out>/api/users.json`, `/` at `<out>/index.json`.
+/// Versioned entities are keyed by their prefixed route (`/v2/users`), and a
+/// wildcard route `/api/*` is stored as `<out>/api/_.json`.
+fn snapshot_path(out: &Path, route: &str) -> PathBuf {
+    let route = route.trim_matches('/').replace('*', "_");
+    if route.is_empty() {
+        out.join("index.json")
+    } else {
+        out.join(format!("{route}.json"))
+    }
+}
+
+fn list_json_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    if !dir.exists() {
+        return Ok(files);
+    }
+    let mut dirs_to_visit = vec![dir.to_path_buf()];
+    while let Some(current_dir) = dirs_to_visit.pop() {
+        for entry in fs::read_dir(&current_dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs_to_visit.push(path);
+            } else if path.extension().is_some_and(|ext| ext == "json") {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k, sort_keys(v)))
+                    .collect::<Map<_, _>>(),
+            )
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
+
+enum Line<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Line-based unified diff (LCS) with `context` lines around each hunk.
+fn unified_diff(old: &str, new: &str, context: usize) -> String {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            lines.push(Line::Same(a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(Line::Removed(a[i]));
+            i += 1;
+        } else {
+            lines.push(Line::Added(b[j]));
+            j += 1;
+        }
+    }
+
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| !matches!(l, Line::Same(_)))
+        .map(|(idx, _)| idx)
+        .collect();
+
+    let mut out = String::new();
+    let mut idx = 0;
+    while idx < changed.len() {
+        let start = changed[idx].saturating_sub(context);
+        let mut end = changed[idx];
+        while idx < changed.len() && changed[idx] <= end + 2 * context {
+            end = changed[idx];
+            idx += 1;
+        }
+        let end = (end + context + 1).min(lines.len());
+
+        let old_start = 1 + lines[..start]
+            .iter()
+            .filter(|l| !matches!(l, Line::Added(_)))
+            .count();
+        let new_start = 1 + lines[..start]
+            .iter()
+            .filter(|l| !matches!(l, Line::Removed(_)))
+            .count();
+        let hunk = &lines[start..end];
+        let old_len = hunk.iter().filter(|l| !matches!(l, Line::Added(_))).count();
+        let new_len = hunk
+            .iter()
+            .filter(|l| !matches!(l, Line::Removed(_)))
+            .count();
+
+        out.push_str(&format!(
+            "@@ -{old_start},{old_len} +{new_start},{new_len} @@\n"
+        ));
+        for line in hunk {
+            match line {
+                Line::Same(s) => out.push_str(&format!(" {s}\n")),
+                Line::Removed(s) => out.push_str(&format!("-{s}\n")),
+                Line::Added(s) => out.push_str(&format!("+{s}\n")),
+            }
+        }
+    }
+    out
+}
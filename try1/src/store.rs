@@ -0,0 +1,843 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{http::header::LOCATION, web, HttpRequest, HttpResponse};
+use serde_json::{json, Value};
+
+use crate::{
+    cli::Config,
+    concurrency, date, generate, hooks, overrides,
+    query::{self, CollectionQuery},
+    reply, reply_entity, throttle, validation, wildcard_prefix, Entity, Prop, TProp,
+};
+
+/// When a `@soft-delete` item was deleted; `null` while it's live.
+pub const DELETED_AT: &str = "deletedAt";
+
+/// Request header that reads a `@consistency-lag` collection as it stands.
+pub const CONSISTENT_HEADER: &str = "X-Mock-Consistent";
+
+/// Seed used for the initial dataset when `--seed` isn't given, so every
+/// start (and every reset) begins from the same items.
+pub const DEFAULT_SEED: u64 = 0;
+
+/// In-memory collections for `--stateful`, one per served entity.
+#[derive(Default)]
+pub struct Store {
+    collections: Mutex<HashMap<String, Collection>>,
+}
+
+struct Collection {
+    items: Vec<Value>,
+    next_id: u64,
+    /// Writes reads don't see yet, under a consistency lag.
+    lagging: Vec<Lagging>,
+}
+
+/// A written item reads don't see until `until`: they get `previous`
+/// instead, or no item at all when the write created it.
+struct Lagging {
+    key: Value,
+    until: Instant,
+    previous: Option<Value>,
+}
+
+impl Collection {
+    fn new(items: Vec<Value>, next_id: u64) -> Self {
+        Collection {
+            items,
+            next_id,
+            lagging: Vec::new(),
+        }
+    }
+
+    /// Holds the write to the item keyed `key` back from reads for `lag`.
+    /// A write during another's lag extends it, and reads keep seeing the
+    /// item from before the first one. A lag too long for an `Instant`
+    /// holds the write back for good.
+    fn lag(&mut self, key: &Value, lag: Duration, previous: impl FnOnce() -> Option<Value>) {
+        if lag.is_zero() {
+            return;
+        }
+        let now = Instant::now();
+        self.lagging.retain(|lagging| lagging.until > now);
+        let until = now
+            .checked_add(lag)
+            .unwrap_or_else(|| now + Duration::from_secs(u32::MAX.into()));
+        match self
+            .lagging
+            .iter_mut()
+            .find(|lagging| query::same_value(&lagging.key, key))
+        {
+            Some(lagging) => lagging.until = until,
+            None => self.lagging.push(Lagging {
+                key: key.clone(),
+                until,
+                previous: previous(),
+            }),
+        }
+    }
+
+    /// `item` as a read sees it: the version from before a lagging write,
+    /// or nothing when that write created it. `consistent` reads see every
+    /// write at once.
+    fn visible(&self, key: &str, item: &Value, consistent: bool) -> Option<Value> {
+        let now = Instant::now();
+        let lagging = self.lagging.iter().find(|lagging| {
+            !consistent && lagging.until > now && query::same_value(&lagging.key, &item[key])
+        });
+        match lagging {
+            Some(lagging) => lagging.previous.clone(),
+            None => Some(item.clone()),
+        }
+    }
+}
+
+/// How long `entity`'s writes stay unseen: its `@consistency-lag`, or
+/// `--consistency-lag`.
+fn lag_for(entity: &Entity, config: &Config) -> Duration {
+    entity.consistency_lag.unwrap_or(config.consistency_lag)
+}
+
+/// Whether a read asked to skip the lag with `X-Mock-Consistent: true`.
+fn consistent(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(CONSISTENT_HEADER)
+        .is_some_and(|value| value.as_bytes().eq_ignore_ascii_case(b"true"))
+}
+
+/// Entities are stored by their served path with the version folded in, so
+/// versions sharing a route keep separate data.
+fn collection_key(entity: &Entity) -> String {
+    entity.served_route(true)
+}
+
+impl Store {
+    /// Replaces every collection with freshly seeded items and reports how
+    /// many each one holds.
+    pub fn reset(&self, entities: &[Entity], config: &Config) -> Vec<Value> {
+        let mut collections = self.collections.lock().unwrap();
+        collections.clear();
+        entities
+            .iter()
+            .map(|entity| {
+                let collection = seed(entity, config);
+                let summary = json!({
+                    "name": entity.name,
+                    "route": entity.served_route(config.version_prefix),
+                    "count": collection.items.len(),
+                });
+                collections.insert(collection_key(entity), collection);
+                summary
+            })
+            .collect()
+    }
+}
+
+fn seed(entity: &Entity, config: &Config) -> Collection {
+    Collection::new(
+        seed_items(entity, config).collect(),
+        config.seed_count as u64 + 1,
+    )
+}
+
+/// The initial items of an entity's collection, generated one at a time.
+fn seed_items<'a>(entity: &'a Entity, config: &'a Config) -> impl Iterator<Item = Value> + 'a {
+    let key = collection_key(entity);
+    (1..=config.seed_count as u64).map(move |n| {
+        let mut rng = generate::rng_for(
+            Some(config.seed.unwrap_or(DEFAULT_SEED)),
+            &format!("{key}#{n}"),
+        );
+        let mut item =
+            generate::fake_object(entity, config, generate::Context::of(config), &mut rng);
+        if let Some(id) = id_value(entity, config, n) {
+            item[entity.key()] = id;
+        }
+        if entity.soft_delete {
+            item[DELETED_AT] = Value::Null;
+        }
+        concurrency::stamp(entity, &mut item, 0);
+        item
+    })
+}
+
+/// Writes the seeded collections to `path` as one JSON object keyed by
+/// route, the same data `--stateful` starts from. Items are serialized as
+/// they're generated, so memory stays flat however large the dump gets.
+pub fn dump(entities: &[Entity], config: &Config, path: &Path) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let collections = entities
+        .iter()
+        .filter(|entity| wildcard_prefix(&entity.route).is_none())
+        .map(|entity| (collection_key(entity), seed_items(entity, config)));
+    write_collections(&mut out, collections)?;
+    out.flush()
+}
+
+/// Writes `collections` as one JSON object of arrays, pulling each item
+/// only once the one before it has been written out.
+fn write_collections<I: Iterator<Item = Value>>(
+    out: &mut impl Write,
+    collections: impl Iterator<Item = (String, I)>,
+) -> io::Result<()> {
+    out.write_all(b"{")?;
+    for (i, (key, items)) in collections.enumerate() {
+        if i > 0 {
+            out.write_all(b",")?;
+        }
+        serde_json::to_writer(&mut *out, &key)?;
+        out.write_all(b": [")?;
+        for (j, item) in items.enumerate() {
+            out.write_all(if j > 0 { b",\n" } else { b"\n" })?;
+            serde_json::to_writer(&mut *out, &item)?;
+        }
+        out.write_all(b"\n]")?;
+    }
+    out.write_all(b"}\n")
+}
+
+/// The key for the `n`th item, shaped like the entity's key prop. An
+/// `@key` with a `@faker` keeps the values it generates, such as UUIDs.
+fn id_value(entity: &Entity, config: &Config, n: u64) -> Option<Value> {
+    let prop = key_prop(entity)?;
+    if prop.key && prop.faker.is_some() {
+        return None;
+    }
+    match prop.ty {
+        TProp::String => Some(json!(n.to_string())),
+        TProp::Number | TProp::BigInt if generate::as_string(prop, config) => {
+            Some(json!(n.to_string()))
+        }
+        TProp::Number | TProp::BigInt => Some(json!(n)),
+        _ => None,
+    }
+}
+
+fn key_prop(entity: &Entity) -> Option<&Prop> {
+    let key = entity.key();
+    entity.props.iter().find(|prop| prop.id == key)
+}
+
+/// The item a detail route's path segment names: the segment read as the
+/// key prop's type, so `/users/01` finds id `1` and `/users/abc` is a 400
+/// when ids are numbers. The segment is the route's param named after the
+/// key, so params earlier in the route (`/users/{userId}/posts/{id}`) don't
+/// get in its way.
+struct Lookup<'e> {
+    key: &'e str,
+    value: Value,
+}
+
+impl<'e> Lookup<'e> {
+    fn new(entity: &'e Entity, config: &Config, req: &HttpRequest) -> Result<Self, HttpResponse> {
+        let segment = req.match_info().get(entity.key()).unwrap_or_default();
+        let value = match key_prop(entity) {
+            Some(prop) => query::coerce(prop, config, segment).map_err(|expected| {
+                HttpResponse::BadRequest().json(json!({
+                    "error": format!("`{segment}` isn't a valid `{}`: expected {expected}", prop.id),
+                }))
+            })?,
+            None => Value::String(segment.to_string()),
+        };
+        Ok(Lookup {
+            key: entity.key(),
+            value,
+        })
+    }
+
+    fn matches(&self, item: &Value) -> bool {
+        query::same_value(&item[self.key], &self.value)
+    }
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(json!({ "error": "not found" }))
+}
+
+fn is_deleted(entity: &Entity, item: &Value) -> bool {
+    entity.soft_delete && !item[DELETED_AT].is_null()
+}
+
+fn gone(item: &Value) -> HttpResponse {
+    HttpResponse::Gone().json(json!({
+        "error": "deleted",
+        DELETED_AT: item[DELETED_AT],
+    }))
+}
+
+pub async fn list(
+    req: HttpRequest,
+    entity: web::Data<Entity>,
+    store: web::Data<Store>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    let bandwidth = match throttle::bandwidth_for(&req, &entity, &config) {
+        Ok(bandwidth) => bandwidth,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    let query = match CollectionQuery::requested(&req, &entity, &config) {
+        Ok(query) => query,
+        Err(bad) => return bad.response(),
+    };
+    let patch = match overrides::requested(&req) {
+        Ok(patch) => patch,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    let collections = store.collections.lock().unwrap();
+    let consistent = consistent(&req);
+    let mut items: Vec<Value> = collections
+        .get(&collection_key(&entity))
+        .map_or(Vec::new(), |c| {
+            c.items
+                .iter()
+                .filter_map(|item| c.visible(entity.key(), item, consistent))
+                .filter(|item| query.include_deleted || !is_deleted(&entity, item))
+                .collect()
+        });
+    drop(collections);
+    let mut response = HttpResponse::Ok();
+    if !query.is_empty() {
+        let (page, total) = query.apply(items);
+        items = page;
+        query.describe(&mut response, &req, total);
+    }
+    for item in &mut items {
+        hooks::transform(&req, &entity, item);
+        if let Some(patch) = &patch {
+            overrides::apply(item, patch);
+        }
+    }
+    let mut items = Value::Array(items);
+    if let Some(status) = hooks::run(&req, &entity.route, &mut items) {
+        response.status(status);
+    }
+    reply(&req, response, &items, bandwidth)
+}
+
+pub async fn fetch(
+    req: HttpRequest,
+    entity: web::Data<Entity>,
+    store: web::Data<Store>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    let bandwidth = match throttle::bandwidth_for(&req, &entity, &config) {
+        Ok(bandwidth) => bandwidth,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    let lookup = match Lookup::new(&entity, &config, &req) {
+        Ok(lookup) => lookup,
+        Err(response) => return response,
+    };
+    let patch = match overrides::requested(&req) {
+        Ok(patch) => patch,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    let collections = store.collections.lock().unwrap();
+    let item = collections.get(&collection_key(&entity)).and_then(|c| {
+        let item = c.items.iter().find(|item| lookup.matches(item))?;
+        c.visible(lookup.key, item, consistent(&req))
+    });
+    drop(collections);
+    match item {
+        Some(item) if is_deleted(&entity, &item) => gone(&item),
+        Some(mut item) => {
+            let mut response = HttpResponse::Ok();
+            concurrency::tag(&mut response, &entity, &item);
+            hooks::transform(&req, &entity, &mut item);
+            if let Some(patch) = &patch {
+                overrides::apply(&mut item, patch);
+            }
+            if let Some(status) = hooks::run(&req, &entity.route, &mut item) {
+                response.status(status);
+            }
+            reply_entity(&req, response, &entity, &item, bandwidth)
+        }
+        None => not_found(),
+    }
+}
+
+/// Adds the submitted fields over a generated item, assigning the next id
+/// unless the body brings its own.
+pub async fn create(
+    req: HttpRequest,
+    entity: web::Data<Entity>,
+    store: web::Data<Store>,
+    config: web::Data<Config>,
+    body: web::Json<Value>,
+) -> HttpResponse {
+    let failures = validation::check(&entity, &body, &config, validation::Body::Partial);
+    if !failures.is_empty() {
+        return validation::reject(&req, &entity, &body, failures);
+    }
+    let Value::Object(fields) = body.into_inner() else {
+        return HttpResponse::BadRequest().json(json!({ "error": "expected a JSON object" }));
+    };
+    let ctx = match generate::requested(&req, &config) {
+        Ok(ctx) => ctx,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    let mut collections = store.collections.lock().unwrap();
+    let collection = collections
+        .entry(collection_key(&entity))
+        .or_insert_with(|| Collection::new(Vec::new(), 1));
+
+    let mut rng = generate::rng_for(config.seed, req.path());
+    let mut item = generate::fake_object(&entity, &config, ctx, &mut rng);
+    let key = entity.key();
+    if let Some(id) = id_value(&entity, &config, collection.next_id) {
+        item[key] = id;
+    }
+    if let Some(data) = item.as_object_mut() {
+        data.extend(fields);
+    }
+    if entity.soft_delete {
+        item[DELETED_AT] = Value::Null;
+    }
+    concurrency::stamp(&entity, &mut item, 0);
+
+    let id = query::as_text(&item[key]);
+    if let Some(id) = &id {
+        if collection
+            .items
+            .iter()
+            .any(|existing| query::same_value(&existing[key], &item[key]))
+        {
+            return HttpResponse::Conflict()
+                .json(json!({ "error": format!("`{id}` already exists") }));
+        }
+    }
+    collection.next_id += 1;
+    collection.items.push(item.clone());
+    collection.lag(&item[key], lag_for(&entity, &config), || None);
+
+    let mut response = HttpResponse::Created();
+    concurrency::tag(&mut response, &entity, &item);
+    if let Some(id) = id {
+        response.insert_header((
+            LOCATION,
+            format!("{}/{id}", req.path().trim_end_matches('/')),
+        ));
+    }
+    hooks::transform(&req, &entity, &mut item);
+    if let Some(status) = hooks::run(&req, &entity.route, &mut item) {
+        response.status(status);
+    }
+    reply(&req, response, &item, None)
+}
+
+/// Replaces an existing item with the body, keeping its id. Unlike PATCH,
+/// the body has to send every required prop, or it's a 422 naming the
+/// missing ones. Soft-deleted items are gone and can't be replaced.
+pub async fn replace(
+    req: HttpRequest,
+    entity: web::Data<Entity>,
+    store: web::Data<Store>,
+    config: web::Data<Config>,
+    body: web::Json<Value>,
+) -> HttpResponse {
+    let failures = validation::check(&entity, &body, &config, validation::Body::Whole);
+    if !failures.is_empty() {
+        return validation::reject(&req, &entity, &body, failures);
+    }
+    let Value::Object(fields) = body.into_inner() else {
+        return HttpResponse::BadRequest().json(json!({ "error": "expected a JSON object" }));
+    };
+    let lookup = match Lookup::new(&entity, &config, &req) {
+        Ok(lookup) => lookup,
+        Err(response) => return response,
+    };
+    let mut collections = store.collections.lock().unwrap();
+    let Some(collection) = collections.get_mut(&collection_key(&entity)) else {
+        return not_found();
+    };
+    let Some(item) = collection
+        .items
+        .iter_mut()
+        .find(|item| lookup.matches(item))
+    else {
+        return not_found();
+    };
+    if is_deleted(&entity, item) {
+        return gone(item);
+    }
+    let lag = lag_for(&entity, &config);
+    let before = (!lag.is_zero()).then(|| item.clone());
+    if let Err(response) =
+        concurrency::check(&req, &entity, item, fields.get(concurrency::VERSION_PROP))
+    {
+        return response;
+    }
+    let previous = concurrency::version(item);
+    let key = item[lookup.key].take();
+    *item = Value::Object(fields);
+    item[lookup.key] = key;
+    if entity.soft_delete {
+        item[DELETED_AT] = Value::Null;
+    }
+    concurrency::stamp(&entity, item, previous);
+    let mut response = HttpResponse::Ok();
+    concurrency::tag(&mut response, &entity, item);
+    let mut item = item.clone();
+    collection.lag(&item[lookup.key], lag, || before);
+    drop(collections);
+    hooks::transform(&req, &entity, &mut item);
+    if let Some(status) = hooks::run(&req, &entity.route, &mut item) {
+        response.status(status);
+    }
+    reply(&req, response, &item, None)
+}
+
+/// Merges the body into an existing item: objects merge key by key, any
+/// other value replaces what was there. The id (and `deletedAt`) stay.
+pub async fn update(
+    req: HttpRequest,
+    entity: web::Data<Entity>,
+    store: web::Data<Store>,
+    config: web::Data<Config>,
+    body: web::Json<Value>,
+) -> HttpResponse {
+    let failures = validation::check(&entity, &body, &config, validation::Body::Partial);
+    if !failures.is_empty() {
+        return validation::reject(&req, &entity, &body, failures);
+    }
+    let lookup = match Lookup::new(&entity, &config, &req) {
+        Ok(lookup) => lookup,
+        Err(response) => return response,
+    };
+    let mut collections = store.collections.lock().unwrap();
+    let Some(collection) = collections.get_mut(&collection_key(&entity)) else {
+        return not_found();
+    };
+    let Some(item) = collection
+        .items
+        .iter_mut()
+        .find(|item| lookup.matches(item))
+    else {
+        return not_found();
+    };
+    if is_deleted(&entity, item) {
+        return gone(item);
+    }
+    let lag = lag_for(&entity, &config);
+    let before = (!lag.is_zero()).then(|| item.clone());
+    if let Err(response) =
+        concurrency::check(&req, &entity, item, body.get(concurrency::VERSION_PROP))
+    {
+        return response;
+    }
+    let previous = concurrency::version(item);
+    let key = item[lookup.key].clone();
+    let deleted_at = item.get(DELETED_AT).cloned();
+    merge(item, body.into_inner());
+    item[lookup.key] = key;
+    if let Some(deleted_at) = deleted_at {
+        item[DELETED_AT] = deleted_at;
+    }
+    concurrency::stamp(&entity, item, previous);
+    let mut response = HttpResponse::Ok();
+    concurrency::tag(&mut response, &entity, item);
+    let mut item = item.clone();
+    collection.lag(&item[lookup.key], lag, || before);
+    drop(collections);
+    hooks::transform(&req, &entity, &mut item);
+    if let Some(status) = hooks::run(&req, &entity.route, &mut item) {
+        response.status(status);
+    }
+    reply(&req, response, &item, None)
+}
+
+/// Deep-merges `patch` into `target`, as PATCH does to a stored item.
+pub fn merge(target: &mut Value, patch: Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
+
+/// Removes an item, or under `@soft-delete` stamps its `deletedAt`.
+pub async fn remove(
+    req: HttpRequest,
+    entity: web::Data<Entity>,
+    store: web::Data<Store>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    if !entity.soft_delete {
+        return purge(req, entity, store, config).await;
+    }
+    let ctx = match generate::requested(&req, &config) {
+        Ok(ctx) => ctx,
+        Err(error) => return HttpResponse::BadRequest().json(json!({ "error": error })),
+    };
+    let lookup = match Lookup::new(&entity, &config, &req) {
+        Ok(lookup) => lookup,
+        Err(response) => return response,
+    };
+    let mut collections = store.collections.lock().unwrap();
+    let Some(collection) = collections.get_mut(&collection_key(&entity)) else {
+        return not_found();
+    };
+    let Some(item) = collection
+        .items
+        .iter_mut()
+        .find(|item| lookup.matches(item))
+    else {
+        return not_found();
+    };
+    if is_deleted(&entity, item) {
+        return gone(item);
+    }
+    let lag = lag_for(&entity, &config);
+    let before = (!lag.is_zero()).then(|| item.clone());
+    item[DELETED_AT] = json!(date::format(ctx.now));
+    let key = item[lookup.key].clone();
+    collection.lag(&key, lag, || before);
+    HttpResponse::NoContent().finish()
+}
+
+/// Removes an item for good, soft-deleted or not: `DELETE {id}/purge`.
+pub async fn purge(
+    req: HttpRequest,
+    entity: web::Data<Entity>,
+    store: web::Data<Store>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    let lookup = match Lookup::new(&entity, &config, &req) {
+        Ok(lookup) => lookup,
+        Err(response) => return response,
+    };
+    let mut collections = store.collections.lock().unwrap();
+    let Some(collection) = collections.get_mut(&collection_key(&entity)) else {
+        return not_found();
+    };
+    match collection
+        .items
+        .iter()
+        .position(|item| lookup.matches(item))
+    {
+        Some(index) => {
+            let item = collection.items.remove(index);
+            collection
+                .lagging
+                .retain(|lagging| !query::same_value(&lagging.key, &item[lookup.key]));
+            HttpResponse::NoContent().finish()
+        }
+        None => not_found(),
+    }
+}
+
+/// Clears and re-seeds every collection from the configured seed.
+pub async fn reset(
+    store: web::Data<Store>,
+    entities: web::Data<Vec<Entity>>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    let collections = store.reset(&entities, &config);
+    HttpResponse::Ok().json(json!({ "collections": collections }))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test as http, App};
+
+    use super::*;
+    use crate::{register_route, Prop};
+
+    fn prop(id: &str, ty: TProp) -> Prop {
+        Prop {
+            id: id.to_string(),
+            ty,
+            ..Prop::default()
+        }
+    }
+
+    fn users() -> Entity {
+        let address = TProp::Object {
+            name: "Address".to_string(),
+            props: vec![prop("city", TProp::String), prop("zip", TProp::String)],
+        };
+        Entity {
+            name: "User".to_string(),
+            route: "/users".to_string(),
+            props: vec![
+                prop("id", TProp::Number),
+                prop("name", TProp::String),
+                prop("address", address),
+                prop("tags", TProp::Array(Box::new(TProp::String))),
+            ],
+            ..Entity::default()
+        }
+    }
+
+    /// A `--stateful` collection of `users()`, seeded with two items.
+    struct Users {
+        config: web::Data<Config>,
+        store: web::Data<Store>,
+    }
+
+    impl Users {
+        fn seeded() -> Self {
+            Users::with(Config {
+                stateful: true,
+                seed_count: 2,
+                ..Config::default()
+            })
+        }
+
+        fn with(config: Config) -> Self {
+            let config = web::Data::new(config);
+            let store = web::Data::new(Store::default());
+            store.reset(&[users()], &config);
+            Users { config, store }
+        }
+
+        /// Serves `req` from the collection, which outlives the app.
+        async fn send(&self, req: http::TestRequest) -> (StatusCode, Value) {
+            let entity = users();
+            let app = App::new()
+                .app_data(self.config.clone())
+                .app_data(self.store.clone());
+            let app = register_route(app, "/users", &[&entity], &self.config);
+            let app = http::init_service(app).await;
+            let res = http::call_service(&app, req.to_request()).await;
+            let status = res.status();
+            let body = http::read_body(res).await;
+            (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+        }
+    }
+
+    #[actix_web::test]
+    async fn patch_merges_the_body_into_the_stored_item() {
+        let users = Users::seeded();
+        let (_, before) = users.send(http::TestRequest::get().uri("/users/1")).await;
+
+        let patch = json!({ "name": "ann", "address": { "city": "Oslo" } });
+        let req = http::TestRequest::patch().uri("/users/1").set_json(&patch);
+        let (status, patched) = users.send(req).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut expected = before.clone();
+        expected["name"] = json!("ann");
+        expected["address"]["city"] = json!("Oslo");
+        assert_eq!(patched, expected);
+        assert_eq!(patched["address"]["zip"], before["address"]["zip"]);
+
+        let (_, after) = users.send(http::TestRequest::get().uri("/users/1")).await;
+        assert_eq!(after, patched);
+    }
+
+    #[actix_web::test]
+    async fn patch_replaces_arrays_whole_and_keeps_the_id() {
+        let users = Users::seeded();
+        let patch = json!({ "id": 9, "tags": ["a"] });
+        let req = http::TestRequest::patch().uri("/users/2").set_json(&patch);
+        let (status, patched) = users.send(req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(patched["id"], json!(2));
+        assert_eq!(patched["tags"], json!(["a"]));
+    }
+
+    #[actix_web::test]
+    async fn put_replaces_the_whole_item() {
+        let users = Users::seeded();
+        let bob = json!({
+            "name": "bob",
+            "address": { "city": "Oslo", "zip": "0150" },
+            "tags": [],
+        });
+        let req = http::TestRequest::put().uri("/users/1").set_json(&bob);
+        let (status, replaced) = users.send(req).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut expected = bob;
+        expected["id"] = json!(1);
+        assert_eq!(replaced, expected);
+        let (_, after) = users.send(http::TestRequest::get().uri("/users/1")).await;
+        assert_eq!(after, replaced);
+    }
+
+    #[actix_web::test]
+    async fn put_needs_every_prop_but_the_id() {
+        let users = Users::seeded();
+        let (_, before) = users.send(http::TestRequest::get().uri("/users/1")).await;
+        let req = http::TestRequest::put()
+            .uri("/users/1")
+            .set_json(json!({ "name": "bob", "address": { "city": "Oslo" } }));
+        let (status, body) = users.send(req).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body["violations"],
+            json!([
+                { "field": "/address/zip", "expected": "string", "got": "missing" },
+                { "field": "/tags", "expected": "array", "got": "missing" },
+            ])
+        );
+        let (_, after) = users.send(http::TestRequest::get().uri("/users/1")).await;
+        assert_eq!(after, before);
+    }
+
+    #[actix_web::test]
+    async fn patch_checks_only_the_fields_it_sends() {
+        let users = Users::seeded();
+        let req = http::TestRequest::patch()
+            .uri("/users/1")
+            .set_json(json!({ "address": { "zip": 1000 } }));
+        let (status, body) = users.send(req).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body["violations"],
+            json!([{ "field": "/address/zip", "expected": "string", "got": "number" }])
+        );
+    }
+
+    #[actix_web::test]
+    async fn unknown_ids_are_404() {
+        let users = Users::seeded();
+        let body = json!({
+            "name": "cy",
+            "address": { "city": "Oslo", "zip": "0150" },
+            "tags": [],
+        });
+        for req in [
+            http::TestRequest::put().uri("/users/3"),
+            http::TestRequest::patch().uri("/users/3"),
+        ] {
+            let (status, _) = users.send(req.set_json(&body)).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+    }
+
+    #[actix_web::test]
+    async fn lags_too_long_for_an_instant_hold_writes_back() {
+        let users = Users::with(Config {
+            stateful: true,
+            seed_count: 2,
+            consistency_lag: Duration::MAX,
+            ..Config::default()
+        });
+        let (_, before) = users.send(http::TestRequest::get().uri("/users/1")).await;
+        let req = http::TestRequest::patch()
+            .uri("/users/1")
+            .set_json(json!({ "name": "bob" }));
+        assert_eq!(users.send(req).await.0, StatusCode::OK);
+        let (_, lagging) = users.send(http::TestRequest::get().uri("/users/1")).await;
+        assert_eq!(lagging, before);
+        let req = http::TestRequest::get()
+            .uri("/users/1")
+            .insert_header((CONSISTENT_HEADER, "true"));
+        assert_eq!(users.send(req).await.1["name"], "bob");
+    }
+}
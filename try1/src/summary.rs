@@ -0,0 +1,175 @@
+use std::path::Path;
+
+use crate::{
+    cli::Config, defaults::Origin, entity_docs, parser::ScanStats, route_groups, route_table,
+    Entity, Prop, TProp,
+};
+
+/// Prints where startup went: files found, parsed and skipped, imports
+/// re-read for types, and what the scan produced.
+pub fn scan(stats: &ScanStats, entities: &[Entity]) {
+    println!(
+        "scanned {} files in {:.1}ms: {} parsed ({} TypeScript), {} skipped, {} stray route comments, {} imports resolved; {} entities on {} routes",
+        stats.files,
+        stats.elapsed.as_secs_f64() * 1000.0,
+        stats.parsed,
+        stats.typescript,
+        stats.files - stats.parsed,
+        stats.stray_routes,
+        stats.imports,
+        entities.len(),
+        route_groups(entities).len()
+    );
+}
+
+/// Explains, on stderr, a scan that found no entities: where it looked,
+/// what it read, and how many route comments it couldn't use. `fatal`
+/// when ssg exits over it.
+pub fn no_entities(root: &Path, stats: &ScanStats, config: &Config, fatal: bool) {
+    let level = if fatal { "error" } else { "warning" };
+    let keywords: Vec<String> = config
+        .route_keywords
+        .iter()
+        .map(|keyword| format!("`{keyword}`"))
+        .collect();
+    eprintln!();
+    eprintln!("{level}: no entities found under {}", root.display());
+    eprintln!(
+        "  {} files found, {} parsed ({} TypeScript), {} skipped",
+        stats.files,
+        stats.parsed,
+        stats.typescript,
+        stats.files - stats.parsed
+    );
+    eprintln!(
+        "  {} comments mention {} but lead no interface, type, class or const",
+        stats.stray_routes,
+        keywords.join(" or ")
+    );
+    eprintln!(
+        "  an entity is a declaration led by a comment like `// {} /users`",
+        config.route_keywords[0]
+    );
+    if fatal {
+        eprintln!("  run ssg from your project, or pass --allow-empty to start anyway");
+    } else {
+        eprintln!("  run ssg from your project");
+    }
+    eprintln!();
+}
+
+/// Prints what would be served without starting the server: each entity's
+/// route and the settings it ended up with, labelled by origin, then the
+/// route annotations that were normalized on the way.
+pub fn dry_run(entities: &[Entity], stats: &ScanStats, config: &Config) {
+    println!("precedence: entity > file > directory > global");
+    for entity in entities {
+        println!(
+            "{} {}",
+            entity.served_route(config.version_prefix),
+            entity.name
+        );
+        if let Some(prefix) = &entity.route_prefix {
+            println!("  prefix {prefix} (--routes-from-dirs)");
+        }
+        for setting in &entity.settings {
+            println!("  @{} {} ({})", setting.tag, setting.value, setting.origin);
+        }
+        if let Some(bandwidth) = config.bandwidth.filter(|_| entity.bandwidth.is_none()) {
+            println!("  @bandwidth {bandwidth}B/s ({})", Origin::Global);
+        }
+    }
+    if !stats.route_fixes.is_empty() {
+        println!("normalized routes:");
+        let rows: Vec<[String; 4]> = stats
+            .route_fixes
+            .iter()
+            .map(|fix| {
+                [
+                    fix.location.clone(),
+                    fix.written.clone(),
+                    format!("-> {}", fix.route),
+                    fix.fixes.join(", "),
+                ]
+            })
+            .collect();
+        print_aligned("  ", &rows);
+    }
+}
+
+/// Prints each route an entity answers on, one line per path, with its
+/// methods and how many props the entity has.
+pub fn entities(entities: &[Entity], config: &Config) {
+    let mut rows = Vec::new();
+    for entity in entities {
+        for (index, (path, methods)) in route_table::entity_routes(entity, config)
+            .into_iter()
+            .enumerate()
+        {
+            let detail = if index == 0 {
+                let plural = if entity.props.len() == 1 { "" } else { "s" };
+                format!("{} ({} prop{plural})", entity.name, entity.props.len())
+            } else {
+                String::new()
+            };
+            rows.push([methods.join(" "), path, detail]);
+        }
+    }
+    print_aligned("  ", &rows);
+}
+
+/// Prints every entity with its props in aligned columns, for
+/// `--print-entities`.
+pub fn entity_details(entities: &[Entity], config: &Config) {
+    let root = std::env::current_dir().unwrap_or_default();
+    for entity in entities {
+        let source = entity.source.strip_prefix(&root).unwrap_or(&entity.source);
+        println!(
+            "\n{} {}{} ({})",
+            entity.name,
+            entity.served_route(config.version_prefix),
+            if entity.list { " (list)" } else { "" },
+            source.display()
+        );
+        let mut rows = Vec::new();
+        prop_rows(&entity.props, "", &mut rows);
+        print_aligned("  ", &rows);
+    }
+}
+
+fn prop_rows(props: &[Prop], parent: &str, rows: &mut Vec<[String; 5]>) {
+    for prop in props {
+        let name = format!("{parent}{}", prop.id);
+        rows.push([
+            name.clone(),
+            entity_docs::type_name(&prop.ty),
+            if prop.optional { "optional" } else { "" }.to_string(),
+            entity_docs::constraints(prop).join(", "),
+            entity_docs::hints(prop).join("; "),
+        ]);
+        let mut ty = &prop.ty;
+        while let TProp::Array(item) = ty {
+            ty = item;
+        }
+        if let TProp::Object { props, .. } = ty {
+            prop_rows(props, &format!("{name}."), rows);
+        }
+    }
+}
+
+/// Prints `rows` with each column padded to its widest cell.
+fn print_aligned<const N: usize>(indent: &str, rows: &[[String; N]]) {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for row in rows {
+        let mut line = indent.to_string();
+        for (cell, width) in row.iter().zip(widths) {
+            line.push_str(&format!("{cell:<width$}  "));
+        }
+        println!("{}", line.trim_end());
+    }
+}
@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    middleware::Next,
+    rt::time::sleep,
+    web::{self, Bytes, BytesMut},
+    Error, HttpRequest,
+};
+use fake::rand::Rng;
+use futures_util::{stream, Stream, StreamExt};
+
+use crate::{cli::Config, generate, Entity};
+
+/// Chunks are written this often, each sized to match the target rate.
+const TICK: Duration = Duration::from_millis(100);
+
+/// Parses a rate such as `50kbps`, `1mbps` (bits) or `64KB/s` (bytes) into
+/// bytes per second.
+pub fn parse_bandwidth(s: &str) -> Result<u64, String> {
+    let trimmed = s.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid bandwidth `{s}`"))?;
+
+    let bytes_per_sec = match unit.trim() {
+        "bps" => number / 8.0,
+        "kbps" => number * 1000.0 / 8.0,
+        "mbps" => number * 1_000_000.0 / 8.0,
+        "" | "B/s" => number,
+        "KB/s" => number * 1024.0,
+        "MB/s" => number * 1024.0 * 1024.0,
+        _ => return Err(format!("unknown bandwidth unit in `{s}`")),
+    };
+    if bytes_per_sec < 1.0 {
+        return Err(format!("bandwidth `{s}` is too low"));
+    }
+    Ok(bytes_per_sec as u64)
+}
+
+/// The rate for this request: `X-Mock-Bandwidth`, then the entity's
+/// `@bandwidth`, then `--bandwidth`.
+pub fn bandwidth_for(
+    req: &HttpRequest,
+    entity: &Entity,
+    config: &Config,
+) -> Result<Option<u64>, String> {
+    if let Some(header) = req.headers().get("X-Mock-Bandwidth") {
+        let header = header
+            .to_str()
+            .map_err(|_| "X-Mock-Bandwidth is not valid UTF-8".to_string())?;
+        return parse_bandwidth(header).map(Some);
+    }
+    Ok(entity.bandwidth.or(config.bandwidth))
+}
+
+/// Streams `body` in small chunks with async sleeps in between, so the
+/// transfer takes roughly `len / bytes_per_sec` without blocking a worker.
+pub fn throttled(
+    body: Vec<u8>,
+    bytes_per_sec: u64,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    paced(stream::once(async { Ok(Bytes::from(body)) }), bytes_per_sec)
+}
+
+/// Re-chunks a body stream to `bytes_per_sec`, pulling from `body` only
+/// when the previous chunk has gone out. Chunks smaller than a tick's worth
+/// are joined, so a stream of small items still goes out at the full rate.
+pub fn paced(
+    body: impl Stream<Item = Result<Bytes, actix_web::Error>>,
+    bytes_per_sec: u64,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> {
+    let chunk_size = ((bytes_per_sec as f64 * TICK.as_secs_f64()) as usize).max(1);
+
+    stream::unfold(
+        (Box::pin(body), Bytes::new(), false),
+        move |(mut body, mut pending, mut sent)| async move {
+            let mut chunk = BytesMut::new();
+            while chunk.len() < chunk_size {
+                if pending.is_empty() {
+                    match body.next().await {
+                        Some(Ok(next)) => pending = next,
+                        Some(Err(err)) => return Some((Err(err), (body, pending, sent))),
+                        None => break,
+                    }
+                }
+                let take = (chunk_size - chunk.len()).min(pending.len());
+                chunk.extend_from_slice(&pending.split_to(take));
+            }
+            if chunk.is_empty() {
+                return None;
+            }
+            if sent {
+                sleep(TICK).await;
+            }
+            sent = true;
+            Some((Ok(chunk.freeze()), (body, pending, sent)))
+        },
+    )
+}
+
+/// Holds each entity response back by a random `0..=--jitter` on top of
+/// any other delay. Under `--seed` a given request always waits the same.
+pub async fn jitter(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let jitter = req
+        .app_data::<web::Data<Config>>()
+        .filter(|config| !config.is_internal(req.path()))
+        .map(|config| (config.jitter, config.seed));
+    if let Some((jitter, seed)) = jitter.filter(|(jitter, _)| !jitter.is_zero()) {
+        let key = format!(
+            "{} {}?{}#jitter",
+            req.method(),
+            req.path(),
+            req.query_string()
+        );
+        let mut rng = generate::rng_for(seed, &key);
+        sleep(jitter.mul_f64(rng.random_range(0.0..=1.0))).await;
+    }
+    next.call(req).await
+}
@@ -0,0 +1,273 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde_json::{json, Value};
+
+use crate::{
+    cli::Config,
+    concurrency::{self, Concurrency},
+    date, generate, store, Entity, NumberFormat, Prop, TProp,
+};
+
+/// Distinct violations `/__violations` keeps; later ones are only counted.
+const MAX_DISTINCT: usize = 1000;
+/// Bytes of the first offending body kept per violation.
+const SAMPLE_BYTES: usize = 512;
+
+/// One field of a request body that doesn't match the entity.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Failure {
+    /// JSON Pointer to the field, `""` for the body itself.
+    pub field: String,
+    pub expected: String,
+    pub got: String,
+}
+
+/// How much of an entity a body has to send.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Body {
+    /// Only the fields it sends are checked, since the mock or the stored
+    /// item fills in the rest: POST, PATCH.
+    Partial,
+    /// Every required prop has to be there too, nested ones included, as
+    /// the body replaces what was stored: a `--stateful` PUT. The key and
+    /// the fields the store stamps itself are the exception.
+    Whole,
+}
+
+/// Checks the fields a body sends against the entity's props. Fields it
+/// doesn't know are fine; missing ones are too unless `body` is `Whole`.
+pub fn check(entity: &Entity, value: &Value, config: &Config, body: Body) -> Vec<Failure> {
+    let mut failures = Vec::new();
+    match value {
+        Value::Object(_) => {
+            let managed = |prop: &Prop| {
+                prop.id == entity.key()
+                    || (entity.soft_delete && prop.id == store::DELETED_AT)
+                    || (entity.concurrency == Concurrency::Version
+                        && prop.id == concurrency::VERSION_PROP)
+            };
+            let props: Vec<Prop> = entity
+                .props
+                .iter()
+                .filter(|prop| value.get(&prop.id).is_some() || !managed(prop))
+                .cloned()
+                .collect();
+            check_props(&props, value, "", config, body, &mut failures)
+        }
+        value => failures.push(Failure {
+            field: String::new(),
+            expected: "object".to_string(),
+            got: type_name(value).to_string(),
+        }),
+    }
+    failures
+}
+
+fn check_props(
+    props: &[Prop],
+    object: &Value,
+    path: &str,
+    config: &Config,
+    body: Body,
+    failures: &mut Vec<Failure>,
+) {
+    for prop in props {
+        let path = format!("{path}/{}", prop.id.replace('~', "~0").replace('/', "~1"));
+        match object.get(&prop.id) {
+            Some(value) => check_value(prop, &prop.ty, value, &path, config, body, failures),
+            None if body == Body::Whole && !prop.optional => failures.push(Failure {
+                field: path,
+                expected: expected_name(&prop.ty).to_string(),
+                got: "missing".to_string(),
+            }),
+            None => (),
+        }
+    }
+}
+
+fn check_value(
+    prop: &Prop,
+    ty: &TProp,
+    value: &Value,
+    path: &str,
+    config: &Config,
+    body: Body,
+    failures: &mut Vec<Failure>,
+) {
+    let mut fail = |expected: String, got: String| {
+        failures.push(Failure {
+            field: path.to_string(),
+            expected,
+            got,
+        })
+    };
+    let numeric_string = |value: &Value| {
+        generate::as_string(prop, config)
+            && value.as_str().is_some_and(|s| s.parse::<f64>().is_ok())
+    };
+    match (ty, value) {
+        (TProp::Boolean, Value::Bool(_)) | (TProp::String, Value::String(_)) => (),
+        (TProp::Number, Value::Number(n))
+            if prop.number_format == Some(NumberFormat::Int)
+                && n.as_f64().is_some_and(|n| n.fract() != 0.0) =>
+        {
+            fail("integer".to_string(), n.to_string())
+        }
+        (TProp::Number, Value::Number(_)) => (),
+        (TProp::BigInt, Value::Number(n)) if n.is_i64() || n.is_u64() => (),
+        (TProp::Number | TProp::BigInt, value) if numeric_string(value) => (),
+        (TProp::Date, Value::String(s)) if date::parse(s).is_some() => (),
+        (TProp::Date, Value::String(_)) => fail("date-time".to_string(), "string".to_string()),
+        (TProp::Enum(values), value) if values.contains(value) => (),
+        (TProp::Enum(values), value) => {
+            let expected: Vec<String> = values.iter().map(Value::to_string).collect();
+            fail(format!("one of {}", expected.join(", ")), value.to_string())
+        }
+        (TProp::Object { props, .. }, Value::Object(_)) => {
+            check_props(props, value, path, config, body, failures)
+        }
+        (TProp::Array(item), Value::Array(items)) => {
+            for (index, value) in items.iter().enumerate() {
+                check_value(
+                    prop,
+                    item,
+                    value,
+                    &format!("{path}/{index}"),
+                    config,
+                    body,
+                    failures,
+                );
+            }
+        }
+        (ty, value) => fail(expected_name(ty).to_string(), type_name(value).to_string()),
+    }
+}
+
+fn expected_name(ty: &TProp) -> &'static str {
+    match ty {
+        TProp::Boolean => "boolean",
+        TProp::Number => "number",
+        TProp::BigInt => "integer",
+        TProp::String => "string",
+        TProp::Enum(_) => "enum",
+        TProp::Object { .. } => "object",
+        TProp::Array(_) => "array",
+        TProp::Date => "date-time",
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// The 422 for a body with `failures`, after adding them to the report.
+pub fn reject(
+    req: &HttpRequest,
+    entity: &Entity,
+    body: &Value,
+    failures: Vec<Failure>,
+) -> HttpResponse {
+    if let Some(report) = req.app_data::<web::Data<Report>>() {
+        report.add(req.method().as_str(), &entity.route, body, &failures);
+    }
+    let violations: Vec<Value> = failures.iter().map(Failure::to_json).collect();
+    HttpResponse::UnprocessableEntity().json(json!({
+        "error": format!("the body doesn't match `{}`", entity.name),
+        "violations": violations,
+    }))
+}
+
+impl Failure {
+    fn to_json(&self) -> Value {
+        json!({ "field": self.field, "expected": self.expected, "got": self.got })
+    }
+}
+
+/// Every rejected body's failures, aggregated per route for `/__violations`.
+#[derive(Default)]
+pub struct Report {
+    inner: Mutex<ReportState>,
+}
+
+#[derive(Default)]
+struct ReportState {
+    seen: HashMap<(String, String, Failure), Seen>,
+    /// Failures not kept because `MAX_DISTINCT` was reached.
+    dropped: u64,
+}
+
+struct Seen {
+    count: u64,
+    /// The first body with this failure, truncated to `SAMPLE_BYTES`.
+    sample: String,
+}
+
+impl Report {
+    fn add(&self, method: &str, route: &str, body: &Value, failures: &[Failure]) {
+        let mut state = self.inner.lock().unwrap();
+        let mut sample = None;
+        for failure in failures {
+            let key = (method.to_string(), route.to_string(), failure.clone());
+            if let Some(seen) = state.seen.get_mut(&key) {
+                seen.count += 1;
+            } else if state.seen.len() < MAX_DISTINCT {
+                let sample = sample
+                    .get_or_insert_with(|| truncate(body.to_string()))
+                    .clone();
+                state.seen.insert(key, Seen { count: 1, sample });
+            } else {
+                state.dropped += 1;
+            }
+        }
+    }
+}
+
+fn truncate(mut s: String) -> String {
+    if s.len() > SAMPLE_BYTES {
+        let mut end = SAMPLE_BYTES;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        s.truncate(end);
+        s.push('…');
+    }
+    s
+}
+
+/// `GET /__violations`: each distinct failure seen, by route, most frequent
+/// first.
+pub async fn list(report: web::Data<Report>) -> HttpResponse {
+    let state = report.inner.lock().unwrap();
+    let mut entries: Vec<_> = state.seen.iter().collect();
+    entries.sort_by(|((m1, r1, f1), s1), ((m2, r2, f2), s2)| {
+        (r1, m1)
+            .cmp(&(r2, m2))
+            .then(s2.count.cmp(&s1.count))
+            .then(f1.field.cmp(&f2.field))
+    });
+    let mut routes = serde_json::Map::new();
+    for ((method, route, failure), seen) in entries {
+        let mut entry = failure.to_json();
+        entry["method"] = json!(method);
+        entry["count"] = json!(seen.count);
+        entry["sample"] = json!(seen.sample);
+        if let Value::Array(items) = routes.entry(route.clone()).or_insert_with(|| json!([])) {
+            items.push(entry);
+        }
+    }
+    HttpResponse::Ok().json(json!({ "routes": routes, "dropped": state.dropped }))
+}
+
+/// `DELETE /__violations` starts a fresh report.
+pub async fn clear(report: web::Data<Report>) -> HttpResponse {
+    *report.inner.lock().unwrap() = ReportState::default();
+    HttpResponse::NoContent().finish()
+}
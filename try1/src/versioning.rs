@@ -0,0 +1,48 @@
+use actix_web::{
+    guard::{self, Guard},
+    http::header::{HeaderMap, ACCEPT},
+};
+
+use crate::Entity;
+
+/// Reads the version profile from `Accept: application/json; version=2`.
+pub fn requested_version(headers: &HeaderMap) -> Option<u32> {
+    let accept = headers.get(ACCEPT)?.to_str().ok()?;
+    accept
+        .split(',')
+        .flat_map(|range| range.split(';').skip(1))
+        .find_map(|param| {
+            let (key, value) = param.split_once('=')?;
+            (key.trim() == "version")
+                .then(|| {
+                    value
+                        .trim()
+                        .trim_matches('"')
+                        .trim_start_matches('v')
+                        .parse()
+                        .ok()
+                })
+                .flatten()
+        })
+}
+
+/// Matches requests asking for exactly `version`.
+pub fn accepts(version: u32) -> impl Guard {
+    guard::fn_guard(move |ctx| requested_version(ctx.head().headers()) == Some(version))
+}
+
+/// Matches requests that don't ask for any version.
+pub fn unversioned() -> impl Guard {
+    guard::fn_guard(|ctx| requested_version(ctx.head().headers()).is_none())
+}
+
+/// The entity served when no version is requested: an unversioned one if
+/// present, otherwise the highest version.
+pub fn default_entity<'a>(group: &[&'a Entity]) -> &'a Entity {
+    group
+        .iter()
+        .find(|e| e.version.is_none())
+        .or_else(|| group.iter().max_by_key(|e| e.version))
+        .copied()
+        .expect("route groups are never empty")
+}
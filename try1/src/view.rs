@@ -0,0 +1,616 @@
+use std::{fmt, fs, path::Path};
+
+use serde_json::Value;
+
+use crate::{Prop, TProp};
+
+/// Filters `@transform` files can call.
+const FUNCTIONS: [&str; 2] = ["map", "flatten"];
+
+/// A reshaped view of an entity's objects, from `@transform <file>`: a
+/// small subset of jq. Paths (`.`, `.a.b`, `."a b"`) select fields,
+/// objects (`{id, name: .profile.name}`) rename and pick them, `a + b`
+/// merges two objects (or joins two lists), `map(f)` maps a list,
+/// `flatten` flattens a list of lists one level, and `a | b` feeds one into
+/// the other. `#` starts a comment.
+#[derive(Debug, Clone)]
+pub struct View {
+    expr: Expr,
+    /// The file's text, which `--ir` files carry.
+    source: String,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Path(Vec<String>, Pos),
+    Object(Vec<(String, Expr)>),
+    Map(Box<Expr>, Pos),
+    Flatten(Pos),
+    Add(Box<Expr>, Box<Expr>),
+    Pipe(Box<Expr>, Box<Expr>),
+}
+
+/// 1-based line and column in the transform file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pos {
+    line: usize,
+    column: usize,
+}
+
+impl fmt::Display for Pos {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl View {
+    /// Reads `file` as `View::new` does. Errors start with `file:line:col`.
+    pub fn load(file: &Path, props: &[Prop]) -> Result<Self, String> {
+        let text = fs::read_to_string(file)
+            .map_err(|err| format!("{}: can't read it: {err}", file.display()))?;
+        View::new(&text, props).map_err(|error| format!("{}:{error}", file.display()))
+    }
+
+    /// Parses `text`, then checks its paths against `props`, the shape of
+    /// the objects it will see. Errors start with `line:col`.
+    pub fn new(text: &str, props: &[Prop]) -> Result<Self, String> {
+        let at = |(pos, error): (Pos, String)| format!("{pos}: {error}");
+        let view = View::parse(text).map_err(at)?;
+        check(&view.expr, &Shape::of(props)).map_err(at)?;
+        Ok(view)
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    fn parse(text: &str) -> Result<Self, (Pos, String)> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            next: 0,
+        };
+        let expr = parser.pipe()?;
+        match parser.peek() {
+            Some(token) => Err((token.pos, format!("unexpected {}", token.kind))),
+            None => Ok(View {
+                expr,
+                source: text.to_string(),
+            }),
+        }
+    }
+
+    /// `data` reshaped; a list response is reshaped item by item. Values
+    /// that don't fit the view, such as a missing optional prop, come out
+    /// as `null`.
+    pub fn apply(&self, data: &mut Value) {
+        match data {
+            Value::Array(items) => {
+                for item in items {
+                    *item = eval(&self.expr, item);
+                }
+            }
+            data => *data = eval(&self.expr, data),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    Dot,
+    Ident(String),
+    Str(String),
+    Open(char),
+    Close(char),
+    Colon,
+    Comma,
+    Pipe,
+    Plus,
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenKind::Dot => write!(f, "`.`"),
+            TokenKind::Ident(name) => write!(f, "`{name}`"),
+            TokenKind::Str(s) => write!(f, "string \"{s}\""),
+            TokenKind::Open(c) | TokenKind::Close(c) => write!(f, "`{c}`"),
+            TokenKind::Colon => write!(f, "`:`"),
+            TokenKind::Comma => write!(f, "`,`"),
+            TokenKind::Pipe => write!(f, "`|`"),
+            TokenKind::Plus => write!(f, "`+`"),
+        }
+    }
+}
+
+struct Token {
+    kind: TokenKind,
+    pos: Pos,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, (Pos, String)> {
+    let mut tokens = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let mut chars = line.char_indices().peekable();
+        while let Some((offset, c)) = chars.next() {
+            let pos = Pos {
+                line: index + 1,
+                column: line[..offset].chars().count() + 1,
+            };
+            let kind = match c {
+                '#' => break,
+                c if c.is_whitespace() => continue,
+                '.' => TokenKind::Dot,
+                '{' | '(' => TokenKind::Open(c),
+                '}' | ')' => TokenKind::Close(c),
+                ':' => TokenKind::Colon,
+                ',' => TokenKind::Comma,
+                '|' => TokenKind::Pipe,
+                '+' => TokenKind::Plus,
+                '"' => {
+                    let mut s = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '"')) => break,
+                            Some((_, '\\')) => match chars.next() {
+                                Some((_, c @ ('"' | '\\'))) => s.push(c),
+                                _ => {
+                                    return Err((
+                                        pos,
+                                        "only `\\\"` and `\\\\` escapes are supported".to_string(),
+                                    ))
+                                }
+                            },
+                            Some((_, c)) => s.push(c),
+                            None => {
+                                return Err((pos, "string isn't closed on its line".to_string()))
+                            }
+                        }
+                    }
+                    TokenKind::Str(s)
+                }
+                c if c.is_alphabetic() || c == '_' => {
+                    let mut name = c.to_string();
+                    while let Some((_, c)) =
+                        chars.next_if(|(_, c)| c.is_alphanumeric() || *c == '_')
+                    {
+                        name.push(c);
+                    }
+                    TokenKind::Ident(name)
+                }
+                c => return Err((pos, format!("unexpected `{c}`"))),
+            };
+            tokens.push(Token { kind, pos });
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        let matched = self.peek().is_some_and(|token| token.kind == *kind);
+        self.next += matched as usize;
+        matched
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<(), (Pos, String)> {
+        if self.eat(&kind) {
+            return Ok(());
+        }
+        Err(self.unexpected(&format!("{kind}")))
+    }
+
+    fn unexpected(&self, wanted: &str) -> (Pos, String) {
+        match self.peek() {
+            Some(token) => (token.pos, format!("expected {wanted}, got {}", token.kind)),
+            None => (
+                self.tokens
+                    .last()
+                    .map_or(Pos { line: 1, column: 1 }, |t| t.pos),
+                format!("expected {wanted}, got the end of the file"),
+            ),
+        }
+    }
+
+    fn pipe(&mut self) -> Result<Expr, (Pos, String)> {
+        let mut expr = self.sum()?;
+        while self.eat(&TokenKind::Pipe) {
+            expr = Expr::Pipe(Box::new(expr), Box::new(self.sum()?));
+        }
+        Ok(expr)
+    }
+
+    fn sum(&mut self) -> Result<Expr, (Pos, String)> {
+        let mut expr = self.term()?;
+        while self.eat(&TokenKind::Plus) {
+            expr = Expr::Add(Box::new(expr), Box::new(self.term()?));
+        }
+        Ok(expr)
+    }
+
+    fn term(&mut self) -> Result<Expr, (Pos, String)> {
+        let Some(token) = self.peek() else {
+            return Err(self.unexpected("a path, an object, `map(...)` or `flatten`"));
+        };
+        let pos = token.pos;
+        match token.kind.clone() {
+            TokenKind::Dot => self.path(),
+            TokenKind::Open('{') => {
+                self.next += 1;
+                self.object()
+            }
+            TokenKind::Open('(') => {
+                self.next += 1;
+                let expr = self.pipe()?;
+                self.expect(TokenKind::Close(')'))?;
+                Ok(expr)
+            }
+            TokenKind::Ident(name) if name == "map" => {
+                self.next += 1;
+                self.expect(TokenKind::Open('('))?;
+                let expr = self.pipe()?;
+                self.expect(TokenKind::Close(')'))?;
+                Ok(Expr::Map(Box::new(expr), pos))
+            }
+            TokenKind::Ident(name) if name == "flatten" => {
+                self.next += 1;
+                Ok(Expr::Flatten(pos))
+            }
+            TokenKind::Ident(name) => Err((
+                pos,
+                format!(
+                    "unknown filter `{name}`; transforms can use {}",
+                    FUNCTIONS.join(" and ")
+                ),
+            )),
+            _ => Err(self.unexpected("a path, an object, `map(...)` or `flatten`")),
+        }
+    }
+
+    /// `.`, `.a`, `.a.b`, `."a b"`.
+    fn path(&mut self) -> Result<Expr, (Pos, String)> {
+        let pos = self.peek().map(|token| token.pos).unwrap();
+        let mut fields = Vec::new();
+        while self.eat(&TokenKind::Dot) {
+            match self.peek().map(|token| token.kind.clone()) {
+                Some(TokenKind::Ident(name) | TokenKind::Str(name)) => {
+                    self.next += 1;
+                    fields.push(name);
+                }
+                _ if fields.is_empty() => break,
+                _ => return Err(self.unexpected("a field name after `.`")),
+            }
+        }
+        Ok(Expr::Path(fields, pos))
+    }
+
+    /// The entries of `{...}` after its `{`: `key`, `"key"`, `key: value`.
+    fn object(&mut self) -> Result<Expr, (Pos, String)> {
+        let mut entries: Vec<(String, Expr)> = Vec::new();
+        while !self.eat(&TokenKind::Close('}')) {
+            let Some(Token { kind, pos }) = self.peek() else {
+                return Err(self.unexpected("`}`"));
+            };
+            let pos = *pos;
+            let key = match kind {
+                TokenKind::Ident(key) | TokenKind::Str(key) => key.clone(),
+                _ => return Err(self.unexpected("a key")),
+            };
+            self.next += 1;
+            if entries.iter().any(|(existing, _)| *existing == key) {
+                return Err((pos, format!("key `{key}` appears twice")));
+            }
+            let value = if self.eat(&TokenKind::Colon) {
+                self.sum()?
+            } else {
+                Expr::Path(vec![key.clone()], pos)
+            };
+            entries.push((key, value));
+            if !self.eat(&TokenKind::Comma)
+                && self.peek().map(|t| &t.kind) != Some(&TokenKind::Close('}'))
+            {
+                return Err(self.unexpected("`,` or `}`"));
+            }
+        }
+        Ok(Expr::Object(entries))
+    }
+}
+
+/// What the checker knows of a value from the entity's props.
+#[derive(Debug, Clone)]
+enum Shape {
+    Unknown,
+    Scalar,
+    Object(Vec<(String, Shape)>),
+    List(Box<Shape>),
+}
+
+impl Shape {
+    fn of(props: &[Prop]) -> Shape {
+        if props.is_empty() {
+            return Shape::Unknown;
+        }
+        Shape::Object(
+            props
+                .iter()
+                .map(|prop| (prop.id.clone(), Shape::of_type(&prop.ty)))
+                .collect(),
+        )
+    }
+
+    fn of_type(ty: &TProp) -> Shape {
+        match ty {
+            TProp::Object { props, .. } => Shape::of(props),
+            TProp::Array(item) => Shape::List(Box::new(Shape::of_type(item))),
+            _ => Shape::Scalar,
+        }
+    }
+}
+
+/// The shape `expr` makes of `input`, or why it can't work on it.
+fn check(expr: &Expr, input: &Shape) -> Result<Shape, (Pos, String)> {
+    match expr {
+        Expr::Path(fields, pos) => {
+            let mut shape = input.clone();
+            for (depth, field) in fields.iter().enumerate() {
+                let walked = if depth == 0 {
+                    "the input".to_string()
+                } else {
+                    format!("`.{}`", fields[..depth].join("."))
+                };
+                shape = match shape {
+                    Shape::Unknown => Shape::Unknown,
+                    Shape::Object(fields) => match fields.into_iter().find(|(id, _)| id == field) {
+                        Some((_, shape)) => shape,
+                        None => return Err((*pos, format!("{walked} has no field `{field}`"))),
+                    },
+                    Shape::List(_) => {
+                        return Err((
+                            *pos,
+                            format!(
+                                "{walked} is a list; reach into its items with `map(.{field})`"
+                            ),
+                        ))
+                    }
+                    Shape::Scalar => {
+                        return Err((
+                            *pos,
+                            format!("{walked} isn't an object, so has no `{field}`"),
+                        ))
+                    }
+                };
+            }
+            Ok(shape)
+        }
+        Expr::Object(entries) => entries
+            .iter()
+            .map(|(key, value)| Ok((key.clone(), check(value, input)?)))
+            .collect::<Result<_, _>>()
+            .map(Shape::Object),
+        Expr::Map(item, pos) => match input {
+            Shape::Unknown => check(item, &Shape::Unknown).map(|_| Shape::Unknown),
+            Shape::List(shape) => Ok(Shape::List(Box::new(check(item, shape)?))),
+            _ => Err((*pos, "`map(...)` needs a list".to_string())),
+        },
+        Expr::Flatten(pos) => match input {
+            Shape::Unknown => Ok(Shape::Unknown),
+            Shape::List(item) => match &**item {
+                Shape::List(inner) => Ok(Shape::List(inner.clone())),
+                _ => Ok(input.clone()),
+            },
+            _ => Err((*pos, "`flatten` needs a list".to_string())),
+        },
+        Expr::Add(a, b) => match (check(a, input)?, check(b, input)?) {
+            (Shape::Object(mut a), Shape::Object(b)) => {
+                a.retain(|(key, _)| !b.iter().any(|(other, _)| other == key));
+                a.extend(b);
+                Ok(Shape::Object(a))
+            }
+            (Shape::List(a), Shape::List(_)) => Ok(Shape::List(a)),
+            _ => Ok(Shape::Unknown),
+        },
+        Expr::Pipe(a, b) => check(b, &check(a, input)?),
+    }
+}
+
+fn eval(expr: &Expr, input: &Value) -> Value {
+    match expr {
+        Expr::Path(fields, _) => fields
+            .iter()
+            .try_fold(input, |value, field| value.get(field))
+            .cloned()
+            .unwrap_or(Value::Null),
+        Expr::Object(entries) => Value::Object(
+            entries
+                .iter()
+                .map(|(key, value)| (key.clone(), eval(value, input)))
+                .collect(),
+        ),
+        Expr::Map(item, _) => match input {
+            Value::Array(items) => Value::Array(items.iter().map(|v| eval(item, v)).collect()),
+            _ => Value::Null,
+        },
+        Expr::Flatten(_) => match input {
+            Value::Array(items) => Value::Array(
+                items
+                    .iter()
+                    .flat_map(|item| match item {
+                        Value::Array(inner) => inner.clone(),
+                        item => vec![item.clone()],
+                    })
+                    .collect(),
+            ),
+            _ => Value::Null,
+        },
+        Expr::Add(a, b) => match (eval(a, input), eval(b, input)) {
+            (Value::Object(mut a), Value::Object(b)) => {
+                a.extend(b);
+                Value::Object(a)
+            }
+            (Value::Array(mut a), Value::Array(b)) => {
+                a.extend(b);
+                Value::Array(a)
+            }
+            (Value::Null, value) | (value, Value::Null) => value,
+            _ => Value::Null,
+        },
+        Expr::Pipe(a, b) => eval(b, &eval(a, input)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn prop(id: &str, ty: TProp) -> Prop {
+        Prop {
+            id: id.to_string(),
+            ty,
+            ..Prop::default()
+        }
+    }
+
+    fn user() -> Vec<Prop> {
+        vec![
+            prop("id", TProp::Number),
+            prop(
+                "profile",
+                TProp::Object {
+                    name: "Profile".to_string(),
+                    props: vec![prop("name", TProp::String), prop("city", TProp::String)],
+                },
+            ),
+            prop("tags", TProp::Array(Box::new(TProp::String))),
+            prop(
+                "groups",
+                TProp::Array(Box::new(TProp::Array(Box::new(TProp::String)))),
+            ),
+        ]
+    }
+
+    /// `text` parsed and checked against `user()`, with errors as
+    /// `line:col: message`.
+    fn view(text: &str) -> Result<View, String> {
+        let at = |(pos, error): (Pos, String)| format!("{pos}: {error}");
+        let view = View::parse(text).map_err(at)?;
+        check(&view.expr, &Shape::of(&user())).map_err(at)?;
+        Ok(view)
+    }
+
+    fn reshape(text: &str, mut data: Value) -> Value {
+        view(text).unwrap().apply(&mut data);
+        data
+    }
+
+    fn ann() -> Value {
+        json!({
+            "id": 1,
+            "profile": {"name": "ann", "city": "Oslo"},
+            "tags": ["a", "b"],
+            "groups": [["x"], ["y", "z"]],
+        })
+    }
+
+    #[test]
+    fn objects_pick_and_rename_fields() {
+        assert_eq!(
+            reshape(
+                "{id, name: .profile.name, \"home town\": .profile.city}",
+                ann()
+            ),
+            json!({"id": 1, "name": "ann", "home town": "Oslo"})
+        );
+    }
+
+    #[test]
+    fn adds_merge_objects_and_pipes_feed_forward() {
+        assert_eq!(
+            reshape("# a comment\n{id} + .profile | {name}", ann()),
+            json!({"name": "ann"})
+        );
+        assert_eq!(
+            reshape(".tags + (.groups | flatten)", ann()),
+            json!(["a", "b", "x", "y", "z"])
+        );
+        assert_eq!(
+            reshape(".tags | map({tag: .})", ann()),
+            json!([{"tag": "a"}, {"tag": "b"}])
+        );
+    }
+
+    #[test]
+    fn lists_are_reshaped_item_by_item() {
+        assert_eq!(
+            reshape("{id}", json!([ann(), {"id": 2}])),
+            json!([{"id": 1}, {"id": 2}])
+        );
+    }
+
+    #[test]
+    fn missing_values_come_out_as_null() {
+        assert_eq!(
+            reshape("{id, city: .profile.city}", json!({"id": 3})),
+            json!({"id": 3, "city": null})
+        );
+    }
+
+    #[test]
+    fn syntax_errors_point_at_their_line_and_column() {
+        assert_eq!(
+            view("{id,\n  name: .profile.}").unwrap_err(),
+            "2:18: expected a field name after `.`, got `}`"
+        );
+        assert_eq!(view("{id, id}").unwrap_err(), "1:6: key `id` appears twice");
+        assert_eq!(
+            view("{id").unwrap_err(),
+            "1:2: expected `,` or `}`, got the end of the file"
+        );
+        assert_eq!(view(".id $").unwrap_err(), "1:5: unexpected `$`");
+        assert_eq!(
+            view("  select(.id)").unwrap_err(),
+            "1:3: unknown filter `select`; transforms can use map and flatten"
+        );
+    }
+
+    #[test]
+    fn paths_are_checked_against_the_props() {
+        assert_eq!(
+            view("{id, zip: .profile.zip}").unwrap_err(),
+            "1:11: `.profile` has no field `zip`"
+        );
+        assert_eq!(
+            view(".tags.name").unwrap_err(),
+            "1:1: `.tags` is a list; reach into its items with `map(.name)`"
+        );
+        assert_eq!(
+            view(".id.value").unwrap_err(),
+            "1:1: `.id` isn't an object, so has no `value`"
+        );
+        assert_eq!(
+            view(".profile | map(.name)").unwrap_err(),
+            "1:12: `map(...)` needs a list"
+        );
+    }
+
+    #[test]
+    fn load_prefixes_errors_with_the_file() {
+        let file = std::env::temp_dir().join(format!("ssg-view-{}.jq", std::process::id()));
+        fs::write(&file, "{id,\n .nope}").unwrap();
+        let error = View::load(&file, &user()).unwrap_err();
+        fs::remove_file(&file).unwrap();
+        assert_eq!(
+            error,
+            format!("{}:2:2: expected a key, got `.`", file.display())
+        );
+    }
+}
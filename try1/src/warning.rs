@@ -0,0 +1,16 @@
+use std::{fmt::Display, path::Path, sync::Mutex};
+
+/// Every warning printed so far, for `--fail-on-warning`.
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Prints a warning about `path` and keeps it for the end-of-scan check.
+pub fn warn(path: &Path, message: impl Display) {
+    let warning = format!("{}: {message}", path.display());
+    eprintln!("warning: {warning}");
+    WARNINGS.lock().unwrap().push(warning);
+}
+
+/// The warnings printed so far, oldest first.
+pub fn collected() -> Vec<String> {
+    WARNINGS.lock().unwrap().clone()
+}
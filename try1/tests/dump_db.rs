@@ -0,0 +1,63 @@
+//! `--dump-db` writes each item as it's generated, so a dump many times
+//! larger than the memory the process may use still completes.
+#![cfg(unix)]
+
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    process::{Command, Stdio},
+};
+
+/// Address space, in KiB, the dumping process is limited to.
+const CEILING_KB: u64 = 128 * 1024;
+
+/// Each page carries an 8 KiB enum value, so 40,000 seeded pages make a
+/// dump of over 300 MiB.
+const PAGES: usize = 40_000;
+
+#[test]
+fn a_dump_larger_than_the_memory_ceiling_completes() {
+    let dir = std::env::temp_dir().join(format!("ssg-dump-db-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let blob = "x".repeat(8 * 1024);
+    let source = format!(
+        "enum Blob {{ Page = \"{blob}\" }}\n\
+         // route /pages\n\
+         interface Page {{ id: number; title: string; body: Blob; }}\n"
+    );
+    fs::write(dir.join("pages.ts"), source).unwrap();
+    let out = dir.join("db.json");
+
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!(
+            "ulimit -v {CEILING_KB} && exec \"$0\" --dump-db \"$1\" --seed-count {PAGES}"
+        ))
+        .arg(env!("CARGO_BIN_EXE_ssg"))
+        .arg(&out)
+        .current_dir(&dir)
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(
+        status.success(),
+        "ssg --dump-db failed under the ceiling: {status}"
+    );
+
+    let mut dump = File::open(&out).unwrap();
+    let size = dump.metadata().unwrap().len();
+    assert!(
+        size > (PAGES * blob.len()) as u64,
+        "dump is only {size} bytes"
+    );
+    assert!(size > 2 * CEILING_KB * 1024);
+    let mut head = [0; 14];
+    dump.read_exact(&mut head).unwrap();
+    assert_eq!(&head, b"{\"/pages\": [\n{");
+    let mut tail = [0; 5];
+    dump.seek(SeekFrom::End(-5)).unwrap();
+    dump.read_exact(&mut tail).unwrap();
+    assert_eq!(&tail, b"}\n]}\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
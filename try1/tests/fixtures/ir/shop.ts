@@ -0,0 +1,65 @@
+// ssg:defaults @bandwidth 8mbps
+
+enum Status { Active = "active", Blocked = "blocked" }
+
+interface Address {
+  street: string;
+  /** @presentRate 0.8 */
+  zip?: string;
+}
+
+/**
+ * route /users version=2
+ * A registered user of the shop.
+ * @summary Users
+ * @operationId listUsers
+ * @concurrency version
+ * @auth apiKey: X-Key
+ */
+interface User {
+  /** @key */
+  handle: string;
+  /** @faker email */
+  email: string;
+  /** @min 18 @max 99 @int */
+  age: number;
+  /** @float precision=3 */
+  score: number;
+  /** @currency eur */
+  balance: number;
+  /** @asString */
+  visits: bigint;
+  status: Status;
+  /** @minDate 2020-01-01 @maxDate 2021-01-01 */
+  joined: Date;
+  address: Address;
+  /** @minItems 1 @maxItems 3 @emptyRate 0.1 */
+  tags: string[];
+  version: number;
+}
+
+interface Author {
+  name: string;
+}
+
+/**
+ * route /posts
+ * @scenario broken status=500 body={"error":"boom"} delay=5
+ * @soft-delete
+ * @consistency-lag 250ms
+ * @role editor
+ * @content-type application/vnd.shop+json
+ * @transform ./views/post.jq
+ */
+interface Post {
+  id: number;
+  title: string;
+  author: Author;
+  deletedAt?: Date;
+}
+
+// route /posts/feed
+type Feed = Post[];
+
+/** route /settings */
+export const settings = { currency: "EUR", pageSize: 20 };
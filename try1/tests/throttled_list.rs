@@ -0,0 +1,118 @@
+//! A huge `?count=` list read over a throttled connection is generated as
+//! it's sent, so the server's resident memory stays flat.
+#![cfg(target_os = "linux")]
+
+use std::{
+    fs,
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Several GB of JSON if it were built whole.
+const COUNT: u64 = 50_000_000;
+
+/// Read at the throttled rate before resident memory is compared.
+const READ: usize = 3 * 1024 * 1024;
+
+/// Most resident memory the server may gain while sending `READ`.
+const GROWTH_KB: u64 = 8 * 1024;
+
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+impl Server {
+    fn rss_kb(&self) -> u64 {
+        let status = fs::read_to_string(format!("/proc/{}/status", self.0.id())).unwrap();
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))
+            .and_then(|kb| kb.trim().trim_end_matches("kB").trim().parse().ok())
+            .unwrap()
+    }
+}
+
+fn connect(port: u16) -> TcpStream {
+    let started = Instant::now();
+    loop {
+        match TcpStream::connect(("127.0.0.1", port)) {
+            Ok(stream) => return stream,
+            Err(_) if started.elapsed() < Duration::from_secs(20) => {
+                thread::sleep(Duration::from_millis(50))
+            }
+            Err(err) => panic!("ssg never listened on {port}: {err}"),
+        }
+    }
+}
+
+#[test]
+fn a_throttled_huge_list_keeps_memory_flat() {
+    let dir = std::env::temp_dir().join(format!("ssg-throttled-list-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let source = "interface User { id: number; name: string; email: string; }\n\
+                  // route /users\n\
+                  type Users = User[];\n";
+    fs::write(dir.join("users.ts"), source).unwrap();
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let server = Server(
+        Command::new(env!("CARGO_BIN_EXE_ssg"))
+            .args(["--port", &port.to_string()])
+            .current_dir(&dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+
+    let mut stream = connect(port);
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    write!(
+        stream,
+        "GET /users?count={COUNT} HTTP/1.1\r\n\
+         Host: localhost\r\n\
+         X-Mock-Bandwidth: 1MB/s\r\n\
+         Connection: close\r\n\r\n"
+    )
+    .unwrap();
+
+    let mut buf = vec![0; 64 * 1024];
+    let mut read = 0;
+    let mut head = Vec::new();
+    let mut baseline = None;
+    let mut peak = 0;
+    while read < READ {
+        let n = stream.read(&mut buf).expect("the response stalled");
+        assert!(n > 0, "the response ended after {read} bytes");
+        if head.len() < 64 {
+            head.extend_from_slice(&buf[..n]);
+        }
+        read += n;
+        // The first chunk is out once the stream has started.
+        let rss = server.rss_kb();
+        baseline.get_or_insert(rss);
+        peak = peak.max(rss);
+    }
+    assert!(head.starts_with(b"HTTP/1.1 200 OK"), "{head:?}");
+    let baseline = baseline.unwrap();
+    assert!(
+        peak < baseline + GROWTH_KB,
+        "resident memory grew from {baseline} kB to {peak} kB"
+    );
+
+    drop(server);
+    fs::remove_dir_all(&dir).unwrap();
+}